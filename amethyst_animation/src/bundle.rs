@@ -4,8 +4,10 @@ use resources::AnimationSampling;
 use skinning::VertexSkinningSystem;
 use std::hash::Hash;
 use std::marker;
+use std::time::Duration;
 use systems::{
     AnimationControlSystem, AnimationProcessor, SamplerInterpolationSystem, SamplerProcessor,
+    TransformInterpolationSystem,
 };
 
 /// Bundle for vertex skinning
@@ -148,3 +150,42 @@ where
             .build(builder)
     }
 }
+
+/// Bundle for the interpolation of received transforms.
+///
+/// Will add `TransformInterpolationSystem` with the name `transform_interpolation_system`,
+/// interpolating the `Transform` of entities with an `InterpolationBuffer` at the given delay.
+/// The system needs to run before the `TransformSystem`, so add this bundle before the
+/// `TransformBundle`, and make the `TransformBundle` depend on it.
+pub struct InterpolationBundle<'a> {
+    delay: Duration,
+    dep: &'a [&'a str],
+}
+
+impl<'a> InterpolationBundle<'a> {
+    /// Create a new interpolation bundle
+    ///
+    /// ### Parameters:
+    ///
+    /// - `delay`: how far in the past the transforms are interpolated
+    pub fn new(delay: Duration) -> Self {
+        InterpolationBundle { delay, dep: &[] }
+    }
+
+    /// Set dependencies for the `TransformInterpolationSystem`
+    pub fn with_dep(mut self, dep: &'a [&'a str]) -> Self {
+        self.dep = dep;
+        self
+    }
+}
+
+impl<'a, 'b, 'c> SystemBundle<'a, 'b> for InterpolationBundle<'c> {
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
+        builder.add(
+            TransformInterpolationSystem::new(self.delay),
+            "transform_interpolation_system",
+            self.dep,
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use amethyst_core::specs::prelude::{Component, DenseVecStorage};
+use amethyst_core::Transform;
+use minterpolate::InterpolationFunction;
+use resources::AnimationSampling;
+use std::collections::VecDeque;
+use transform::TransformChannel;
+use util::SamplerPrimitive;
+
+/// Default number of samples kept by an `InterpolationBuffer`.
+const CAPACITY: usize = 8;
+
+/// Buffer of the last `Transform`s received for an entity.
+///
+/// Entities with this component have their `Transform` updated less often than the frame rate,
+/// for example by snapshots of a server. The `TransformInterpolationSystem` records every update
+/// of the `Transform` as a sample, and replaces it with the samples interpolated at a delay, so
+/// that the entity moves smoothly between them. Translation and scale are interpolated
+/// linearly, rotation spherically.
+#[derive(Clone, Debug)]
+pub struct InterpolationBuffer {
+    samples: VecDeque<(f64, Transform)>,
+    capacity: usize,
+}
+
+impl Default for InterpolationBuffer {
+    fn default() -> Self {
+        InterpolationBuffer::with_capacity(CAPACITY)
+    }
+}
+
+impl InterpolationBuffer {
+    /// Creates a buffer keeping the last 8 samples.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a buffer keeping the last `capacity` samples.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `capacity` is less than 2.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity >= 2, "Interpolation needs at least two samples");
+        InterpolationBuffer {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Returns the number of samples in the buffer.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns true if no sample was received yet.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Adds a sample received at `time`, in seconds, dropping the oldest one if the buffer is
+    /// full. Samples which are not newer than the last one are ignored, as interpolating between
+    /// two samples of the same time would divide by zero.
+    pub fn push(&mut self, time: f64, transform: Transform) {
+        if self.samples.back().map_or(false, |&(last, _)| time <= last) {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((time, transform));
+    }
+
+    /// Returns the `Transform` at `time`, in seconds.
+    ///
+    /// Before the first sample the first one is returned, and after the last sample the last
+    /// one, without extrapolating.
+    pub fn sample(&self, time: f64) -> Option<Transform> {
+        let first = self.samples.front()?;
+        let last = self.samples.back()?;
+        if time <= first.0 {
+            return Some(first.1.clone());
+        }
+        if time >= last.0 {
+            return Some(last.1.clone());
+        }
+
+        let inputs = self
+            .samples
+            .iter()
+            .map(|&(sample, _)| (sample - first.0) as f32)
+            .collect::<Vec<_>>();
+        let input = (time - first.0) as f32;
+        let mut transform = first.1.clone();
+        for &(channel, ref function, normalize) in &[
+            (TransformChannel::Translation, InterpolationFunction::Linear, false),
+            (TransformChannel::Rotation, InterpolationFunction::SphericalLinear, true),
+            (TransformChannel::Scale, InterpolationFunction::Linear, false),
+        ] {
+            let outputs = self
+                .samples
+                .iter()
+                .map(|&(_, ref sample)| sample.current_sample(&channel, &()))
+                .collect::<Vec<SamplerPrimitive<f32>>>();
+            let value = function.interpolate(input, &inputs, &outputs, normalize);
+            transform.apply_sample(&channel, &value, &());
+        }
+        Some(transform)
+    }
+}
+
+impl Component for InterpolationBuffer {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f32) -> Transform {
+        let mut transform = Transform::default();
+        transform.translation.x = x;
+        transform
+    }
+
+    fn assert_x(transform: Option<Transform>, x: f32) {
+        let transform = transform.expect("No sample");
+        assert!(
+            (transform.translation.x - x).abs() < 1e-5,
+            "Sampled x {} instead of {}",
+            transform.translation.x,
+            x
+        );
+        assert!(transform.rotation.s.is_finite() && transform.scale.x.is_finite());
+    }
+
+    #[test]
+    fn samples_nothing_without_samples() {
+        assert!(InterpolationBuffer::new().sample(1.0).is_none());
+    }
+
+    #[test]
+    fn holds_a_single_sample() {
+        let mut buffer = InterpolationBuffer::new();
+        buffer.push(1.0, at(2.0));
+        assert_x(buffer.sample(0.0), 2.0);
+        assert_x(buffer.sample(1.0), 2.0);
+        assert_x(buffer.sample(5.0), 2.0);
+    }
+
+    #[test]
+    fn interpolates_between_samples() {
+        let mut buffer = InterpolationBuffer::new();
+        buffer.push(1.0, at(0.0));
+        buffer.push(3.0, at(4.0));
+        buffer.push(4.0, at(0.0));
+        assert_x(buffer.sample(2.0), 2.0);
+        assert_x(buffer.sample(3.5), 2.0);
+    }
+
+    #[test]
+    fn clamps_to_the_oldest_and_newest_samples() {
+        let mut buffer = InterpolationBuffer::with_capacity(2);
+        buffer.push(1.0, at(1.0));
+        buffer.push(2.0, at(2.0));
+        buffer.push(3.0, at(3.0));
+        assert_eq!(buffer.len(), 2);
+        assert_x(buffer.sample(0.5), 2.0);
+        assert_x(buffer.sample(4.0), 3.0);
+    }
+
+    #[test]
+    fn ignores_samples_which_are_not_newer() {
+        let mut buffer = InterpolationBuffer::new();
+        buffer.push(1.0, at(0.0));
+        buffer.push(1.0, at(8.0));
+        buffer.push(0.5, at(8.0));
+        assert_eq!(buffer.len(), 1);
+        buffer.push(3.0, at(4.0));
+        assert_x(buffer.sample(2.0), 2.0);
+    }
+}
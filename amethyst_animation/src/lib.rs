@@ -60,7 +60,10 @@ extern crate serde;
 #[cfg(feature = "profiler")]
 extern crate thread_profiler;
 
-pub use self::bundle::{AnimationBundle, SamplingBundle, VertexSkinningBundle};
+pub use self::bundle::{
+    AnimationBundle, InterpolationBundle, SamplingBundle, VertexSkinningBundle,
+};
+pub use self::interpolation::InterpolationBuffer;
 pub use self::material::{MaterialChannel, MaterialPrimitive};
 pub use self::prefab::{
     AnimatablePrefab, AnimationHierarchyPrefab, AnimationPrefab, AnimationSetPrefab,
@@ -76,12 +79,14 @@ pub use self::skinning::{
 pub use self::sprite::{SpriteRenderChannel, SpriteRenderPrimitive};
 pub use self::systems::{
     AnimationControlSystem, AnimationProcessor, SamplerInterpolationSystem, SamplerProcessor,
+    TransformInterpolationSystem,
 };
 pub use self::transform::TransformChannel;
 pub use self::util::{get_animation_set, SamplerPrimitive};
 pub use minterpolate::{InterpolationFunction, InterpolationPrimitive};
 
 mod bundle;
+mod interpolation;
 mod material;
 mod prefab;
 mod resources;
@@ -0,0 +1,73 @@
+use amethyst_core::specs::prelude::{
+    InsertedFlag, Join, ModifiedFlag, Read, ReaderId, Resources, System, SystemData, WriteStorage,
+};
+use amethyst_core::{duration_to_secs_f64, Time, Transform};
+use hibitset::BitSet;
+use interpolation::InterpolationBuffer;
+use std::time::Duration;
+
+/// System for interpolating the `Transform` of entities with an `InterpolationBuffer`.
+///
+/// Every update of the `Transform` of those entities since the last run is recorded in their
+/// buffer, timed with the real time of `Time`. The `Transform` is then replaced by the samples
+/// interpolated at the current time minus the delay. The delay should cover the interval
+/// between two updates, plus some jitter, so that there is a sample on both sides of the
+/// interpolated time.
+///
+/// The changes made by this system are not recorded as samples. It needs to run before the
+/// `TransformSystem`.
+pub struct TransformInterpolationSystem {
+    delay: Duration,
+    received: BitSet,
+    inserted_id: Option<ReaderId<InsertedFlag>>,
+    modified_id: Option<ReaderId<ModifiedFlag>>,
+}
+
+impl TransformInterpolationSystem {
+    /// Creates a new system interpolating the transforms `delay` in the past.
+    pub fn new(delay: Duration) -> Self {
+        TransformInterpolationSystem {
+            delay,
+            received: BitSet::new(),
+            inserted_id: None,
+            modified_id: None,
+        }
+    }
+}
+
+impl<'a> System<'a> for TransformInterpolationSystem {
+    type SystemData = (
+        Read<'a, Time>,
+        WriteStorage<'a, InterpolationBuffer>,
+        WriteStorage<'a, Transform>,
+    );
+
+    fn run(&mut self, (time, mut buffers, mut transforms): Self::SystemData) {
+        self.received.clear();
+        transforms.populate_inserted(self.inserted_id.as_mut().unwrap(), &mut self.received);
+        transforms.populate_modified(self.modified_id.as_mut().unwrap(), &mut self.received);
+
+        let now = time.absolute_real_time_seconds();
+        for (buffer, transform, _) in (&mut buffers, &transforms, &self.received).join() {
+            buffer.push(now, transform.clone());
+        }
+
+        let delayed = now - duration_to_secs_f64(self.delay);
+        for (buffer, transform) in (&buffers, &mut transforms).join() {
+            if let Some(sample) = buffer.sample(delayed) {
+                *transform = sample;
+            }
+        }
+
+        // Skip the modifications made above, so they are not recorded as samples.
+        self.received.clear();
+        transforms.populate_modified(self.modified_id.as_mut().unwrap(), &mut self.received);
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        let mut transforms = WriteStorage::<Transform>::fetch(res);
+        self.inserted_id = Some(transforms.track_inserted());
+        self.modified_id = Some(transforms.track_modified());
+    }
+}
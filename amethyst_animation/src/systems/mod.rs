@@ -1,9 +1,11 @@
 pub use self::control::AnimationControlSystem;
+pub use self::interpolation::TransformInterpolationSystem;
 pub use self::sampling::SamplerInterpolationSystem;
 use amethyst_assets::Processor;
 use resources::{Animation, Sampler};
 
 mod control;
+mod interpolation;
 mod sampling;
 
 /// Asset storage processor for `Sampler`
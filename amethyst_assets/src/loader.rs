@@ -3,16 +3,29 @@ use rayon::ThreadPool;
 use std::borrow::Borrow;
 use std::hash::Hash;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 use storage::{AssetStorage, Handle, Processed};
 use {Asset, Directory, ErrorKind, Format, FormatValue, Progress, ResultExt, Source};
 
+/// Counts an import as done when dropped, even if the import panicked.
+struct PendingGuard(Arc<AtomicUsize>);
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 /// The asset loader, holding the sources and a reference to the `ThreadPool`.
 pub struct Loader {
     directory: Arc<Directory>,
     hot_reload: bool,
     pool: Arc<ThreadPool>,
     sources: FnvHashMap<String, Arc<Source>>,
+    pending: Arc<AtomicUsize>,
 }
 
 impl Loader {
@@ -27,6 +40,7 @@ impl Loader {
             hot_reload: true,
             pool,
             sources: Default::default(),
+            pending: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -49,6 +63,27 @@ impl Loader {
         self.hot_reload = value;
     }
 
+    /// Returns the number of assets still being imported by the worker threads.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Blocks until the worker threads imported every asset requested so far, or until the
+    /// timeout elapsed.
+    ///
+    /// Returns true if no import is left. The imported data still needs to be processed by the
+    /// `Processor` of its asset type.
+    pub fn flush(&self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while self.pending() > 0 {
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+
     /// Loads an asset with a given format from the default (directory) source.
     /// If you want to load from a custom source instead, use `load_from`.
     ///
@@ -140,10 +175,13 @@ impl Loader {
         let processed = storage.processed.clone();
 
         let hot_reload = self.hot_reload;
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        let pending = PendingGuard(self.pending.clone());
 
         let cl = move || {
             #[cfg(feature = "profiler")]
             profile_scope!("load_asset_from_worker");
+            let _pending = pending;
             let data = format
                 .import(name.clone(), source, options, hot_reload)
                 .chain_err(|| ErrorKind::Format(F::NAME));
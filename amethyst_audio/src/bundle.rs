@@ -11,7 +11,7 @@ use systems::DjSystem;
 /// Audio bundle
 ///
 /// Will only register the `AudioSink` and the `DjSystem` if an audio output is found.
-/// `DjSystem` will be registered with name "dj_system".
+/// `DjSystem` will be registered with name "dj_system", unless set with `with_name`.
 ///
 /// This will also add the asset processor for `Source`.
 ///
@@ -24,6 +24,7 @@ use systems::DjSystem;
 /// Panics during `DjSystem` registration if the bundle is applied twice.
 ///
 pub struct AudioBundle<'a, F, R> {
+    name: &'a str,
     dep: &'a [&'a str],
     marker: PhantomData<R>,
     picker: F,
@@ -33,12 +34,19 @@ impl<'a, F, R> AudioBundle<'a, F, R> {
     /// Create a new DJ bundle
     pub fn new(picker: F) -> Self {
         AudioBundle {
+            name: "dj_system",
             dep: &[],
             marker: PhantomData,
             picker,
         }
     }
 
+    /// Set the name the `DjSystem` is registered with
+    pub fn with_name(mut self, name: &'a str) -> Self {
+        self.name = name;
+        self
+    }
+
     /// Set dependencies for the `DjSystem`
     pub fn with_dep(mut self, dep: &'a [&'a str]) -> Self {
         self.dep = dep;
@@ -54,7 +62,8 @@ where
     fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
         builder.add(Processor::<Source>::new(), "source_processor", &[]);
         if default_output_device().is_some() {
-            builder.add(DjSystem::new(self.picker), "dj_system", self.dep);
+            let system = DjSystem::new(self.picker).with_name(self.name);
+            builder.add(system, self.name, self.dep);
         }
         Ok(())
     }
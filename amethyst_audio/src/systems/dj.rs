@@ -2,14 +2,19 @@ use amethyst_assets::AssetStorage;
 use amethyst_core::shred::{Resource, Resources};
 use amethyst_core::specs::common::Errors;
 use amethyst_core::specs::prelude::{Read, System, WriteExpect};
+use amethyst_core::SystemError;
 use output::init_output;
 use sink::AudioSink;
 use source::{Source, SourceHandle};
 use std::marker::PhantomData;
 
 /// Calls a closure if the `AudioSink` is empty.
+///
+/// Errors appending to the `AudioSink` are reported with the name of the system, "dj_system"
+/// unless set with `with_name`.
 pub struct DjSystem<F, R> {
     f: F,
+    name: String,
     marker: PhantomData<R>,
 }
 
@@ -21,9 +26,16 @@ impl<F, R> DjSystem<F, R> {
     pub fn new(f: F) -> Self {
         DjSystem {
             f,
+            name: "dj_system".to_string(),
             marker: PhantomData,
         }
     }
+
+    /// Sets the name the system is registered with, which its errors are reported with.
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = name.into();
+        self
+    }
 }
 
 impl<'a, F, R> System<'a> for DjSystem<F, R>
@@ -44,7 +56,10 @@ where
         if let Some(ref sink) = sink {
             if sink.empty() {
                 if let Some(source) = (&mut self.f)(&mut res).and_then(|h| storage.get(&h)) {
-                    errors.execute(|| sink.append(source));
+                    let name = &self.name;
+                    errors.execute(|| {
+                        sink.append(source).map_err(|err| SystemError::new(name.as_str(), err))
+                    });
                 }
             }
         }
@@ -1,9 +1,21 @@
-use specs::prelude::DispatcherBuilder;
+use specs::prelude::{DispatcherBuilder, World};
 
 error_chain!{}
 
+/// Cleanup of a bundle, run once by the `Application` when it shuts down.
+pub type Teardown<'b> = Box<FnMut(&mut World) + 'b>;
+
 /// A bundle of ECS components, resources and systems.
 pub trait SystemBundle<'a, 'b> {
     /// Build and add ECS resources, register components, add systems etc to the Application.
     fn build(self, dispatcher: &mut DispatcherBuilder<'a, 'b>) -> Result<()>;
+
+    /// Returns the cleanup to run when the application shuts down, if the bundle needs one.
+    ///
+    /// Called right before `build`, so the bundle can share state between the teardown and its
+    /// systems, like a flag telling them to close their connections. Teardowns run after the
+    /// states stopped, in the reverse order their bundles were added.
+    fn teardown(&mut self) -> Option<Teardown<'b>> {
+        None
+    }
 }
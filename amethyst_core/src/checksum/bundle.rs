@@ -0,0 +1,62 @@
+//! ECS checksum bundle
+
+use super::{Checksum, WorldChecksumSystem};
+use bundle::{Result, SystemBundle};
+use specs::prelude::{Component, DispatcherBuilder};
+
+/// Checksum bundle
+///
+/// Adds the thread-local `WorldChecksumSystem`, which records the checksum of the registered
+/// components in the `WorldChecksum` resource. Add it to the simulation stage of the game data,
+/// so the checksum is computed on every tick after the gameplay systems ran.
+///
+/// By default a checksum is computed every 10 ticks, and no component is registered.
+///
+/// ## Errors
+///
+/// No errors will be returned by this bundle.
+///
+pub struct ChecksumBundle {
+    system: WorldChecksumSystem,
+}
+
+impl Default for ChecksumBundle {
+    fn default() -> Self {
+        ChecksumBundle::new()
+    }
+}
+
+impl ChecksumBundle {
+    /// Create a new checksum bundle
+    pub fn new() -> Self {
+        ChecksumBundle {
+            system: WorldChecksumSystem::new(),
+        }
+    }
+
+    /// Set the number of ticks between two checksums
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `interval` is 0.
+    pub fn with_interval(mut self, interval: u64) -> Self {
+        self.system = self.system.with_interval(interval);
+        self
+    }
+
+    /// Include the components of type `C` in the checksum
+    pub fn with<C>(mut self) -> Self
+    where
+        C: Component + Checksum,
+    {
+        self.system = self.system.with::<C>();
+        self
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for ChecksumBundle {
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
+        builder.add_thread_local(self.system);
+        Ok(())
+    }
+}
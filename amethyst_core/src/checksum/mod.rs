@@ -0,0 +1,178 @@
+//! Checksums of the world state, used to detect lockstep clients drifting apart.
+
+pub use self::bundle::ChecksumBundle;
+pub use self::system::WorldChecksumSystem;
+
+use fixed::Fixed;
+use named::Named;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use transform::Transform;
+
+mod bundle;
+mod system;
+
+/// Number of checksums kept by `WorldChecksum`.
+const HISTORY: usize = 64;
+
+/// Data which contributes to the checksum of the world state.
+///
+/// Implement this for the components which must be the same on every lockstep client, and
+/// register them with the `ChecksumBundle`. Two values which are equal for the simulation must
+/// feed the same bytes into the hasher.
+pub trait Checksum {
+    /// Feeds the value into the given hasher.
+    fn checksum<H: Hasher>(&self, state: &mut H);
+}
+
+macro_rules! impl_checksum_hash {
+    ($($ty:ty),*) => {
+        $(
+            impl Checksum for $ty {
+                fn checksum<H: Hasher>(&self, state: &mut H) {
+                    self.hash(state);
+                }
+            }
+        )*
+    }
+}
+
+impl_checksum_hash!(bool, u8, u16, u32, u64, i8, i16, i32, i64, Fixed);
+
+impl Checksum for f32 {
+    fn checksum<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().hash(state);
+    }
+}
+
+impl Checksum for f64 {
+    fn checksum<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().hash(state);
+    }
+}
+
+impl Checksum for Named {
+    fn checksum<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+impl Checksum for Transform {
+    fn checksum<H: Hasher>(&self, state: &mut H) {
+        let rotation = &self.rotation;
+        for value in &[rotation.s, rotation.v.x, rotation.v.y, rotation.v.z] {
+            value.checksum(state);
+        }
+        for vector in &[self.scale, self.translation] {
+            for value in &[vector.x, vector.y, vector.z] {
+                value.checksum(state);
+            }
+        }
+    }
+}
+
+/// The checksums of the world state computed on the last simulation ticks.
+///
+/// Filled by the `WorldChecksumSystem`. The network layer sends the new checksums to the other
+/// clients, and compares the checksums they sent with the ones in the history.
+#[derive(Debug, Default)]
+pub struct WorldChecksum {
+    history: VecDeque<(u64, u64)>,
+    new: Vec<(u64, u64)>,
+}
+
+impl WorldChecksum {
+    /// Creates an empty history.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Records the checksum computed on a tick.
+    ///
+    /// The checksums recorded before for this tick and the ones after it are dropped, since the
+    /// simulation was rolled back.
+    pub fn record(&mut self, tick: u64, checksum: u64) {
+        while self.history.back().map_or(false, |&(t, _)| t >= tick) {
+            self.history.pop_back();
+        }
+        self.new.retain(|&(t, _)| t < tick);
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back((tick, checksum));
+        if self.new.len() == HISTORY {
+            self.new.remove(0);
+        }
+        self.new.push((tick, checksum));
+    }
+
+    /// Gets the checksum computed on the given tick, if it is still in the history.
+    pub fn get(&self, tick: u64) -> Option<u64> {
+        self.history
+            .iter()
+            .find(|&&(t, _)| t == tick)
+            .map(|&(_, checksum)| checksum)
+    }
+
+    /// Gets the tick and the checksum computed last.
+    pub fn latest(&self) -> Option<(u64, u64)> {
+        self.history.back().cloned()
+    }
+
+    /// Gets the oldest tick in the history.
+    pub fn oldest_tick(&self) -> Option<u64> {
+        self.history.front().map(|&(tick, _)| tick)
+    }
+
+    /// Removes and returns the checksums recorded since the last call, up to the size of the
+    /// history.
+    pub fn drain_new(&mut self) -> Vec<(u64, u64)> {
+        self.new.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fnv::FnvHasher;
+
+    fn checksum<T: Checksum>(value: &T) -> u64 {
+        let mut hasher = FnvHasher::default();
+        value.checksum(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn transform_checksum_follows_state() {
+        let mut transform = Transform::default();
+        let original = checksum(&transform);
+        assert_eq!(original, checksum(&transform.clone()));
+
+        transform.translation.x += 1.0;
+        assert_ne!(original, checksum(&transform));
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut checksums = WorldChecksum::new();
+        for tick in 0..(HISTORY as u64 + 1) {
+            checksums.record(tick, tick * 2);
+        }
+        assert_eq!(checksums.get(0), None);
+        assert_eq!(checksums.get(HISTORY as u64), Some(HISTORY as u64 * 2));
+        assert_eq!(checksums.drain_new().len(), HISTORY + 1);
+        assert!(checksums.drain_new().is_empty());
+    }
+
+    #[test]
+    fn rolled_back_ticks_are_replaced() {
+        let mut checksums = WorldChecksum::new();
+        for tick in 1..4 {
+            checksums.record(tick, tick);
+        }
+        checksums.record(2, 20);
+        assert_eq!(checksums.get(2), Some(20));
+        assert_eq!(checksums.get(3), None);
+        assert_eq!(checksums.drain_new(), vec![(1, 1), (2, 20)]);
+    }
+}
@@ -0,0 +1,105 @@
+//! World checksum system.
+
+use super::{Checksum, WorldChecksum};
+use fnv::FnvHasher;
+use specs::prelude::{Component, Entities, Join, ReadStorage, Resources, RunNow, SystemData};
+use std::hash::{Hash, Hasher};
+use timing::SimulationTime;
+
+type ComponentHasher = fn(&Resources, &mut FnvHasher);
+type ComponentSetup = fn(&mut Resources);
+
+/// Computes the checksum of the registered components every `interval` simulation ticks, and
+/// records it in the `WorldChecksum` resource.
+///
+/// Components are hashed in the order they were registered, and the entities of a component in
+/// the order of their ids, so clients with the same state compute the same checksum.
+///
+/// This is a thread-local system, so that it runs after all the other systems of the
+/// dispatcher. Add it to the simulation stage of the game data with the `ChecksumBundle`.
+pub struct WorldChecksumSystem {
+    interval: u64,
+    hashers: Vec<(ComponentHasher, ComponentSetup)>,
+}
+
+impl Default for WorldChecksumSystem {
+    fn default() -> Self {
+        WorldChecksumSystem::new()
+    }
+}
+
+impl WorldChecksumSystem {
+    /// Creates a system computing a checksum every 10 ticks, without any component.
+    pub fn new() -> Self {
+        WorldChecksumSystem {
+            interval: 10,
+            hashers: Vec::new(),
+        }
+    }
+
+    /// Sets the number of ticks between two checksums.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `interval` is 0.
+    pub fn with_interval(mut self, interval: u64) -> Self {
+        assert!(interval > 0, "Checksum interval must be at least one tick");
+        self.interval = interval;
+        self
+    }
+
+    /// Includes the components of type `C` in the checksum.
+    pub fn with<C>(mut self) -> Self
+    where
+        C: Component + Checksum,
+    {
+        self.hashers.push((
+            hash_components::<C> as ComponentHasher,
+            setup_components::<C> as ComponentSetup,
+        ));
+        self
+    }
+}
+
+fn hash_components<C>(res: &Resources, state: &mut FnvHasher)
+where
+    C: Component + Checksum,
+{
+    let (entities, components) = <(Entities, ReadStorage<C>)>::fetch(res);
+    for (entity, component) in (&*entities, &components).join() {
+        entity.id().hash(state);
+        component.checksum(state);
+    }
+}
+
+fn setup_components<C>(res: &mut Resources)
+where
+    C: Component + Checksum,
+{
+    <(Entities, ReadStorage<C>)>::setup(res);
+}
+
+impl<'a> RunNow<'a> for WorldChecksumSystem {
+    fn run_now(&mut self, res: &'a Resources) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("world_checksum_system");
+
+        let tick = res.fetch::<SimulationTime>().tick();
+        if tick % self.interval != 0 {
+            return;
+        }
+        let mut state = FnvHasher::default();
+        for &(hasher, _) in &self.hashers {
+            hasher(res, &mut state);
+        }
+        res.fetch_mut::<WorldChecksum>().record(tick, state.finish());
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        res.entry::<SimulationTime>().or_insert_with(Default::default);
+        res.entry::<WorldChecksum>().or_insert_with(Default::default);
+        for &(_, setup) in &self.hashers {
+            setup(res);
+        }
+    }
+}
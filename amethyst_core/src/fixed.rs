@@ -0,0 +1,156 @@
+//! Fixed-point numbers for deterministic simulations.
+
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+const FRACTION_BITS: u32 = 16;
+const UNIT: i32 = 1 << FRACTION_BITS;
+
+/// A signed 16.16 fixed-point number.
+///
+/// Floating point results can differ between compilers, CPUs and optimisation levels, which
+/// makes lockstep clients drift apart. Arithmetic on `Fixed` only uses integer operations, so
+/// it gives the same results everywhere. Overflowing values wrap around.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    /// Zero.
+    pub const ZERO: Fixed = Fixed(0);
+
+    /// One.
+    pub const ONE: Fixed = Fixed(UNIT);
+
+    /// Creates a fixed-point number from its raw bits.
+    pub fn from_bits(bits: i32) -> Fixed {
+        Fixed(bits)
+    }
+
+    /// Gets the raw bits of the number.
+    pub fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    /// Creates a fixed-point number from an integer.
+    pub fn from_int(value: i16) -> Fixed {
+        Fixed(i32::from(value) << FRACTION_BITS)
+    }
+
+    /// Creates the fixed-point number closest to a float.
+    ///
+    /// Only use this on values which are the same for every client, like constants.
+    pub fn from_f32(value: f32) -> Fixed {
+        Fixed((value * UNIT as f32).round() as i32)
+    }
+
+    /// Converts the number to a float, for example for rendering.
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / UNIT as f32
+    }
+
+    /// Rounds the number towards negative infinity, to an integer.
+    pub fn floor(self) -> i32 {
+        self.0 >> FRACTION_BITS
+    }
+
+    /// Gets the absolute value of the number.
+    pub fn abs(self) -> Fixed {
+        Fixed(self.0.wrapping_abs())
+    }
+}
+
+impl fmt::Debug for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Fixed({})", self.to_f32())
+    }
+}
+
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_f32())
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_add(other.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, other: Fixed) -> Fixed {
+        Fixed(((i64::from(self.0) * i64::from(other.0)) >> FRACTION_BITS) as i32)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+
+    /// ## Panics
+    ///
+    /// Panics when dividing by zero.
+    fn div(self, other: Fixed) -> Fixed {
+        Fixed(((i64::from(self.0) << FRACTION_BITS) / i64::from(other.0)) as i32)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(self.0.wrapping_neg())
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, other: Fixed) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, other: Fixed) {
+        *self = *self - other;
+    }
+}
+
+impl MulAssign for Fixed {
+    fn mul_assign(&mut self, other: Fixed) {
+        *self = *self * other;
+    }
+}
+
+impl DivAssign for Fixed {
+    fn div_assign(&mut self, other: Fixed) {
+        *self = *self / other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fixed;
+
+    #[test]
+    fn arithmetic() {
+        let a = Fixed::from_int(3);
+        let b = Fixed::from_f32(0.5);
+        assert_eq!(a + b, Fixed::from_f32(3.5));
+        assert_eq!(a - b, Fixed::from_f32(2.5));
+        assert_eq!(a * b, Fixed::from_f32(1.5));
+        assert_eq!(a / b, Fixed::from_int(6));
+        assert_eq!(-a, Fixed::from_int(-3));
+        assert_eq!((-b).floor(), -1);
+    }
+}
@@ -8,6 +8,7 @@ pub extern crate specs;
 extern crate error_chain;
 extern crate fnv;
 extern crate hibitset;
+#[macro_use]
 extern crate log;
 extern crate rayon;
 #[macro_use]
@@ -21,17 +22,23 @@ extern crate thread_profiler;
 #[cfg(all(target_os = "emscripten", not(no_threading)))]
 compile_error!("the cfg flag \"no_threading\" is required when building for emscripten");
 
+pub use self::fixed::Fixed;
 pub use self::named::{Named, WithNamed};
-pub use bundle::{Error, ErrorKind, Result, SystemBundle};
+pub use bundle::{Error, ErrorKind, Result, SystemBundle, Teardown};
 pub use orientation::Orientation;
 use std::sync::Arc;
+pub use system_error::SystemError;
 pub use timing::*;
 pub use transform::*;
 
 pub mod bundle;
+pub mod checksum;
+mod fixed;
 pub mod frame_limiter;
 mod named;
 mod orientation;
+pub mod snapshot;
+mod system_error;
 pub mod timing;
 pub mod transform;
 
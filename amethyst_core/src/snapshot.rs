@@ -0,0 +1,360 @@
+//! Snapshots of the world state, used to roll the simulation back to an earlier tick.
+
+use specs::prelude::{
+    Component, Entities, Entity, Join, ReadStorage, Resources, SystemData, WriteStorage,
+};
+use std::any::Any;
+use std::collections::VecDeque;
+
+/// Default number of snapshots kept by a `Rollback`.
+const CAPACITY: usize = 64;
+
+type SaveFn = fn(&Resources) -> Box<Any + Send + Sync>;
+type RestoreFn = fn(&Resources, &(Any + Send + Sync));
+type SetupFn = fn(&mut Resources);
+
+/// The state of the registered components and resources after a simulation tick.
+pub struct WorldSnapshot {
+    tick: u64,
+    data: Vec<Box<Any + Send + Sync>>,
+}
+
+impl WorldSnapshot {
+    /// Gets the tick after which the snapshot was taken.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+}
+
+/// The components and resources which are part of a `WorldSnapshot`.
+///
+/// Register every component and resource the simulation systems write to. Components are saved
+/// along with the entity they belong to. Entities are not created or deleted by a restore, so
+/// restored components of an entity deleted in the meantime are dropped.
+#[derive(Default)]
+pub struct SnapshotRegistry {
+    entries: Vec<(SaveFn, RestoreFn, SetupFn)>,
+}
+
+impl SnapshotRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers a component.
+    pub fn with<C>(mut self) -> Self
+    where
+        C: Component + Clone + Send + Sync,
+    {
+        self.entries.push((
+            save_component::<C>,
+            restore_component::<C>,
+            setup_component::<C>,
+        ));
+        self
+    }
+
+    /// Registers a resource.
+    pub fn with_resource<R>(mut self) -> Self
+    where
+        R: Clone + Default + Send + Sync + 'static,
+    {
+        self.entries.push((
+            save_resource::<R>,
+            restore_resource::<R>,
+            setup_resource::<R>,
+        ));
+        self
+    }
+
+    /// Returns true if nothing is registered.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds the storages and resources of the registry to the world.
+    pub fn setup(&self, res: &mut Resources) {
+        for &(_, _, setup) in &self.entries {
+            setup(res);
+        }
+    }
+
+    /// Saves the current state of the world.
+    pub fn save(&self, tick: u64, res: &Resources) -> WorldSnapshot {
+        WorldSnapshot {
+            tick,
+            data: self.entries.iter().map(|&(save, _, _)| save(res)).collect(),
+        }
+    }
+
+    /// Restores the state of the world saved in the snapshot.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the snapshot was not saved by this registry.
+    pub fn restore(&self, snapshot: &WorldSnapshot, res: &Resources) {
+        assert_eq!(snapshot.data.len(), self.entries.len());
+        for (&(_, restore, _), data) in self.entries.iter().zip(&snapshot.data) {
+            restore(res, &**data);
+        }
+    }
+}
+
+fn save_component<C>(res: &Resources) -> Box<Any + Send + Sync>
+where
+    C: Component + Clone + Send + Sync,
+{
+    let (entities, storage) = <(Entities, ReadStorage<C>)>::fetch(res);
+    let saved = (&*entities, &storage)
+        .join()
+        .map(|(entity, component)| (entity, component.clone()))
+        .collect::<Vec<_>>();
+    Box::new(saved)
+}
+
+fn restore_component<C>(res: &Resources, data: &(Any + Send + Sync))
+where
+    C: Component + Clone + Send + Sync,
+{
+    let saved = data
+        .downcast_ref::<Vec<(Entity, C)>>()
+        .expect("Snapshot data doesn't match the registered component");
+    let (entities, mut storage) = <(Entities, WriteStorage<C>)>::fetch(res);
+    let current = (&*entities, &storage)
+        .join()
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    for entity in current {
+        storage.remove(entity);
+    }
+    for &(entity, ref component) in saved {
+        if entities.is_alive(entity) {
+            storage
+                .insert(entity, component.clone())
+                .expect("Failed to restore the component of a live entity");
+        }
+    }
+}
+
+fn setup_component<C>(res: &mut Resources)
+where
+    C: Component + Clone + Send + Sync,
+{
+    <(Entities, ReadStorage<C>)>::setup(res);
+}
+
+fn save_resource<R>(res: &Resources) -> Box<Any + Send + Sync>
+where
+    R: Clone + Default + Send + Sync + 'static,
+{
+    Box::new(res.fetch::<R>().clone())
+}
+
+fn restore_resource<R>(res: &Resources, data: &(Any + Send + Sync))
+where
+    R: Clone + Default + Send + Sync + 'static,
+{
+    let saved = data
+        .downcast_ref::<R>()
+        .expect("Snapshot data doesn't match the registered resource");
+    *res.fetch_mut::<R>() = saved.clone();
+}
+
+fn setup_resource<R>(res: &mut Resources)
+where
+    R: Clone + Default + Send + Sync + 'static,
+{
+    res.entry::<R>().or_insert_with(R::default);
+}
+
+/// Asks the `Rollback` to drop its snapshots before the next tick.
+///
+/// Request it when the world is replaced by a state which doesn't follow from the ticks simulated
+/// so far, like a world state loaded when joining a match in progress, so that no tick is rolled
+/// back to a snapshot from before the change.
+#[derive(Debug, Default)]
+pub struct RollbackReset {
+    requested: bool,
+}
+
+impl RollbackReset {
+    /// Drops the snapshots before the next tick.
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// Returns true once after a reset was requested.
+    pub fn take(&mut self) -> bool {
+        let requested = self.requested;
+        self.requested = false;
+        requested
+    }
+}
+
+/// Keeps the snapshots of the last simulation ticks, to simulate them again.
+///
+/// The game data calls `before_tick` and `after_tick` around every simulation tick. When a tick
+/// which was simulated before comes up again, because the `SimulationTime` was rewound, the
+/// world is first restored to the snapshot of the tick before it. The snapshots are dropped
+/// when a reset is requested through the `RollbackReset` resource.
+pub struct Rollback {
+    registry: SnapshotRegistry,
+    snapshots: VecDeque<WorldSnapshot>,
+    capacity: usize,
+}
+
+impl Rollback {
+    /// Creates a rollback history saving the state registered in `registry`.
+    pub fn new(registry: SnapshotRegistry) -> Self {
+        Rollback {
+            registry,
+            snapshots: VecDeque::new(),
+            capacity: CAPACITY,
+        }
+    }
+
+    /// Sets the number of snapshots kept, which limits how many ticks can be rolled back.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "A rollback needs to keep at least one snapshot");
+        self.capacity = capacity;
+        self
+    }
+
+    /// Adds the storages and resources of the registry, and the `RollbackReset`, to the world.
+    pub fn setup(&self, res: &mut Resources) {
+        self.registry.setup(res);
+        res.entry::<RollbackReset>()
+            .or_insert_with(RollbackReset::default);
+    }
+
+    /// Drops every snapshot, so that no tick simulated so far can be rolled back to.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Gets the oldest tick the world can be restored to.
+    pub fn oldest_tick(&self) -> Option<u64> {
+        self.snapshots.front().map(WorldSnapshot::tick)
+    }
+
+    /// Prepares the world for simulating the given tick.
+    ///
+    /// Returns true if the tick was simulated before and the world was restored to the state
+    /// after the previous tick.
+    pub fn before_tick(&mut self, tick: u64, res: &Resources) -> bool {
+        if res.fetch_mut::<RollbackReset>().take() {
+            self.clear();
+        }
+        if self.snapshots.is_empty() {
+            let snapshot = self.registry.save(tick - 1, res);
+            self.snapshots.push_back(snapshot);
+            return false;
+        }
+        if self.snapshots.back().map_or(true, |snapshot| snapshot.tick < tick) {
+            return false;
+        }
+        while self.snapshots.back().map_or(false, |snapshot| snapshot.tick >= tick) {
+            self.snapshots.pop_back();
+        }
+        match self.snapshots.back() {
+            Some(snapshot) if snapshot.tick == tick - 1 => {
+                self.registry.restore(snapshot, res);
+                true
+            }
+            _ => {
+                error!(
+                    "Can't roll back to tick {}, it is older than the snapshots kept",
+                    tick
+                );
+                false
+            }
+        }
+    }
+
+    /// Saves the state of the world after the given tick.
+    pub fn after_tick(&mut self, tick: u64, res: &Resources) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        let snapshot = self.registry.save(tick, res);
+        self.snapshots.push_back(snapshot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::prelude::{Builder, World};
+    use transform::Transform;
+
+    fn position(world: &World, entity: Entity) -> i32 {
+        world
+            .read_storage::<Transform>()
+            .get(entity)
+            .unwrap()
+            .translation
+            .x as i32
+    }
+
+    fn simulate(world: &World, entity: Entity) {
+        world
+            .write_storage::<Transform>()
+            .get_mut(entity)
+            .unwrap()
+            .translation
+            .x += 1.0;
+        *world.write_resource::<u64>() += 1;
+    }
+
+    #[test]
+    fn restores_the_previous_tick() {
+        let mut world = World::new();
+        let mut rollback = Rollback::new(
+            SnapshotRegistry::new()
+                .with::<Transform>()
+                .with_resource::<u64>(),
+        );
+        rollback.setup(&mut world.res);
+        let entity = world.create_entity().with(Transform::default()).build();
+
+        for tick in 1..4 {
+            assert!(!rollback.before_tick(tick, &world.res));
+            simulate(&world, entity);
+            rollback.after_tick(tick, &world.res);
+        }
+        assert_eq!(position(&world, entity), 3);
+
+        assert!(rollback.before_tick(2, &world.res));
+        assert_eq!(position(&world, entity), 1);
+        assert_eq!(*world.read_resource::<u64>(), 1);
+        assert_eq!(rollback.oldest_tick(), Some(0));
+    }
+
+    #[test]
+    fn drops_the_snapshots_on_reset() {
+        let mut world = World::new();
+        let mut rollback = Rollback::new(
+            SnapshotRegistry::new()
+                .with::<Transform>()
+                .with_resource::<u64>(),
+        );
+        rollback.setup(&mut world.res);
+        let entity = world.create_entity().with(Transform::default()).build();
+
+        for tick in 1..4 {
+            rollback.before_tick(tick, &world.res);
+            simulate(&world, entity);
+            rollback.after_tick(tick, &world.res);
+        }
+        world.write_resource::<RollbackReset>().request();
+
+        assert!(!rollback.before_tick(2, &world.res));
+        assert_eq!(position(&world, entity), 3);
+        assert_eq!(rollback.oldest_tick(), Some(1));
+    }
+}
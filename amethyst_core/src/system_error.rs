@@ -0,0 +1,94 @@
+//! Errors reported by systems, with the name of the system.
+
+use specs::error::BoxedErr;
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// An error reported by a system through the `Errors` resource, tagged with the name the system
+/// was registered with.
+///
+/// Systems report their errors with the system name by adding a `SystemError` to `Errors`:
+///
+/// ```rust,ignore
+/// errors.execute(|| sink.append(source).map_err(|err| SystemError::new("dj_system", err)));
+/// ```
+///
+/// The `Application` turns every error it collects from `Errors` into a `SystemError`, without
+/// a system name for the errors which were added directly.
+#[derive(Debug)]
+pub struct SystemError {
+    system: Option<String>,
+    error: BoxedErr,
+}
+
+impl SystemError {
+    /// Creates an error reported by the system registered as `system`.
+    pub fn new<S, E>(system: S, error: E) -> Self
+    where
+        S: Into<String>,
+        E: StdError + Send + Sync + 'static,
+    {
+        SystemError {
+            system: Some(system.into()),
+            error: BoxedErr::new(error),
+        }
+    }
+
+    /// Returns the name of the system which reported the error, if it is known.
+    pub fn system(&self) -> Option<&str> {
+        self.system.as_ref().map(|system| system.as_str())
+    }
+
+    /// Returns the error reported by the system.
+    pub fn error(&self) -> &(StdError + Send + Sync + 'static) {
+        &*self.error.0
+    }
+}
+
+impl From<BoxedErr> for SystemError {
+    fn from(error: BoxedErr) -> Self {
+        match error.0.downcast::<SystemError>() {
+            Ok(error) => *error,
+            Err(error) => SystemError {
+                system: None,
+                error: BoxedErr(error),
+            },
+        }
+    }
+}
+
+impl Display for SystemError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self.system {
+            Some(ref system) => write!(f, "System `{}` failed: {}", system, self.error),
+            None => write!(f, "A system failed: {}", self.error),
+        }
+    }
+}
+
+impl StdError for SystemError {
+    fn description(&self) -> &str {
+        self.error.0.description()
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        Some(&*self.error.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Error as IoError, ErrorKind};
+
+    #[test]
+    fn keeps_the_system_name_through_errors() {
+        let error = SystemError::new("dj_system", IoError::new(ErrorKind::Other, "no sink"));
+        let error = SystemError::from(BoxedErr::new(error));
+        assert_eq!(error.system(), Some("dj_system"));
+        assert_eq!(error.to_string(), "System `dj_system` failed: no sink");
+
+        let error = SystemError::from(BoxedErr::new(IoError::new(ErrorKind::Other, "no sink")));
+        assert_eq!(error.system(), None);
+    }
+}
@@ -183,6 +183,231 @@ impl Default for Time {
     }
 }
 
+/// Where the ticks of the simulation come from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TickSource {
+    /// The simulation ticks at a fixed rate, measured with the wall clock.
+    Local,
+    /// Ticks are released by the network layer, one per confirmed frame. Several ticks can be
+    /// released at once, for example to catch up with the server.
+    Network,
+}
+
+/// Timing of the deterministic game simulation.
+///
+/// Unlike `Time`, which measures the wall-clock time between rendered frames, the simulation
+/// advances in ticks of a fixed duration. When playing over the network, the tick number is the
+/// number of the last frame confirmed by the server, so every client simulates the same ticks
+/// with the same delta. Gameplay systems which must stay in step between clients should use
+/// this resource instead of `Time`.
+///
+/// The `Application` runs the simulation systems of the game data once per tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimulationTime {
+    /// Where the ticks come from.
+    source: TickSource,
+    /// Number of the last simulated tick. There is no tick 0.
+    tick: u64,
+    /// Duration of a tick.
+    tick_time: Duration,
+    /// Duration of a tick in seconds.
+    tick_seconds: f32,
+    /// Whether the simulation advanced during the current frame.
+    advanced: bool,
+    /// Tick to simulate up to, released by the network or again after a rewind.
+    target: u64,
+    /// Tick the simulation was rewound to, which is simulated again on the next frame.
+    rewound: Option<u64>,
+    /// Wall-clock time accumulated towards the next local tick.
+    accumulated: Duration,
+}
+
+impl SimulationTime {
+    /// Gets where the ticks of the simulation come from.
+    pub fn source(&self) -> TickSource {
+        self.source
+    }
+
+    /// Gets the number of the last simulated tick.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Gets the duration of a tick.
+    pub fn tick_time(&self) -> Duration {
+        self.tick_time
+    }
+
+    /// Gets the duration of a tick in seconds.
+    pub fn tick_seconds(&self) -> f32 {
+        self.tick_seconds
+    }
+
+    /// Returns true if the simulation advanced by a tick during the current frame.
+    pub fn advanced(&self) -> bool {
+        self.advanced
+    }
+
+    /// Gets the simulated time elapsed during the current frame in seconds.
+    ///
+    /// This is the tick duration if the simulation advanced during the frame, and 0 otherwise.
+    pub fn delta_seconds(&self) -> f32 {
+        if self.advanced {
+            self.tick_seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Gets the simulated time elapsed during the current frame.
+    pub fn delta_time(&self) -> Duration {
+        if self.advanced {
+            self.tick_time
+        } else {
+            Duration::from_secs(0)
+        }
+    }
+
+    /// Gets the simulated time since the start of the game.
+    pub fn absolute_time(&self) -> Duration {
+        nanos_to_duration(duration_to_nanos(self.tick_time) * self.tick)
+    }
+
+    /// Sets both `tick_time` and `tick_seconds` based on the duration given.
+    pub fn set_tick_time(&mut self, time: Duration) {
+        self.tick_seconds = duration_to_secs(time);
+        self.tick_time = time;
+    }
+
+    /// Sets where the ticks of the simulation come from.
+    ///
+    /// The network layer sets this when it is set up; it defaults to `TickSource::Local`.
+    pub fn set_source(&mut self, source: TickSource) {
+        self.source = source;
+    }
+
+    /// Advances the simulation by one tick.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn advance(&mut self) {
+        self.tick += 1;
+        self.advanced = true;
+    }
+
+    /// Releases every tick up to the given one, as numbered by the network, to be simulated by
+    /// the next calls to `next_tick`.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn advance_to(&mut self, tick: u64) {
+        self.advanced = tick > self.target.max(self.tick);
+        self.target = self.target.max(tick);
+    }
+
+    /// Gets the number of the last tick released by the network, which is simulated by the end
+    /// of the current frame.
+    pub fn target(&self) -> u64 {
+        self.target.max(self.tick)
+    }
+
+    /// Rewinds the simulation, so that the given tick and all the ticks simulated after it are
+    /// simulated again on the next frame.
+    ///
+    /// The game data restores the state of the world saved after the previous tick, see
+    /// `amethyst_core::snapshot::Rollback`. Does nothing if the tick was not simulated yet.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn rewind(&mut self, tick: u64) {
+        if tick > 0 && tick <= self.tick {
+            self.rewound = Some(self.rewound.map_or(tick, |rewound| rewound.min(tick)));
+        }
+    }
+
+    /// Jumps to the given tick without simulating the ticks before it, once the state of the
+    /// world after that tick was loaded, for example from another client.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn jump_to(&mut self, tick: u64) {
+        self.tick = tick;
+        self.target = tick;
+        self.rewound = None;
+        self.advanced = false;
+    }
+
+    /// Accumulates the wall-clock time elapsed during a frame, towards the next local tick.
+    ///
+    /// Does nothing but stalling the simulation with `TickSource::Network`.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn accumulate(&mut self, elapsed: Duration) {
+        if self.source == TickSource::Local {
+            self.advanced = false;
+            self.accumulated += elapsed;
+        }
+    }
+
+    /// Moves to the next tick which is due, returning false if there is none.
+    ///
+    /// With `TickSource::Local`, every tick duration of accumulated time makes a tick due. With
+    /// `TickSource::Network`, every tick up to the one released by `advance_to` is due, so a
+    /// client which fell behind catches up within a single frame. After a rewind, every tick
+    /// from the one rewound to is due again first.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn next_tick(&mut self) -> bool {
+        if let Some(tick) = self.rewound.take() {
+            self.target = self.target.max(self.tick);
+            self.tick = tick;
+            self.advanced = true;
+            return true;
+        }
+        if self.tick < self.target {
+            self.tick += 1;
+            self.advanced = true;
+            return true;
+        }
+        match self.source {
+            TickSource::Local => {
+                if self.accumulated < self.tick_time {
+                    return false;
+                }
+                self.accumulated -= self.tick_time;
+                self.advance();
+                true
+            }
+            TickSource::Network => false,
+        }
+    }
+
+    /// Indicates the simulation did not advance during the current frame.
+    ///
+    /// This should only be called by the engine.  Bad things might happen if you call this in
+    /// your game.
+    pub fn stall(&mut self) {
+        self.advanced = false;
+    }
+}
+
+impl Default for SimulationTime {
+    fn default() -> SimulationTime {
+        SimulationTime {
+            source: TickSource::Local,
+            tick: 0,
+            tick_time: Duration::from_millis(20),
+            tick_seconds: 0.02,
+            advanced: false,
+            target: 0,
+            rewound: None,
+            accumulated: Duration::from_secs(0),
+        }
+    }
+}
+
 /// A stopwatch which accurately measures elapsed time.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Stopwatch {
@@ -254,7 +479,7 @@ impl Stopwatch {
 // Unit tests
 #[cfg(test)]
 mod tests {
-    use super::Stopwatch;
+    use super::{SimulationTime, Stopwatch, TickSource};
     use std::thread;
     use std::time::Duration;
 
@@ -324,6 +549,100 @@ mod tests {
         );
     }
 
+    #[test]
+    fn simulation_advances_by_fixed_ticks() {
+        let mut time = SimulationTime::default();
+        time.set_tick_time(Duration::from_millis(50));
+        assert_eq!(time.delta_time(), Duration::from_secs(0));
+
+        time.advance_to(1);
+        time.advance_to(2);
+        while time.next_tick() {}
+        assert_eq!(time.tick(), 2);
+        assert_eq!(time.delta_time(), Duration::from_millis(50));
+        assert_eq!(time.absolute_time(), Duration::from_millis(100));
+
+        time.stall();
+        assert_eq!(time.delta_time(), Duration::from_secs(0));
+        assert_eq!(time.tick(), 2);
+    }
+
+    #[test]
+    fn local_ticks_follow_the_wall_clock() {
+        let mut time = SimulationTime::default();
+        time.set_tick_time(Duration::from_millis(20));
+        time.accumulate(Duration::from_millis(50));
+        assert!(time.next_tick());
+        assert!(time.next_tick());
+        assert!(!time.next_tick());
+        assert_eq!(time.tick(), 2);
+
+        time.accumulate(Duration::from_millis(10));
+        assert!(time.next_tick());
+        assert_eq!(time.tick(), 3);
+    }
+
+    #[test]
+    fn network_ticks_are_released_once() {
+        let mut time = SimulationTime::default();
+        time.set_source(TickSource::Network);
+        time.accumulate(Duration::from_secs(1));
+        assert!(!time.next_tick());
+
+        time.advance_to(1);
+        assert!(time.next_tick());
+        assert!(!time.next_tick());
+        assert_eq!(time.tick(), 1);
+
+        time.advance_to(4);
+        assert_eq!(time.target(), 4);
+        assert!(time.next_tick());
+        assert!(time.next_tick());
+        assert!(time.next_tick());
+        assert!(!time.next_tick());
+        assert_eq!(time.tick(), 4);
+    }
+
+    #[test]
+    fn rewound_ticks_are_simulated_again() {
+        let mut time = SimulationTime::default();
+        time.set_source(TickSource::Network);
+        time.advance_to(3);
+        while time.next_tick() {}
+        time.rewind(4);
+        assert!(!time.next_tick());
+
+        time.rewind(2);
+        assert!(time.next_tick());
+        assert_eq!(time.tick(), 2);
+        assert!(time.next_tick());
+        assert_eq!(time.tick(), 3);
+        assert!(!time.next_tick());
+
+        time.rewind(3);
+        time.advance_to(4);
+        assert!(time.next_tick());
+        assert_eq!(time.tick(), 3);
+        assert!(time.next_tick());
+        assert_eq!(time.tick(), 4);
+        assert!(!time.next_tick());
+    }
+
+    #[test]
+    fn jumps_without_simulating() {
+        let mut time = SimulationTime::default();
+        time.set_source(TickSource::Network);
+        time.advance_to(3);
+        time.rewind(2);
+        time.jump_to(40);
+        assert!(!time.next_tick());
+        assert_eq!(time.tick(), 40);
+
+        time.advance_to(41);
+        assert!(time.next_tick());
+        assert_eq!(time.tick(), 41);
+    }
+
     // test that multiple start-stop cycles are cumulative
     #[test]
     fn stop_start() {
@@ -10,7 +10,7 @@ use serde::Serialize;
 use std::hash::Hash;
 use std::path::Path;
 use std::result::Result as StdResult;
-use {Bindings, InputSystem};
+use {Bindings, InputSource, InputSystem, Replay, ReplaySystem};
 
 /// Bundle for adding the `InputHandler`.
 ///
@@ -24,6 +24,9 @@ use {Bindings, InputSystem};
 ///
 /// String is appropriate for either of these if you don't know what to use.
 ///
+/// By default the input of the local window is used, see `with_source` to play in lockstep,
+/// and `with_replay` to replay a recorded match.
+///
 /// ## Errors
 ///
 /// No errors returned from this bundle.
@@ -36,6 +39,9 @@ where
     AC: Hash + Eq,
 {
     bindings: Option<Bindings<AX, AC>>,
+    source: InputSource,
+    replay: Option<Replay<AX, AC>>,
+    fast_forward: bool,
     #[cfg(feature = "sdl_controller")]
     controller_mappings: Option<ControllerMappings>,
 }
@@ -56,6 +62,32 @@ where
         self
     }
 
+    /// Sets where the keyboard input applied to the `InputHandler` comes from.
+    ///
+    /// With any source but `InputSource::Local`, add a `TickInputSystem` to the simulation stage
+    /// of the game data, which applies the input of the players tick by tick.
+    pub fn with_source(mut self, source: InputSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Plays back a recorded match instead of taking keyboard input from the window.
+    ///
+    /// This adds a `ReplaySystem` and uses `InputSource::Replay`. The recorded input is applied
+    /// by the `TickInputSystem` of the simulation stage.
+    pub fn with_replay(mut self, replay: Replay<AX, AC>) -> Self {
+        self.replay = Some(replay);
+        self.source = InputSource::Replay;
+        self
+    }
+
+    /// Plays the recorded match back as fast as possible, one tick per frame, instead of at its
+    /// original tick cadence. See `ReplaySystem::fast_forward`.
+    pub fn fast_forward(mut self) -> Self {
+        self.fast_forward = true;
+        self
+    }
+
     /// Load bindings from file
     pub fn with_bindings_from_file<P: AsRef<Path>>(self, file: P) -> StdResult<Self, ConfigError>
     where
@@ -101,11 +133,17 @@ where
                 SdlEventsSystem::<AX, AC>::new(self.controller_mappings).unwrap(),
             );
         }
-        builder.add(
-            InputSystem::<AX, AC>::new(self.bindings),
-            "input_system",
-            &[],
-        );
+        let mut deps: &[&str] = &[];
+        if let Some(replay) = self.replay {
+            let mut system = ReplaySystem::new(replay);
+            if self.fast_forward {
+                system = system.fast_forward();
+            }
+            builder.add(system, "replay_system", &[]);
+            deps = &["replay_system"];
+        }
+        let system = InputSystem::<AX, AC>::with_source(self.bindings, self.source);
+        builder.add(system, "input_system", deps);
         Ok(())
     }
 }
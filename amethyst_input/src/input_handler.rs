@@ -11,15 +11,13 @@ use std::hash::Hash;
 use winit::{
     DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent,
 };
-use futures::sync::mpsc;
-use futures::Sink;
 
 /// This struct holds state information about input devices.
 ///
 /// For example, if a key is pressed on the keyboard, this struct will record
 /// that the key is pressed until it is released again.
 #[derive(Derivative)]
-#[derivative(Default(bound = ""))]
+#[derivative(Default(bound = ""), Clone(bound = "AX: Clone, AC: Clone"))]
 pub struct InputHandler<AX, AC>
 where
     AX: Hash + Eq,
@@ -38,6 +36,10 @@ where
     /// while second is the ID used by incoming events.
     connected_controllers: SmallVec<[(u32, u32); 8]>,
     mouse_position: Option<(f64, f64)>,
+    /// Actions which are down according to the input received over the network.
+    net_actions: Vec<AC>,
+    /// Axis values received over the network.
+    net_axes: Vec<(AX, f64)>,
 }
 
 impl<AX, AC> InputHandler<AX, AC>
@@ -56,65 +58,45 @@ where
     AX: Hash + Eq + Clone + Send + Sync + 'static,
     AC: Hash + Eq + Clone + Send + Sync + 'static,
 {
-    /// Updates the input handler with a new engine event.
+    /// Updates the input handler with an input event confirmed by the network.
     ///
-    /// The Amethyst game engine will automatically call this if the InputHandler is attached to
-    /// the world as a resource with id 0.
-
-    pub fn send_net_event(&mut self, event: &InputEvent<AC>, event_handler: &mut EventChannel<InputEvent<AC>>) {
+    /// Called by `PlayerInputs` for every event of the player owning this handler. Actions and
+    /// axes set this way are reported by `action_is_down` and `axis_value` in addition to the
+    /// local input.
+    pub fn send_net_event(
+        &mut self,
+        event: &NetInputEvent<AX, AC>,
+        event_handler: &mut EventChannel<InputEvent<AC>>,
+    ) {
         match *event {
-            KeyPressed { key_code, scancode } => {
-                if self.pressed_keys.iter().all(|&k| k.0 != key_code) {
-                    self.pressed_keys.push((key_code, scancode));
-                    event_handler.iter_write(
-                        [
-                            KeyPressed { key_code, scancode },
-                            ButtonPressed(Button::Key(key_code)),
-                            ButtonPressed(Button::ScanCode(scancode)),
-                        ].iter()
-                            .cloned(),
-                    );
-                    for (k, v) in self.bindings.actions.iter() {
-                        for &button in v {
-                            if Button::Key(key_code) == button {
-                                event_handler.single_write(ActionPressed(k.clone()));
-                            }
-                            if Button::ScanCode(scancode) == button {
-                                event_handler.single_write(ActionPressed(k.clone()));
-                            }
-                        }
-                    }
+            NetInputEvent::ActionPressed(ref action) => {
+                if !self.net_actions.contains(action) {
+                    self.net_actions.push(action.clone());
+                    event_handler.single_write(ActionPressed(action.clone()));
                 }
-            },
-            KeyReleased { key_code, scancode } => {
-                let index = self.pressed_keys.iter().position(|&k| k.0 == key_code);
-                    if let Some(i) = index {
-                        self.pressed_keys.swap_remove(i);
-                        event_handler.iter_write(
-                            [
-                                KeyReleased { key_code, scancode },
-                                ButtonReleased(Button::Key(key_code)),
-                                ButtonReleased(Button::ScanCode(scancode)),
-                            ].iter()
-                                .cloned(),
-                        );
-                        for (k, v) in self.bindings.actions.iter() {
-                            for &button in v {
-                                if Button::Key(key_code) == button {
-                                    event_handler.single_write(ActionReleased(k.clone()));
-                                }
-                                if Button::ScanCode(scancode) == button {
-                                    event_handler.single_write(ActionReleased(k.clone()));
-                                }
-                            }
-                        }
-                    }
-                },
-                _ => {},
+            }
+            NetInputEvent::ActionReleased(ref action) => {
+                let index = self.net_actions.iter().position(|a| a == action);
+                if let Some(i) = index {
+                    self.net_actions.swap_remove(i);
+                    event_handler.single_write(ActionReleased(action.clone()));
+                }
+            }
+            NetInputEvent::AxisMoved { ref axis, value } => {
+                let index = self.net_axes.iter().position(|&(ref a, _)| a == axis);
+                match index {
+                    Some(i) => self.net_axes[i].1 = value,
+                    None => self.net_axes.push((axis.clone(), value)),
+                }
+            }
         }
     }
 
-    pub fn send_event(&mut self, event: &Event, event_handler: &mut EventChannel<InputEvent<AC>>, tx: &mut mpsc::UnboundedSender<InputEvent<AC>>) {
+    /// Updates the input handler with a new engine event.
+    ///
+    /// The Amethyst game engine will automatically call this if the InputHandler is attached to
+    /// the world as a resource with id 0.
+    pub fn send_event(&mut self, event: &Event, event_handler: &mut EventChannel<InputEvent<AC>>) {
         match *event {
             Event::WindowEvent { ref event, .. } => match *event {
                 WindowEvent::ReceivedCharacter(c) => {
@@ -130,8 +112,8 @@ where
                         },
                     ..
                 } => {
-                    let _ = tx.start_send(KeyPressed { key_code, scancode }).unwrap();
-                },
+                    self.press_key(key_code, scancode, event_handler);
+                }
                 WindowEvent::KeyboardInput {
                     input:
                         KeyboardInput {
@@ -142,7 +124,7 @@ where
                         },
                     ..
                 } => {
-                    let _ = tx.start_send(KeyReleased { key_code, scancode }).unwrap();
+                    self.release_key(key_code, scancode, event_handler);
                 }
                 WindowEvent::MouseInput {
                     state: ElementState::Pressed,
@@ -435,10 +417,19 @@ where
     }
 
     /// Returns the value of an axis by the string id, if the id doesn't exist this returns None.
+    ///
+    /// A value received over the network takes precedence over the local input.
     pub fn axis_value<T: Hash + Eq + ?Sized>(&self, id: &T) -> Option<f64>
     where
         AX: Borrow<T>,
     {
+        let net_value = self.net_axes.iter().find(|&&(ref a, _)| {
+            let a: &T = a.borrow();
+            a == id
+        });
+        if let Some(&(_, value)) = net_value {
+            return Some(value);
+        }
         self.bindings.axes.get(id).map(|a| match a {
             &Axis::Emulated { pos, neg, .. } => {
                 let pos = self.button_is_down(pos);
@@ -475,17 +466,86 @@ where
         })
     }
 
-    /// Returns true if any of the action keys are down.
+    /// Returns true if any of the action keys are down, or if the action was pressed according
+    /// to the input received over the network.
     pub fn action_is_down<T: Hash + Eq + ?Sized>(&self, action: &T) -> Option<bool>
     where
         AC: Borrow<T>,
     {
+        let net_down = self.net_actions.iter().any(|a| {
+            let a: &T = a.borrow();
+            a == action
+        });
+        if net_down {
+            return Some(true);
+        }
         self.bindings
             .actions
             .get(action)
             .map(|ref buttons| buttons.iter().any(|&b| self.button_is_down(b)))
     }
 
+    /// Records a key as pressed and writes the resulting events.
+    fn press_key(
+        &mut self,
+        key_code: VirtualKeyCode,
+        scancode: u32,
+        event_handler: &mut EventChannel<InputEvent<AC>>,
+    ) {
+        if self.pressed_keys.iter().all(|&k| k.0 != key_code) {
+            self.pressed_keys.push((key_code, scancode));
+            event_handler.iter_write(
+                [
+                    KeyPressed { key_code, scancode },
+                    ButtonPressed(Button::Key(key_code)),
+                    ButtonPressed(Button::ScanCode(scancode)),
+                ].iter()
+                    .cloned(),
+            );
+            for (k, v) in self.bindings.actions.iter() {
+                for &button in v {
+                    if Button::Key(key_code) == button {
+                        event_handler.single_write(ActionPressed(k.clone()));
+                    }
+                    if Button::ScanCode(scancode) == button {
+                        event_handler.single_write(ActionPressed(k.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Records a key as released and writes the resulting events.
+    fn release_key(
+        &mut self,
+        key_code: VirtualKeyCode,
+        scancode: u32,
+        event_handler: &mut EventChannel<InputEvent<AC>>,
+    ) {
+        let index = self.pressed_keys.iter().position(|&k| k.0 == key_code);
+        if let Some(i) = index {
+            self.pressed_keys.swap_remove(i);
+            event_handler.iter_write(
+                [
+                    KeyReleased { key_code, scancode },
+                    ButtonReleased(Button::Key(key_code)),
+                    ButtonReleased(Button::ScanCode(scancode)),
+                ].iter()
+                    .cloned(),
+            );
+            for (k, v) in self.bindings.actions.iter() {
+                for &button in v {
+                    if Button::Key(key_code) == button {
+                        event_handler.single_write(ActionReleased(k.clone()));
+                    }
+                    if Button::ScanCode(scancode) == button {
+                        event_handler.single_write(ActionReleased(k.clone()));
+                    }
+                }
+            }
+        }
+    }
+
     /// Retrieve next free controller number to allocate new controller to
     fn alloc_controller_id(&self) -> u32 {
         let mut i = 0u32;
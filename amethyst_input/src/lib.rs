@@ -7,7 +7,6 @@ extern crate fnv;
 extern crate serde;
 extern crate smallvec;
 extern crate winit;
-extern crate futures;
 
 #[cfg(feature = "sdl_controller")]
 extern crate sdl2;
@@ -22,9 +21,15 @@ pub use self::button::Button;
 pub use self::controller::{ControllerAxis, ControllerButton};
 pub use self::event::InputEvent;
 pub use self::input_handler::InputHandler;
+pub use self::net_event::{NetInputEvent, PlayerInputEvent};
+pub use self::player_inputs::PlayerInputs;
+pub use self::relay::InputRelay;
+pub use self::replay::{Replay, ReplayFrame, ReplaySystem};
 #[cfg(feature = "sdl_controller")]
 pub use self::sdl_events_system::SdlEventsSystem;
+pub use self::source::InputSource;
 pub use self::system::InputSystem;
+pub use self::tick_input_system::TickInputSystem;
 pub use self::util::{get_key, is_close_requested, is_key_down};
 use std::iter::Iterator;
 use winit::VirtualKeyCode;
@@ -38,7 +43,13 @@ mod event;
 mod input_handler;
 mod local_mouse_button;
 mod local_virtual_key_code;
+mod net_event;
+mod player_inputs;
+mod relay;
+mod replay;
+mod source;
 mod system;
+mod tick_input_system;
 mod util;
 
 #[cfg(feature = "sdl_controller")]
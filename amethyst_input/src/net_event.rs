@@ -0,0 +1,29 @@
+//! Input events exchanged between players over the network.
+
+/// Change of the bound input of a player, independent of the device that caused it.
+///
+/// Type parameters AX and AC are the types assigned to your axes and actions for your
+/// InputBundle or InputHandler.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum NetInputEvent<AX, AC> {
+    /// One of the buttons bound to the action was pressed.
+    ActionPressed(AC),
+    /// The last pressed button bound to the action was released.
+    ActionReleased(AC),
+    /// The value of the axis changed.
+    AxisMoved {
+        /// The axis which changed.
+        axis: AX,
+        /// The new value of the axis.
+        value: f64,
+    },
+}
+
+/// A `NetInputEvent` attributed to the player which produced it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayerInputEvent<AX, AC> {
+    /// The player which produced the event.
+    pub player: u32,
+    /// The event.
+    pub event: NetInputEvent<AX, AC>,
+}
@@ -0,0 +1,114 @@
+//! World resource holding the input state of every player of a networked game.
+
+use super::*;
+use amethyst_core::shrev::EventChannel;
+use fnv::FnvHashMap;
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+/// Keeps one `InputHandler` per player.
+///
+/// With `InputSource::Lockstep`, `InputSource::Spectator` or `InputSource::Replay`, the
+/// input events confirmed by the network are applied by the `TickInputSystem` to the handler of
+/// the player which produced them, instead of being merged into the single `InputHandler`
+/// resource. Game systems can then react to the player who actually pressed a key.
+///
+/// With `InputSource::Rollback`, register it with `GameDataBuilder::with_snapshot_resource`, so
+/// it is restored along with the rest of the simulation.
+///
+/// Type parameters AX and AC are the types assigned to your axes and actions for your
+/// InputBundle or InputHandler.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Clone(bound = "AX: Clone, AC: Clone"))]
+pub struct PlayerInputs<AX, AC>
+where
+    AX: Hash + Eq,
+    AC: Hash + Eq,
+{
+    /// Bindings given to the handler of every new player.
+    pub bindings: Bindings<AX, AC>,
+    players: FnvHashMap<u32, InputHandler<AX, AC>>,
+    local_player: Option<u32>,
+}
+
+impl<AX, AC> PlayerInputs<AX, AC>
+where
+    AX: Hash + Eq,
+    AC: Hash + Eq,
+{
+    /// Creates an empty resource, without any player.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the id of the player playing on this machine, once the network layer knows it.
+    pub fn local_player(&self) -> Option<u32> {
+        self.local_player
+    }
+
+    /// Sets the id of the player playing on this machine.
+    pub fn set_local_player(&mut self, player: u32) {
+        self.local_player = Some(player);
+    }
+
+    /// Returns the ids of all players which sent input so far, in no particular order.
+    pub fn players<'a>(&'a self) -> impl Iterator<Item = u32> + 'a {
+        self.players.keys().cloned()
+    }
+
+    /// Returns the input handler of a player, if that player sent input so far.
+    pub fn player(&self, player: u32) -> Option<&InputHandler<AX, AC>> {
+        self.players.get(&player)
+    }
+
+    /// Returns true if any of the action keys are down for the given player.
+    ///
+    /// Returns `None` if the action is not bound, like `InputHandler::action_is_down`.
+    pub fn action_is_down<T: Hash + Eq + ?Sized>(&self, player: u32, action: &T) -> Option<bool>
+    where
+        AC: Borrow<T>,
+    {
+        match self.players.get(&player) {
+            Some(handler) => handler.action_is_down(action),
+            None => self.bindings.actions.get(action).map(|_| false),
+        }
+    }
+
+    /// Returns the value of an axis for the given player.
+    ///
+    /// Returns `None` if the axis is not bound, like `InputHandler::axis_value`.
+    pub fn axis_value<T: Hash + Eq + ?Sized>(&self, player: u32, axis: &T) -> Option<f64>
+    where
+        AX: Borrow<T>,
+    {
+        match self.players.get(&player) {
+            Some(handler) => handler.axis_value(axis),
+            None => self.bindings.axes.get(axis).map(|_| 0.0),
+        }
+    }
+}
+
+impl<AX, AC> PlayerInputs<AX, AC>
+where
+    AX: Hash + Eq + Clone + Send + Sync + 'static,
+    AC: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    /// Applies an input event confirmed by the network to the handler of its player.
+    ///
+    /// The handler is created with the current bindings on the first event of a player.
+    pub fn send_net_event(
+        &mut self,
+        event: &PlayerInputEvent<AX, AC>,
+        event_handler: &mut EventChannel<InputEvent<AC>>,
+    ) {
+        let bindings = &self.bindings;
+        self.players
+            .entry(event.player)
+            .or_insert_with(|| {
+                let mut handler = InputHandler::new();
+                handler.bindings = bindings.clone();
+                handler
+            })
+            .send_net_event(&event.event, event_handler);
+    }
+}
@@ -0,0 +1,81 @@
+//! Resource used to exchange input events with a lockstep network layer.
+
+use super::net_event::{NetInputEvent, PlayerInputEvent};
+use std::collections::VecDeque;
+use std::vec::Drain;
+
+/// Exchanges input events between the `InputSystem` and a networking layer.
+///
+/// With `InputSource::Lockstep`, changes of the locally bound actions and axes are queued here
+/// instead of being applied to the `InputHandler` directly.
+/// The networking layer drains them, sends them to the server and pushes back the events of
+/// every confirmed frame, which the `TickInputSystem` then applies in the tick of the frame.
+/// With `InputSource::Rollback`, the frames are predicted ones until confirmed.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct InputRelay<AX, AC> {
+    outgoing: Vec<NetInputEvent<AX, AC>>,
+    scheduled: Vec<(u64, NetInputEvent<AX, AC>)>,
+    frames: VecDeque<(u64, Vec<PlayerInputEvent<AX, AC>>)>,
+}
+
+impl<AX, AC> InputRelay<AX, AC> {
+    /// Creates an empty relay.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Queues a local input event to be sent over the network.
+    pub fn send(&mut self, event: NetInputEvent<AX, AC>) {
+        self.outgoing.push(event);
+    }
+
+    /// Removes and returns all local input events queued since the last call.
+    pub fn drain_outgoing(&mut self) -> Drain<NetInputEvent<AX, AC>> {
+        self.outgoing.drain(..)
+    }
+
+    /// Queues a local input event to be sent for the given network frame, so that it is applied
+    /// in a known tick, for example to script input.
+    ///
+    /// The event is applied in a later tick if the server confirmed the frame before receiving
+    /// it.
+    pub fn send_at(&mut self, frame: u64, event: NetInputEvent<AX, AC>) {
+        self.scheduled.push((frame, event));
+    }
+
+    /// Removes and returns all local input events queued with `send_at` since the last call,
+    /// along with their frames.
+    pub fn drain_scheduled(&mut self) -> Drain<(u64, NetInputEvent<AX, AC>)> {
+        self.scheduled.drain(..)
+    }
+
+    /// Adds the events of a confirmed network frame, along with the simulation tick it is
+    /// applied in.
+    pub fn push_frame(&mut self, tick: u64, events: Vec<PlayerInputEvent<AX, AC>>) {
+        self.frames.push_back((tick, events));
+    }
+
+    /// Takes the oldest confirmed frame which has not been applied yet, with its tick.
+    pub fn pop_frame(&mut self) -> Option<(u64, Vec<PlayerInputEvent<AX, AC>>)> {
+        self.frames.pop_front()
+    }
+
+    /// Takes the frame of the given tick, dropping the frames of the ticks before it.
+    pub fn take_frame(&mut self, tick: u64) -> Option<Vec<PlayerInputEvent<AX, AC>>> {
+        while self.frames.front().map_or(false, |&(t, _)| t < tick) {
+            self.frames.pop_front();
+        }
+        if self.frames.front().map_or(false, |&(t, _)| t == tick) {
+            self.frames.pop_front().map(|(_, events)| events)
+        } else {
+            None
+        }
+    }
+
+    /// Drops all the frames which have not been applied yet, for example to replace them after
+    /// a rollback.
+    pub fn clear_frames(&mut self) {
+        self.frames.clear();
+    }
+}
@@ -0,0 +1,217 @@
+//! Recording and playback of the input confirmed for every simulation tick.
+
+use amethyst_core::specs::prelude::{Read, Resources, System, SystemData, Write};
+use amethyst_core::timing::{SimulationTime, TickSource, Time};
+use std::hash::Hash;
+use std::time::Duration;
+use {InputRelay, PlayerInputEvent};
+
+/// The input events applied in a single simulation tick.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFrame<AX, AC> {
+    /// Number of the tick.
+    pub tick: u64,
+    /// Input events of every player, in the order they were applied.
+    pub events: Vec<PlayerInputEvent<AX, AC>>,
+}
+
+/// A recording of the input of a match, tick by tick.
+///
+/// Since the simulation only depends on the input confirmed for every tick, playing the
+/// recording back with a `ReplaySystem` simulates the exact same match. It can be saved to and
+/// loaded from a RON file with `amethyst_config::Config`.
+///
+/// Type parameters AX and AC are the types assigned to your axes and actions for your
+/// InputBundle or InputHandler.
+#[derive(Clone, Debug, Derivative, Serialize, Deserialize)]
+#[derivative(Default(bound = ""))]
+pub struct Replay<AX, AC> {
+    /// Duration of a tick when the match was recorded.
+    pub tick_time: Duration,
+    /// The recorded ticks, in order.
+    pub frames: Vec<ReplayFrame<AX, AC>>,
+}
+
+impl<AX, AC> Replay<AX, AC>
+where
+    AX: Clone,
+    AC: Clone,
+{
+    /// Creates an empty recording.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends the input events applied in a tick.
+    ///
+    /// When a tick is simulated again after a rollback, the ticks recorded from that one on are
+    /// replaced.
+    pub fn record(&mut self, tick: u64, events: &[PlayerInputEvent<AX, AC>]) {
+        while self.last_tick().map_or(false, |last| last >= tick) {
+            self.frames.pop();
+        }
+        self.frames.push(ReplayFrame {
+            tick,
+            events: events.to_vec(),
+        });
+    }
+
+    /// Returns the number of the last recorded tick.
+    pub fn last_tick(&self) -> Option<u64> {
+        self.frames.last().map(|frame| frame.tick)
+    }
+}
+
+/// Plays a `Replay` back through the `InputRelay`.
+///
+/// One recorded tick is released per tick duration of the recording, measured with the real
+/// time of `Time`. Its events are pushed to the `InputRelay`, and the `SimulationTime` is
+/// advanced to its tick number, the same way the lockstep network layer does. Use it along with
+/// `InputSource::Replay` and a `TickInputSystem` in the simulation stage.
+///
+/// Once every tick has been released, the simulation stalls.
+pub struct ReplaySystem<AX, AC> {
+    replay: Replay<AX, AC>,
+    position: usize,
+    elapsed: Duration,
+    fast_forward: bool,
+}
+
+impl<AX, AC> ReplaySystem<AX, AC> {
+    /// Creates a system playing the given recording at its original tick cadence.
+    pub fn new(replay: Replay<AX, AC>) -> Self {
+        ReplaySystem {
+            replay,
+            position: 0,
+            elapsed: Duration::from_secs(0),
+            fast_forward: false,
+        }
+    }
+
+    /// Releases a tick on every run instead of waiting for the tick duration, to replay the
+    /// match as fast as possible, for example in tests.
+    pub fn fast_forward(mut self) -> Self {
+        self.fast_forward = true;
+        self
+    }
+
+    /// Returns true once every recorded tick has been released.
+    pub fn finished(&self) -> bool {
+        self.position >= self.replay.frames.len()
+    }
+}
+
+impl<'a, AX, AC> System<'a> for ReplaySystem<AX, AC>
+where
+    AX: Hash + Eq + Clone + Send + Sync + 'static,
+    AC: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    type SystemData = (
+        Read<'a, Time>,
+        Write<'a, InputRelay<AX, AC>>,
+        Write<'a, SimulationTime>,
+    );
+
+    fn run(&mut self, (time, mut relay, mut sim_time): Self::SystemData) {
+        if self.finished() {
+            sim_time.stall();
+            return;
+        }
+
+        if !self.fast_forward {
+            self.elapsed += time.delta_real_time();
+            if self.elapsed < self.replay.tick_time {
+                sim_time.stall();
+                return;
+            }
+            self.elapsed -= self.replay.tick_time;
+        }
+
+        let frame = &self.replay.frames[self.position];
+        self.position += 1;
+        relay.push_frame(frame.tick, frame.events.clone());
+        sim_time.advance_to(frame.tick);
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        let mut time = res.fetch_mut::<SimulationTime>();
+        time.set_source(TickSource::Network);
+        time.set_tick_time(self.replay.tick_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::specs::prelude::{RunNow, World};
+    use NetInputEvent;
+
+    fn simulated_ticks(world: &World) -> Vec<u64> {
+        let mut time = world.write_resource::<SimulationTime>();
+        let mut ticks = Vec::new();
+        while time.next_tick() {
+            ticks.push(time.tick());
+        }
+        ticks
+    }
+
+    fn replay() -> Replay<String, String> {
+        let mut replay = Replay::new();
+        replay.tick_time = Duration::from_millis(20);
+        replay.record(1, &[]);
+        replay.record(
+            2,
+            &[PlayerInputEvent {
+                player: 1,
+                event: NetInputEvent::ActionPressed("jump".to_string()),
+            }],
+        );
+        replay
+    }
+
+    #[test]
+    fn releases_recorded_ticks_in_order() {
+        let mut world = World::new();
+        let mut system = ReplaySystem::new(replay()).fast_forward();
+        RunNow::setup(&mut system, &mut world.res);
+
+        system.run_now(&world.res);
+        assert_eq!(simulated_ticks(&world), vec![1]);
+        system.run_now(&world.res);
+        assert_eq!(simulated_ticks(&world), vec![2]);
+        assert!(system.finished());
+
+        let mut relay = world.write_resource::<InputRelay<String, String>>();
+        assert_eq!(relay.pop_frame(), Some((1, vec![])));
+        assert_eq!(
+            relay.pop_frame().map(|(tick, events)| (tick, events.len())),
+            Some((2, 1))
+        );
+    }
+
+    #[test]
+    fn waits_for_the_tick_duration() {
+        let mut world = World::new();
+        let mut system = ReplaySystem::new(replay());
+        RunNow::setup(&mut system, &mut world.res);
+
+        world
+            .write_resource::<Time>()
+            .set_delta_time(Duration::from_millis(10));
+        system.run_now(&world.res);
+        assert!(simulated_ticks(&world).is_empty());
+        system.run_now(&world.res);
+        assert_eq!(simulated_ticks(&world), vec![1]);
+    }
+
+    #[test]
+    fn rerecords_rolled_back_ticks() {
+        let mut replay = replay();
+        replay.record(3, &[]);
+        replay.record(2, &[]);
+        assert_eq!(replay.frames.len(), 2);
+        assert!(replay.frames[1].events.is_empty());
+        assert_eq!(replay.last_tick(), Some(2));
+    }
+}
@@ -0,0 +1,30 @@
+//! Input source modes of the `InputSystem`.
+
+/// Determines where the `InputSystem` takes the keyboard input applied to the `InputHandler` from.
+///
+/// Mouse, controller and window focus events are always applied locally.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputSource {
+    /// Keyboard events of the window are applied as soon as they are received.
+    Local,
+    /// Keyboard events of the window are queued in the `InputRelay` to be sent over the network,
+    /// and only the frames confirmed by the network are applied, tick by tick by a
+    /// `TickInputSystem` in the simulation stage.
+    Lockstep,
+    /// Like `Lockstep`, but the frames in the `InputRelay` are predicted until the network
+    /// confirms them, and are applied again when the simulation is rolled back.
+    Rollback,
+    /// Keyboard events of the window are ignored, and only the frames pushed to the
+    /// `InputRelay` are applied, for example from a recorded match.
+    Replay,
+    /// Keyboard events of the window are applied locally as with `Local`, but never sent over
+    /// the network. The frames confirmed by the network are applied to the `PlayerInputs` as
+    /// with `Lockstep`, for spectators of a networked match.
+    Spectator,
+}
+
+impl Default for InputSource {
+    fn default() -> Self {
+        InputSource::Local
+    }
+}
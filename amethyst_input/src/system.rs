@@ -1,20 +1,21 @@
 //! Input system
 
-use std::sync::{Arc, Mutex};
-use std::sync::mpsc::Receiver;
-use std::time::Duration;
-use amethyst_core::timing::Time;
 use amethyst_core::shrev::{EventChannel, ReaderId};
 use amethyst_core::specs::prelude::{Read, Resources, System, Write};
 use std::hash::Hash;
-use winit::Event;
-use {Bindings, InputEvent, InputHandler};
-use futures::sync::mpsc;
+use winit::{Event, WindowEvent};
+use {Bindings, InputEvent, InputHandler, InputRelay, InputSource, NetInputEvent, PlayerInputs};
 
 /// Input system
 ///
 /// Will read `winit::Event` from `EventHandler<winit::Event>`, process them with `InputHandler`,
 /// and push the results in `EventHandler<InputEvent>`.
+///
+/// Depending on its `InputSource`, keyboard events are instead ignored or translated into
+/// changes of the bound actions and axes which are sent through the `InputRelay`. The frames
+/// pushed to the `InputRelay` are applied to the `PlayerInputs` tick by tick by the
+/// `TickInputSystem`. With `InputSource::Spectator`, keyboard events are applied locally and
+/// nothing is sent through the `InputRelay`.
 pub struct InputSystem<AX, AC>
 where
     AX: Hash + Eq,
@@ -22,6 +23,9 @@ where
 {
     reader: Option<ReaderId<Event>>,
     bindings: Option<Bindings<AX, AC>>,
+    source: InputSource,
+    /// Input of the local player in lockstep mode, which is only sent over the network.
+    local: InputHandler<AX, AC>,
 }
 
 impl<AX, AC> InputSystem<AX, AC>
@@ -29,38 +33,69 @@ where
     AX: Hash + Eq,
     AC: Hash + Eq,
 {
-    /// Create a new input system. Needs a reader id for `EventHandler<winit::Event>`.
+    /// Create a new input system taking its input from the local window.
+    /// Needs a reader id for `EventHandler<winit::Event>`.
     pub fn new(bindings: Option<Bindings<AX, AC>>) -> Self {
+        InputSystem::with_source(bindings, InputSource::Local)
+    }
+
+    /// Create a new input system taking its keyboard input from the given source.
+    pub fn with_source(bindings: Option<Bindings<AX, AC>>, source: InputSource) -> Self {
         InputSystem {
             reader: None,
             bindings,
+            source,
+            local: InputHandler::new(),
         }
     }
 
+    /// Updates `handler` with the event, writing the resulting `InputEvent`s to `output` if
+    /// there is one. Without an output, the events are dropped.
     fn process_event(
         event: &Event,
         handler: &mut InputHandler<AX, AC>,
-        output: &mut EventChannel<InputEvent<AC>>,
-        tx: &mut mpsc::UnboundedSender<InputEvent<AC>>,
+        output: Option<&mut EventChannel<InputEvent<AC>>>,
     ) where
         AX: Hash + Eq + Clone + Send + Sync + 'static,
         AC: Hash + Eq + Clone + Send + Sync + 'static,
     {
-        handler.send_event(event, output, tx);
+        match output {
+            Some(output) => handler.send_event(event, output),
+            None => handler.send_event(event, &mut EventChannel::new()),
+        }
     }
+}
 
-    fn process_net_event(
-        event: &InputEvent<AC>,
-        handler: &mut InputHandler<AX, AC>,
-        output: &mut EventChannel<InputEvent<AC>>,
-    ) where
-        AX: Hash + Eq + Clone + Send + Sync + 'static,
-        AC: Hash + Eq + Clone + Send + Sync + 'static,
-    {
-        handler.send_net_event(event, output);
+/// Checks whether the event is a key press or release.
+fn is_key_event(event: &Event) -> bool {
+    match *event {
+        Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { .. },
+            ..
+        } => true,
+        _ => false,
     }
+}
 
-
+/// Returns the state of all bound actions and axes.
+fn bound_state<AX, AC>(handler: &InputHandler<AX, AC>) -> (Vec<(AC, bool)>, Vec<(AX, f64)>)
+where
+    AX: Hash + Eq + Clone + Send + Sync + 'static,
+    AC: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    let actions = handler
+        .bindings
+        .actions
+        .keys()
+        .map(|action| (action.clone(), handler.action_is_down(action).unwrap_or(false)))
+        .collect();
+    let axes = handler
+        .bindings
+        .axes
+        .keys()
+        .map(|axis| (axis.clone(), handler.axis_value(axis).unwrap_or(0.0)))
+        .collect();
+    (actions, axes)
 }
 
 impl<'a, AX, AC> System<'a> for InputSystem<AX, AC>
@@ -72,27 +107,38 @@ where
         Read<'a, EventChannel<Event>>,
         Write<'a, InputHandler<AX, AC>>,
         Write<'a, EventChannel<InputEvent<AC>>>,
-        Write<'a, Time>,
-        Read<'a, Option<Arc<Mutex<Receiver<Vec<InputEvent<AC>>>>>>>,
-        Read<'a, Option<Arc<Mutex<mpsc::UnboundedSender<InputEvent<AC>>>>>>,
+        Write<'a, InputRelay<AX, AC>>,
     );
 
-    fn run(&mut self, (input, mut handler, mut output, mut time, rx, tx): Self::SystemData) {
-        time.set_delta_time(Duration::from_millis(20));
-        println!("Input system Tick with time delta: {}", time.delta_seconds());
-        let net_rx = rx.as_ref().unwrap();
-        let rx = net_rx.lock().unwrap();
-        let events = rx.recv().unwrap();
-        println!("Total {} net events", events.len());
-        for event in events {
-            Self::process_net_event(&event, &mut *handler, &mut *output);
-        }
-        // println!("Net_RX: {:?}", events);
-        let tx = tx.as_ref().unwrap();
-        let mut tx = tx.lock().unwrap();
+    fn run(&mut self, (input, mut handler, mut output, mut relay): Self::SystemData) {
+        let networked =
+            self.source == InputSource::Lockstep || self.source == InputSource::Rollback;
+        let before = bound_state(&self.local);
         for event in input.read(&mut self.reader.as_mut().unwrap()) {
-            println!("New event: {:?}", event);
-            Self::process_event(event, &mut *handler, &mut *output, &mut *tx);
+            if networked {
+                Self::process_event(event, &mut self.local, None);
+            }
+            let local =
+                self.source == InputSource::Local || self.source == InputSource::Spectator;
+            if local || !is_key_event(event) {
+                Self::process_event(event, &mut *handler, Some(&mut *output));
+            }
+        }
+
+        if networked {
+            let (actions, axes) = bound_state(&self.local);
+            for ((action, down), &(_, was_down)) in actions.into_iter().zip(before.0.iter()) {
+                if down && !was_down {
+                    relay.send(NetInputEvent::ActionPressed(action));
+                } else if !down && was_down {
+                    relay.send(NetInputEvent::ActionReleased(action));
+                }
+            }
+            for ((axis, value), &(_, old_value)) in axes.into_iter().zip(before.1.iter()) {
+                if value != old_value {
+                    relay.send(NetInputEvent::AxisMoved { axis, value });
+                }
+            }
         }
     }
 
@@ -102,6 +148,10 @@ where
         self.reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
         if let Some(ref bindings) = self.bindings {
             res.fetch_mut::<InputHandler<AX, AC>>().bindings = bindings.clone();
+            res.entry::<PlayerInputs<AX, AC>>()
+                .or_insert_with(PlayerInputs::default)
+                .bindings = bindings.clone();
+            self.local.bindings = bindings.clone();
         }
     }
 }
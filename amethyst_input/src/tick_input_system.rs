@@ -0,0 +1,67 @@
+//! Simulation system applying networked input tick by tick.
+
+use amethyst_core::shrev::EventChannel;
+use amethyst_core::specs::prelude::{Read, System, Write};
+use amethyst_core::timing::SimulationTime;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use {InputEvent, InputRelay, PlayerInputs, Replay};
+
+/// Applies the frame of the current simulation tick from the `InputRelay` to the `PlayerInputs`.
+///
+/// Add it as the first system of the simulation stage of the game data with every `InputSource`
+/// but `InputSource::Local`. Since the frames are applied tick by tick, the `Application` can
+/// simulate several ticks in one frame, for example when a client catches up with the server.
+/// When the simulation is rolled back, the network layer pushes the frames of the ticks to
+/// simulate again, and this system applies them to the restored `PlayerInputs`. Ticks without a
+/// frame keep the input state of the previous tick.
+///
+/// When recording, every applied frame is also appended to the `Replay` resource.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct TickInputSystem<AX, AC> {
+    recording: bool,
+    marker: PhantomData<(AX, AC)>,
+}
+
+impl<AX, AC> TickInputSystem<AX, AC> {
+    /// Creates a new tick input system.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Records the input of every simulation tick in the `Replay` resource.
+    ///
+    /// The recording can be saved with `amethyst_config::Config::write` at the end of the
+    /// match.
+    pub fn with_recording(mut self) -> Self {
+        self.recording = true;
+        self
+    }
+}
+
+impl<'a, AX, AC> System<'a> for TickInputSystem<AX, AC>
+where
+    AX: Hash + Eq + Clone + Send + Sync + 'static,
+    AC: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    type SystemData = (
+        Read<'a, SimulationTime>,
+        Write<'a, InputRelay<AX, AC>>,
+        Write<'a, PlayerInputs<AX, AC>>,
+        Write<'a, EventChannel<InputEvent<AC>>>,
+        Write<'a, Replay<AX, AC>>,
+    );
+
+    fn run(&mut self, (time, mut relay, mut players, mut output, mut replay): Self::SystemData) {
+        if let Some(events) = relay.take_frame(time.tick()) {
+            for event in &events {
+                players.send_net_event(event, &mut *output);
+            }
+            if self.recording {
+                replay.tick_time = time.tick_time();
+                replay.record(time.tick(), &events);
+            }
+        }
+    }
+}
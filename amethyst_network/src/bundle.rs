@@ -0,0 +1,261 @@
+//! ECS lockstep bundle
+
+use amethyst_core::bundle::{Result, SystemBundle, Teardown};
+use amethyst_core::specs::prelude::{Component, DispatcherBuilder, World};
+use connection::Connection;
+use error;
+use event::NetworkEvent;
+use message::Message;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use system::LockstepSystem;
+use transport::Transport;
+use world_state::WorldStateSystem;
+
+/// Bundle for playing in lockstep with other clients through a lockstep server.
+///
+/// Talks to the server through the given `Transport`, and adds the `LockstepSystem` which
+/// exchanges input events with the `InputSystem` through the `InputRelay` resource.
+///
+/// The transport needs to deliver every message, in order, like the `TcpTransport`. A frame
+/// which is lost or arrives out of order fails the connection with `ErrorKind::FrameGap`, after
+/// which the client reconnects and rejoins the match if `with_reconnect` was set.
+///
+/// Gameplay systems which must stay in step between the clients need to be added to the
+/// simulation stage of the game data, with `GameDataBuilder::with_sim`. They then only run on
+/// confirmed frames, while the rest of the game keeps running every frame.
+///
+/// To detect desyncs, add the `ChecksumBundle` to the simulation stage too.
+///
+/// To let clients join a match in progress, register the components and resources of the
+/// simulation with `with_state` and `with_state_resource`. This bundle adds the thread-local
+/// `WorldStateSystem`, which saves them for the joining clients and loads them on those.
+///
+/// A spectator bundle, made with `with_spectator`, watches a match without taking part in it.
+/// It runs the same simulation from the frames it receives, with the `InputBundle` using
+/// `InputSource::Spectator`, and spectates a lobby with `Session::spectate`.
+///
+/// The `InputBundle` needs to use `InputSource::Lockstep`, or `InputSource::Rollback` in rollback
+/// mode, and the `TickInputSystem` must be the first system of the simulation stage. It applies
+/// the confirmed input tick by tick, so several ticks can be simulated in one frame when the
+/// client catches up with the server.
+///
+/// In rollback mode, everything the simulation writes to, including the `PlayerInputs`, must be
+/// registered with `GameDataBuilder::with_snapshot` and `with_snapshot_resource`.
+///
+/// When the application shuts down, the teardown of this bundle sends a `Disconnect` message to
+/// the server, which then drops the client right away instead of waiting for the connection to
+/// time out.
+///
+/// ## Type parameters
+///
+/// AX: The type used to identify input axes, same as in the `InputBundle`.
+/// AC: The type used to identify input actions, same as in the `InputBundle`.
+///
+/// Both are sent over the network, so they need to be serializable with serde.
+///
+/// E: The custom event type of the `Application`, which receives the `NetworkEvent`s.
+///
+/// ## Errors
+///
+/// No errors returned from this bundle.
+///
+pub struct LockstepBundle<AX, AC, E> {
+    connection: Connection<AX, AC>,
+    transport: Arc<Mutex<Box<Transport>>>,
+    frame_duration: Duration,
+    input_delay: u64,
+    max_input_delay: Option<u64>,
+    ping_interval: Duration,
+    jitter_buffer: usize,
+    max_prediction: Option<u64>,
+    reconnect: Option<Box<Fn() -> error::Result<Box<Transport>> + Send + Sync>>,
+    state: WorldStateSystem,
+    spectator: bool,
+    _marker: PhantomData<E>,
+}
+
+impl<AX, AC, E> LockstepBundle<AX, AC, E>
+where
+    AX: Serialize + DeserializeOwned,
+    AC: Serialize + DeserializeOwned,
+{
+    /// Creates a new lockstep bundle talking to the server through `transport`.
+    ///
+    /// The frame duration defaults to 20 milliseconds, without input delay and with a jitter
+    /// buffer of one frame.
+    pub fn new<T>(transport: T) -> Self
+    where
+        T: Transport + 'static,
+    {
+        let transport = Arc::new(Mutex::new(Box::new(transport) as Box<Transport>));
+        LockstepBundle {
+            connection: Connection::new(SharedTransport(transport.clone())),
+            transport,
+            frame_duration: Duration::from_millis(20),
+            input_delay: 0,
+            max_input_delay: None,
+            ping_interval: Duration::from_millis(250),
+            jitter_buffer: 1,
+            max_prediction: None,
+            reconnect: None,
+            state: WorldStateSystem::new(),
+            spectator: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the duration of a server frame, used as the tick duration of the `SimulationTime`.
+    pub fn with_frame_duration(mut self, frame_duration: Duration) -> Self {
+        self.frame_duration = frame_duration;
+        self
+    }
+
+    /// Sets the number of frames the local input is delayed by.
+    ///
+    /// Delaying the input gives it time to reach the other clients before they simulate the
+    /// frame it belongs to.
+    pub fn with_input_delay(mut self, frames: u64) -> Self {
+        self.input_delay = frames;
+        self
+    }
+
+    /// Adapts the input delay to the latency to the server, up to `max_frames`.
+    ///
+    /// The input delay then follows `NetworkStats::recommended_input_delay`, starting from the
+    /// delay set with `with_input_delay` until a round-trip time was measured.
+    pub fn with_adaptive_input_delay(mut self, max_frames: u64) -> Self {
+        self.max_input_delay = Some(max_frames);
+        self
+    }
+
+    /// Sets the time between two pings of the server, which measure the latency reported in
+    /// the `NetworkStats` resource. Defaults to 250 milliseconds.
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+
+    /// Sets the number of confirmed frames buffered before the simulation starts, and before it
+    /// resumes after running out of frames.
+    ///
+    /// A bigger buffer hides more network jitter, at the cost of latency.
+    pub fn with_jitter_buffer(mut self, frames: usize) -> Self {
+        self.jitter_buffer = frames;
+        self
+    }
+
+    /// Predicts up to `max_prediction` ticks ahead of the server instead of waiting for its
+    /// frames, and rolls the simulation back when a prediction was wrong.
+    ///
+    /// The local input then shows up without waiting for the other clients, at the cost of
+    /// simulating some ticks several times. The jitter buffer is not used in this mode.
+    pub fn with_rollback(mut self, max_prediction: u64) -> Self {
+        self.max_prediction = Some(max_prediction);
+        self
+    }
+
+    /// Reconnects to the server with `connect` when the connection is lost, and rejoins the
+    /// match with the world state of another client.
+    pub fn with_reconnect<F, T>(mut self, connect: F) -> Self
+    where
+        F: Fn() -> error::Result<T> + Send + Sync + 'static,
+        T: Transport + 'static,
+    {
+        self.reconnect = Some(Box::new(move || {
+            connect().map(|transport| Box::new(transport) as Box<Transport>)
+        }));
+        self
+    }
+
+    /// Watches the match as a spectator, which never sends input or checksums to the server.
+    ///
+    /// The server sends the frames to spectators with its spectator delay. Spectators don't
+    /// predict ahead of the server, so rollback is not used with this.
+    pub fn with_spectator(mut self) -> Self {
+        self.spectator = true;
+        self
+    }
+
+    /// Sends the components of type `C` to the clients joining the match in progress.
+    ///
+    /// The components need to be registered in the same order on every client.
+    pub fn with_state<C>(mut self) -> Self
+    where
+        C: Component + Serialize + DeserializeOwned,
+    {
+        self.state = self.state.with::<C>();
+        self
+    }
+
+    /// Sends the resource of type `R` to the clients joining the match in progress.
+    pub fn with_state_resource<R>(mut self) -> Self
+    where
+        R: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+    {
+        self.state = self.state.with_resource::<R>();
+        self
+    }
+}
+
+impl<'a, 'b, AX, AC, E> SystemBundle<'a, 'b> for LockstepBundle<AX, AC, E>
+where
+    AX: Hash + Eq + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    AC: Hash + Eq + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    E: From<NetworkEvent> + Send + Sync + 'static,
+{
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
+        let mut system = LockstepSystem::<AX, AC, E>::new(self.connection, self.frame_duration)
+            .with_input_delay(self.input_delay)
+            .with_ping_interval(self.ping_interval)
+            .with_jitter_buffer(self.jitter_buffer);
+        if let Some(max_frames) = self.max_input_delay {
+            system = system.with_adaptive_input_delay(max_frames);
+        }
+        match self.max_prediction {
+            _ if self.spectator => system = system.with_spectator(),
+            Some(max_prediction) => system = system.with_rollback(max_prediction),
+            None => (),
+        }
+        if let Some(connect) = self.reconnect {
+            let transport = self.transport;
+            system = system.with_reconnect(move || {
+                *transport.lock().unwrap() = connect()?;
+                Ok(SharedTransport(transport.clone()))
+            });
+        }
+        builder.add(system, "lockstep_system", &[]);
+        builder.add_thread_local(self.state);
+        Ok(())
+    }
+
+    fn teardown(&mut self) -> Option<Teardown<'b>> {
+        let transport = self.transport.clone();
+        Some(Box::new(move |_: &mut World| {
+            let sent = Message::Disconnect
+                .encode()
+                .and_then(|bytes| transport.lock().unwrap().send(&bytes));
+            if let Err(err) = sent {
+                warn!("Failed to tell the lockstep server about leaving: {}", err);
+            }
+        }))
+    }
+}
+
+/// The transport of the `LockstepSystem`, shared with the teardown of the bundle. A reconnect
+/// replaces the transport inside, so the teardown always uses the current one.
+struct SharedTransport(Arc<Mutex<Box<Transport>>>);
+
+impl Transport for SharedTransport {
+    fn send(&mut self, payload: &[u8]) -> error::Result<()> {
+        self.0.lock().unwrap().send(payload)
+    }
+
+    fn recv(&mut self) -> error::Result<Option<Vec<u8>>> {
+        self.0.lock().unwrap().recv()
+    }
+}
@@ -0,0 +1,37 @@
+//! Encoding of input events for the wire.
+//!
+//! Input is sent as changes of the bound actions and axes rather than as raw key codes, so the
+//! clients do not need to share a keyboard layout, or even an input device, to understand each
+//! other. Any action and axis type which can be serialized with serde can be used.
+
+use amethyst_input::{NetInputEvent, PlayerInputEvent};
+use bincode;
+use error::Result;
+use message::PlayerInput;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Encodes the input events of the local player.
+pub fn encode_input<AX, AC>(events: &[NetInputEvent<AX, AC>]) -> Result<Vec<u8>>
+where
+    AX: Serialize,
+    AC: Serialize,
+{
+    Ok(bincode::serialize(events)?)
+}
+
+/// Decodes the input events a player sent, attributing them to that player.
+pub fn decode_input<AX, AC>(input: &PlayerInput) -> Result<Vec<PlayerInputEvent<AX, AC>>>
+where
+    AX: DeserializeOwned,
+    AC: DeserializeOwned,
+{
+    let events: Vec<NetInputEvent<AX, AC>> = bincode::deserialize(&input.data)?;
+    Ok(events
+        .into_iter()
+        .map(|event| PlayerInputEvent {
+            player: input.player,
+            event,
+        })
+        .collect())
+}
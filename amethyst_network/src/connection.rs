@@ -0,0 +1,271 @@
+//! Connection to a lockstep server.
+
+use amethyst_core::timing::duration_to_nanos;
+use amethyst_input::{NetInputEvent, PlayerInputEvent};
+use codec::{decode_input, encode_input};
+use error::{ErrorKind, Result};
+use event::NetworkEvent;
+use message::Message;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use stats::NetworkStats;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+use transport::Transport;
+
+/// Seconds after which an unanswered ping counts as lost.
+const PING_TIMEOUT: u64 = 2;
+
+/// Client side of a lockstep server connection.
+pub struct Connection<AX, AC> {
+    transport: Box<Transport>,
+    player: Option<u32>,
+    last_frame: u64,
+    checksums: Vec<(u64, u64)>,
+    events: Vec<NetworkEvent>,
+    pings: VecDeque<(u64, Instant)>,
+    next_ping: u64,
+    since_ping: Option<Duration>,
+    server_frame: Option<(u64, Instant)>,
+    stats: NetworkStats,
+    state_requested: bool,
+    world_state: Option<(u64, Vec<u8>)>,
+    _marker: PhantomData<(AX, AC)>,
+}
+
+impl<AX, AC> Connection<AX, AC>
+where
+    AX: Serialize + DeserializeOwned,
+    AC: Serialize + DeserializeOwned,
+{
+    /// Creates a connection talking to the server through the given transport.
+    pub fn new<T>(transport: T) -> Self
+    where
+        T: Transport + 'static,
+    {
+        Connection {
+            transport: Box::new(transport),
+            player: None,
+            last_frame: 0,
+            checksums: Vec::new(),
+            events: Vec::new(),
+            pings: VecDeque::new(),
+            next_ping: 0,
+            since_ping: None,
+            server_frame: None,
+            stats: NetworkStats::new(),
+            state_requested: false,
+            world_state: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the player id the server assigned to this client, once it is known.
+    pub fn player(&self) -> Option<u32> {
+        self.player
+    }
+
+    /// Returns the number of the last frame received from the server.
+    pub fn last_frame(&self) -> u64 {
+        self.last_frame
+    }
+
+    /// Returns the latency statistics measured so far.
+    pub fn stats(&self) -> &NetworkStats {
+        &self.stats
+    }
+
+    /// Estimates the number of the frame the server is broadcasting right now, from the frame
+    /// it reported in the last answered ping.
+    pub fn estimated_server_frame(&self, frame_duration: Duration) -> Option<u64> {
+        let (frame, received) = self.server_frame?;
+        let elapsed = duration_to_nanos(received.elapsed() + self.stats.rtt() / 2);
+        Some(frame + elapsed / duration_to_nanos(frame_duration).max(1))
+    }
+
+    /// Sends a ping to the server if the last one was sent at least `interval` ago, and counts
+    /// the pings which were not answered in time as lost.
+    ///
+    /// The time since the last ping is counted from the `elapsed` time of every call, rather
+    /// than measured, so that the pings are sent on the same frames for the same frame times.
+    pub fn ping(&mut self, interval: Duration, elapsed: Duration) -> Result<()> {
+        let timeout = Duration::from_secs(PING_TIMEOUT);
+        while self
+            .pings
+            .front()
+            .map_or(false, |&(_, sent)| sent.elapsed() > timeout)
+        {
+            self.pings.pop_front();
+            self.stats.record_loss();
+        }
+
+        if let Some(ref mut since) = self.since_ping {
+            *since += elapsed;
+            if *since < interval {
+                return Ok(());
+            }
+        }
+        let id = self.next_ping;
+        self.next_ping += 1;
+        self.transport.send(&Message::Ping { id }.encode()?)?;
+        self.pings.push_back((id, Instant::now()));
+        self.since_ping = Some(Duration::from_secs(0));
+        Ok(())
+    }
+
+    /// Sends the input events of the local player to the server, for the given frame.
+    pub fn send(&mut self, events: &[NetInputEvent<AX, AC>], frame: u64) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let message = Message::DataInput {
+            frame,
+            data: encode_input(events)?,
+        };
+        self.transport.send(&message.encode()?)
+    }
+
+    /// Sends the checksum of the world state computed on the given tick to the server.
+    pub fn send_checksum(&mut self, tick: u64, checksum: u64) -> Result<()> {
+        self.transport.send(&Message::Checksum { tick, checksum }.encode()?)
+    }
+
+    /// Sends a lobby request of the `Session` to the server.
+    pub fn send_request(&mut self, request: &Message) -> Result<()> {
+        self.transport.send(&request.encode()?)
+    }
+
+    /// Sends the world state saved after the given tick to the server, which asked for it.
+    pub fn send_world_state(&mut self, tick: u64, data: Vec<u8>) -> Result<()> {
+        self.transport.send(&Message::WorldState { tick, data }.encode()?)
+    }
+
+    /// Returns true if the server asked for the world state since the last call.
+    pub fn take_state_request(&mut self) -> bool {
+        let requested = self.state_requested;
+        self.state_requested = false;
+        requested
+    }
+
+    /// Takes the world state of another client received from the server, along with the tick it
+    /// was saved after.
+    pub fn take_world_state(&mut self) -> Option<(u64, Vec<u8>)> {
+        self.world_state.take()
+    }
+
+    /// Removes and returns the lobby events received so far.
+    pub fn drain_events(&mut self) -> Vec<NetworkEvent> {
+        self.events.drain(..).collect()
+    }
+
+    /// Removes and returns the checksums of the other players received so far, as pairs of tick
+    /// and checksum.
+    pub fn drain_checksums(&mut self) -> Vec<(u64, u64)> {
+        self.checksums.drain(..).collect()
+    }
+
+    /// Returns the number and the events of the next frame confirmed by the server, if it has
+    /// arrived.
+    ///
+    /// Returns `None` right after receiving a world state, so that it can be loaded before the
+    /// frames which follow it.
+    pub fn try_frame(&mut self) -> Result<Option<(u64, Vec<PlayerInputEvent<AX, AC>>)>> {
+        while let Some(bytes) = self.transport.recv()? {
+            match Message::decode(&bytes)? {
+                Message::Welcome { player } => {
+                    info!("Joined lockstep server as player {}", player);
+                    self.player = Some(player);
+                }
+                Message::PlayerChecksum {
+                    player,
+                    tick,
+                    checksum,
+                } => {
+                    if Some(player) != self.player {
+                        self.checksums.push((tick, checksum));
+                    }
+                }
+                Message::Pong { id, frame } => {
+                    if let Some(index) = self.pings.iter().position(|&(ping, _)| ping == id) {
+                        let (_, sent) = self.pings.remove(index).expect("unreachable");
+                        self.stats.record_rtt(sent.elapsed());
+                        self.server_frame = Some((frame, Instant::now()));
+                    }
+                }
+                Message::LobbyJoined { lobby, slot } => {
+                    info!("Joined lobby {} in slot {}", lobby, slot);
+                    self.events.push(NetworkEvent::LobbyJoined { lobby, slot });
+                }
+                Message::Spectating { lobby } => {
+                    info!("Spectating lobby {}", lobby);
+                    self.last_frame = 0;
+                    self.events.push(NetworkEvent::Spectating { lobby });
+                }
+                Message::LobbyRejected { lobby } => {
+                    self.events.push(NetworkEvent::LobbyRejected { lobby });
+                }
+                Message::LobbyLeft { lobby } => {
+                    self.events.push(NetworkEvent::LobbyLeft { lobby });
+                }
+                Message::LobbyUpdate { lobby, players } => {
+                    self.events.push(NetworkEvent::LobbyUpdated { lobby, players });
+                }
+                Message::MatchStart { lobby, tick } => {
+                    info!("Match of lobby {} starts on tick {}", lobby, tick);
+                    self.last_frame = 0;
+                    self.events.push(NetworkEvent::MatchStarted { lobby, tick });
+                }
+                Message::StateRequest => self.state_requested = true,
+                Message::WorldState { tick, data } => {
+                    info!("Received the world state of tick {}", tick);
+                    self.world_state = Some((tick, data));
+                    self.last_frame = tick;
+                    self.events.push(NetworkEvent::MatchJoined { tick });
+                    return Ok(None);
+                }
+                Message::DataFrame { frame, inputs } => {
+                    if frame != self.last_frame + 1 {
+                        bail!(ErrorKind::FrameGap(self.last_frame + 1, frame));
+                    }
+                    let mut events = Vec::new();
+                    for input in &inputs {
+                        events.extend(decode_input(input)?);
+                    }
+                    self.last_frame = frame;
+                    return Ok(Some((frame, events)));
+                }
+                message => warn!("Unexpected message from lockstep server: {:?}", message),
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::LoopbackTransport;
+
+    fn frame(server: &mut LoopbackTransport, frame: u64) {
+        let message = Message::DataFrame {
+            frame,
+            inputs: vec![],
+        };
+        server.send(&message.encode().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn fails_on_a_missing_frame() {
+        let (client, mut server) = LoopbackTransport::pair();
+        let mut connection = Connection::<String, String>::new(client);
+        frame(&mut server, 1);
+        frame(&mut server, 3);
+
+        assert_eq!(connection.try_frame().unwrap().map(|(frame, _)| frame), Some(1));
+        match *connection.try_frame().unwrap_err().kind() {
+            ErrorKind::FrameGap(2, 3) => (),
+            ref kind => panic!("Unexpected error: {}", kind),
+        }
+    }
+}
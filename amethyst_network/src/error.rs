@@ -0,0 +1,36 @@
+//! Network error types.
+
+use bincode;
+use std::io;
+
+error_chain! {
+    foreign_links {
+        Io(io::Error) #[doc = "Wraps an I/O error"];
+        Encoding(bincode::Error) #[doc = "Wraps a message encoding error"];
+    }
+
+    errors {
+        /// Returned if the remote side closed the connection.
+        Disconnected {
+            description("Connection closed by the remote side")
+        }
+
+        /// Returned if a received message exceeds the maximum message size.
+        MessageTooLarge(size: usize) {
+            description("Message too large")
+            display("Message of {} bytes exceeds the maximum message size", size)
+        }
+
+        /// Returned if a frame received from the lockstep server is not the one following the
+        /// last frame, because the transport lost or reordered messages.
+        FrameGap(expected: u64, received: u64) {
+            description("Frame received out of order")
+            display("Expected frame {}, but received frame {}", expected, received)
+        }
+
+        /// Returned if a world state doesn't match the registered components and resources.
+        StateMismatch {
+            description("World state doesn't match the registered components and resources")
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Events emitted by the network layer.
+
+use message::LobbySlot;
+
+/// Event emitted by the network layer on the custom `StateEvent` channel.
+///
+/// Your custom event type needs to implement `From<NetworkEvent>`, or be `NetworkEvent` itself.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetworkEvent {
+    /// The world state of this client and the one of another client differ.
+    DesyncDetected {
+        /// The simulation tick on which the checksums were computed.
+        tick: u64,
+        /// The checksum computed by this client.
+        local: u64,
+        /// The checksum computed by the other client.
+        remote: u64,
+    },
+    /// This client joined a lobby.
+    LobbyJoined {
+        /// The id of the lobby.
+        lobby: u32,
+        /// The slot assigned to the local player.
+        slot: u32,
+    },
+    /// The server refused to let this client join a lobby, because it is full or its match
+    /// already started.
+    LobbyRejected {
+        /// The requested lobby, or `None` when looking for any lobby.
+        lobby: Option<u32>,
+    },
+    /// This client left its lobby.
+    LobbyLeft {
+        /// The id of the lobby.
+        lobby: u32,
+    },
+    /// The players in the lobby of this client changed.
+    LobbyUpdated {
+        /// The id of the lobby.
+        lobby: u32,
+        /// The players in the lobby, in slot order.
+        players: Vec<LobbySlot>,
+    },
+    /// Every player in the lobby is ready, and the match starts.
+    MatchStarted {
+        /// The id of the lobby.
+        lobby: u32,
+        /// The simulation tick on which the match begins.
+        tick: u64,
+    },
+    /// This client joined a match in progress, and loaded the world state of another client.
+    /// The ticks simulated by the other clients since are simulated to catch up with them.
+    MatchJoined {
+        /// The last tick simulated before the loaded state was saved.
+        tick: u64,
+    },
+    /// This client spectates the match of a lobby.
+    Spectating {
+        /// The id of the lobby.
+        lobby: u32,
+    },
+}
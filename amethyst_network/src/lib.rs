@@ -0,0 +1,75 @@
+//! Lockstep networking for Amethyst.
+//!
+//! Add the `LockstepBundle` to your game data to relay the local input to a lockstep server and
+//! to only let the game advance on frames confirmed by that server. The `LockstepServer` relays
+//! the input between the clients, over any `Transport`. The server can host several matches in
+//! lobbies, which the clients join through the `Session` resource.
+//!
+//! Input travels as changes of the bound actions and axes, tagged with the player which made
+//! them, so every client can tell the players apart. The clients also exchange checksums of
+//! their world state, and report a `NetworkEvent::DesyncDetected` when they drift apart. The
+//! latency to the server is measured with pings and reported in the `NetworkStats` resource.
+//!
+//! Clients can join a match in progress, for example after losing their connection, by loading
+//! the world state of another client and simulating the frames confirmed since, see
+//! `LockstepBundle::with_state` and `LockstepBundle::with_reconnect`.
+//!
+//! Optionally, the simulation can predict ticks ahead of the server and roll back when a
+//! prediction turns out wrong, see `LockstepBundle::with_rollback`.
+//!
+//! For modes where the server owns the game state, the `replication` module sends the state of
+//! the server's entities to its clients instead of their input.
+
+extern crate amethyst_core;
+extern crate amethyst_input;
+extern crate bincode;
+#[macro_use]
+extern crate error_chain;
+extern crate fnv;
+extern crate hibitset;
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate serde;
+
+#[cfg(feature = "profiler")]
+extern crate thread_profiler;
+
+pub use self::bundle::LockstepBundle;
+pub use self::connection::Connection;
+pub use self::error::{Error, ErrorKind, Result};
+pub use self::event::NetworkEvent;
+pub use self::message::{EntityState, LobbySlot, Message, PlayerInput};
+pub use self::replication::{
+    NetworkId, NetworkIds, Replicated, ReplicationClientBundle, ReplicationClientSystem,
+    ReplicationServerBundle, ReplicationServerSystem,
+};
+pub use self::rollback::RollbackScheduler;
+pub use self::scheduler::LockstepScheduler;
+pub use self::server::LockstepServer;
+pub use self::session::Session;
+pub use self::stats::NetworkStats;
+pub use self::system::LockstepSystem;
+pub use self::transport::{
+    LinkClock, LinkConditions, Listener, LoopbackConnector, LoopbackListener, LoopbackTransport,
+    SimulatedListener, SimulatedTransport, TcpListener, TcpTransport, Transport, UdpListener,
+    UdpTransport,
+};
+pub use self::world_state::WorldStateSystem;
+
+pub mod replication;
+pub mod transport;
+
+mod bundle;
+mod codec;
+mod connection;
+mod error;
+mod event;
+mod message;
+mod rollback;
+mod scheduler;
+mod server;
+mod session;
+mod stats;
+mod system;
+mod world_state;
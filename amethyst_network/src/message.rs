@@ -0,0 +1,200 @@
+//! Messages exchanged between the clients and the servers.
+
+use bincode;
+use error::Result;
+
+/// The encoded input a player sent during a frame.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerInput {
+    /// The player which sent the input, as assigned by the server.
+    pub player: u32,
+    /// The input events, encoded by the client. The server never looks into them.
+    pub data: Vec<u8>,
+}
+
+/// A player in a lobby.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LobbySlot {
+    /// The player id assigned by the server.
+    pub player: u32,
+    /// The slot of the player in the lobby, starting at 0.
+    pub slot: u32,
+    /// True if the player is ready for the match to start.
+    pub ready: bool,
+}
+
+/// The replicated components of an entity, as part of a `Snapshot` message.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EntityState {
+    /// The network id of the entity, assigned by the server.
+    pub id: u64,
+    /// The encoded components of the entity, along with the index of their registration.
+    /// Registered components missing from the list were removed from the entity.
+    pub components: Vec<(u8, Vec<u8>)>,
+}
+
+/// Message of the lockstep protocol.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+    /// Sent by the server to a newly connected client.
+    Welcome {
+        /// The player id assigned to the client.
+        player: u32,
+    },
+    /// Input of a player, sent by a client to the server.
+    DataInput {
+        /// The frame the input is meant for. The server relays it with that frame, or with the
+        /// next one if that frame was already broadcast.
+        frame: u64,
+        /// The input events, encoded by the client.
+        data: Vec<u8>,
+    },
+    /// Checksum of the world state of a client, sent by the client to the server.
+    Checksum {
+        /// The simulation tick on which the checksum was computed.
+        tick: u64,
+        /// The checksum.
+        checksum: u64,
+    },
+    /// Checksum of the world state of a player, relayed by the server to every client.
+    PlayerChecksum {
+        /// The player which computed the checksum.
+        player: u32,
+        /// The simulation tick on which the checksum was computed.
+        tick: u64,
+        /// The checksum.
+        checksum: u64,
+    },
+    /// All inputs of a frame, broadcast by the server to every client.
+    DataFrame {
+        /// Number of the frame, starting at 1.
+        frame: u64,
+        /// The inputs of all players received during the frame, in the order they arrived.
+        inputs: Vec<PlayerInput>,
+    },
+    /// Measures the round-trip time, sent by a client to the server.
+    Ping {
+        /// Identifies the ping in the answer of the server.
+        id: u64,
+    },
+    /// Answer of the server to a `Ping`.
+    Pong {
+        /// The id of the answered ping.
+        id: u64,
+        /// Number of the last frame broadcast by the server.
+        frame: u64,
+    },
+    /// Creates a new lobby and joins it, sent by a client to the server.
+    CreateLobby,
+    /// Joins a lobby, sent by a client to the server.
+    JoinLobby {
+        /// The lobby to join, which is created if it doesn't exist. If `None`, the first lobby
+        /// with a free slot is joined, or a new one created.
+        lobby: Option<u32>,
+    },
+    /// Leaves the current lobby, sent by a client to the server.
+    LeaveLobby,
+    /// Tells whether the player is ready for the match to start, sent by a client to the server.
+    Ready {
+        /// True if the player is ready.
+        ready: bool,
+    },
+    /// Sent by the server to a client which joined a lobby.
+    LobbyJoined {
+        /// The id of the lobby.
+        lobby: u32,
+        /// The slot assigned to the player.
+        slot: u32,
+    },
+    /// Sent by the server to a client which could not join a lobby, because it is full or its
+    /// match already started.
+    LobbyRejected {
+        /// The requested lobby.
+        lobby: Option<u32>,
+    },
+    /// Sent by the server to a client which left a lobby.
+    LobbyLeft {
+        /// The id of the lobby.
+        lobby: u32,
+    },
+    /// Players of a lobby, sent by the server to the clients in the lobby when they change.
+    LobbyUpdate {
+        /// The id of the lobby.
+        lobby: u32,
+        /// The players in the lobby, in slot order.
+        players: Vec<LobbySlot>,
+    },
+    /// Sent by the server to the clients in a lobby once every player is ready.
+    MatchStart {
+        /// The id of the lobby.
+        lobby: u32,
+        /// The first tick of the match. Frames are broadcast from tick 1 on, so the clients have
+        /// time to get ready for it.
+        tick: u64,
+    },
+    /// Joins the match in progress of a lobby, sent by a client to the server, for example after
+    /// reconnecting.
+    Rejoin {
+        /// The id of the lobby, 0 if the server doesn't use lobbies.
+        lobby: u32,
+        /// The player id this client had in the match. If that player left the match, the
+        /// client takes its player id and slot back.
+        player: Option<u32>,
+    },
+    /// Asks a client for its world state, sent by the server when another client joins the
+    /// match in progress.
+    StateRequest,
+    /// The serialized world state of a client after a tick.
+    ///
+    /// Sent by a client to the server in answer to a `StateRequest`, and forwarded by the server
+    /// to the clients joining the match, followed by the frames broadcast after that tick.
+    WorldState {
+        /// The last tick simulated before the state was saved.
+        tick: u64,
+        /// The state, encoded by the client. The server never looks into it.
+        data: Vec<u8>,
+    },
+    /// Watches the match of a lobby without playing in it, sent by a client to the server.
+    Spectate {
+        /// The id of the lobby, 0 if the server doesn't use lobbies.
+        lobby: u32,
+    },
+    /// Sent by the server to a client which spectates a lobby. The frames of the match are sent
+    /// to it with the spectator delay of the server.
+    Spectating {
+        /// The id of the lobby.
+        lobby: u32,
+    },
+    /// Tells the server the client is leaving, sent by a client right before it closes the
+    /// connection.
+    Disconnect,
+    /// Replicated state of the world, sent by a replication server to every client.
+    Snapshot {
+        /// The tick of the server on which the snapshot was taken.
+        tick: u64,
+        /// The tick of the snapshot the changes are relative to, or 0 if the snapshot contains
+        /// every replicated entity.
+        baseline: u64,
+        /// Network ids of the entities removed since the baseline.
+        removed: Vec<u64>,
+        /// The entities which changed since the baseline.
+        entities: Vec<EntityState>,
+    },
+    /// Acknowledges a snapshot, sent by a replication client to the server.
+    SnapshotAck {
+        /// The tick of the applied snapshot.
+        tick: u64,
+    },
+}
+
+impl Message {
+    /// Encodes the message for sending it over a `Transport`.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Decodes a message received from a `Transport`.
+    pub fn decode(bytes: &[u8]) -> Result<Message> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
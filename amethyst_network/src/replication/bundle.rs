@@ -0,0 +1,100 @@
+//! ECS replication bundles
+
+use super::{ReplicationClientSystem, ReplicationServerSystem};
+use amethyst_core::bundle::{Result, SystemBundle};
+use amethyst_core::specs::prelude::{Component, DispatcherBuilder};
+use amethyst_core::specs::storage::Tracked;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use transport::{Listener, Transport};
+
+/// Bundle for the server side of the replication.
+///
+/// Adds the thread-local `ReplicationServerSystem`, which sends the registered components of the
+/// entities marked `Replicated` to the clients accepted from the given `Listener`. Add it to the
+/// simulation stage of the game data to replicate the state once per tick, after the gameplay
+/// systems ran.
+///
+/// ## Errors
+///
+/// No errors will be returned by this bundle.
+///
+pub struct ReplicationServerBundle {
+    system: ReplicationServerSystem,
+}
+
+impl ReplicationServerBundle {
+    /// Creates a new replication server bundle accepting clients from `listener`.
+    pub fn new<L>(listener: L) -> Self
+    where
+        L: Listener + 'static,
+    {
+        ReplicationServerBundle {
+            system: ReplicationServerSystem::new(listener),
+        }
+    }
+
+    /// Replicate the components of type `C`
+    ///
+    /// The component needs to use a `FlaggedStorage`, and to be registered in the same order as
+    /// in the `ReplicationClientBundle`.
+    pub fn with<C>(mut self) -> Self
+    where
+        C: Component + Serialize,
+        C::Storage: Tracked,
+    {
+        self.system = self.system.with::<C>();
+        self
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for ReplicationServerBundle {
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
+        builder.add_thread_local(self.system);
+        Ok(())
+    }
+}
+
+/// Bundle for the client side of the replication.
+///
+/// Adds the thread-local `ReplicationClientSystem`, which applies the snapshots received through
+/// the given `Transport` to the local world.
+///
+/// ## Errors
+///
+/// No errors will be returned by this bundle.
+///
+pub struct ReplicationClientBundle {
+    system: ReplicationClientSystem,
+}
+
+impl ReplicationClientBundle {
+    /// Creates a new replication client bundle receiving snapshots through `transport`.
+    pub fn new<T>(transport: T) -> Self
+    where
+        T: Transport + 'static,
+    {
+        ReplicationClientBundle {
+            system: ReplicationClientSystem::new(transport),
+        }
+    }
+
+    /// Apply the replicated components of type `C`
+    ///
+    /// The component needs to be registered in the same order as in the
+    /// `ReplicationServerBundle`.
+    pub fn with<C>(mut self) -> Self
+    where
+        C: Component + DeserializeOwned,
+    {
+        self.system = self.system.with::<C>();
+        self
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for ReplicationClientBundle {
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
+        builder.add_thread_local(self.system);
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+//! Client side of the state replication.
+
+use super::{NetworkId, NetworkIds};
+use amethyst_core::specs::prelude::{
+    Component, Entities, Entity, ReadStorage, Resources, RunNow, SystemData, Write, WriteStorage,
+};
+use bincode;
+use error::{ErrorKind, Result};
+use fnv::FnvHashSet;
+use message::{EntityState, Message};
+use serde::de::DeserializeOwned;
+use transport::Transport;
+
+type ComponentDecoder = fn(&Resources, Entity, Option<&[u8]>) -> Result<()>;
+type ComponentSetup = fn(&mut Resources);
+
+/// Applies the snapshots sent by a `ReplicationServerSystem` to the local world.
+///
+/// Every replicated entity of the server is mirrored by a local entity, created when it first
+/// shows up in a snapshot and deleted once the server removed it. The mirrored entities carry
+/// the `NetworkId` of the server entity and can be looked up in the `NetworkIds` resource.
+///
+/// Snapshots older than the last applied one are dropped, and every applied snapshot is
+/// acknowledged to the server, so that it only sends the changes since.
+///
+/// This is a thread-local system. Add it with the `ReplicationClientBundle`.
+pub struct ReplicationClientSystem {
+    transport: Box<Transport>,
+    components: Vec<(ComponentDecoder, ComponentSetup)>,
+    tick: u64,
+    disconnected: bool,
+}
+
+impl ReplicationClientSystem {
+    /// Creates a system receiving snapshots through the given transport, without any component.
+    pub fn new<T>(transport: T) -> Self
+    where
+        T: Transport + 'static,
+    {
+        ReplicationClientSystem {
+            transport: Box::new(transport),
+            components: Vec::new(),
+            tick: 0,
+            disconnected: false,
+        }
+    }
+
+    /// Applies the replicated components of type `C`.
+    ///
+    /// The components must be registered in the same order as on the server.
+    pub fn with<C>(mut self) -> Self
+    where
+        C: Component + DeserializeOwned,
+    {
+        self.components.push((
+            decode_component::<C> as ComponentDecoder,
+            setup_component::<C> as ComponentSetup,
+        ));
+        self
+    }
+
+    /// Returns the server tick of the last applied snapshot.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    fn receive(&mut self, res: &Resources) -> Result<()> {
+        while let Some(bytes) = self.transport.recv()? {
+            match Message::decode(&bytes)? {
+                Message::Snapshot {
+                    tick,
+                    baseline,
+                    removed,
+                    entities,
+                } => {
+                    if tick <= self.tick {
+                        continue;
+                    }
+                    if baseline > self.tick {
+                        warn!(
+                            "Dropping snapshot {} relative to unknown snapshot {}",
+                            tick, baseline
+                        );
+                        continue;
+                    }
+                    self.apply(res, baseline == 0, &removed, &entities)?;
+                    self.tick = tick;
+                    self.transport
+                        .send(&Message::SnapshotAck { tick }.encode()?)?;
+                }
+                message => warn!("Unexpected message from replication server: {:?}", message),
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(
+        &self,
+        res: &Resources,
+        full: bool,
+        removed: &[u64],
+        states: &[EntityState],
+    ) -> Result<()> {
+        let targets = {
+            let (entities, mut network_ids, mut ids) =
+                <(Entities, WriteStorage<NetworkId>, Write<NetworkIds>)>::fetch(res);
+            let mut deleted = ids.retain(|entity| entities.is_alive(entity));
+            if full {
+                let sent = states.iter().map(|state| state.id).collect::<FnvHashSet<_>>();
+                deleted.extend(removed.iter().cloned());
+                let stale = ids
+                    .entities
+                    .keys()
+                    .cloned()
+                    .filter(|id| !sent.contains(id))
+                    .collect::<Vec<_>>();
+                deleted.extend(stale);
+            } else {
+                deleted.extend(removed.iter().cloned());
+            }
+            for id in deleted {
+                if let Some(entity) = ids.remove(id) {
+                    let _ = entities.delete(entity);
+                }
+            }
+
+            let mut targets = Vec::with_capacity(states.len());
+            for state in states {
+                let entity = match ids.entity(NetworkId(state.id)) {
+                    Some(entity) => entity,
+                    None => {
+                        let entity = entities.create();
+                        network_ids
+                            .insert(entity, NetworkId(state.id))
+                            .expect("Failed to mirror a replicated entity");
+                        ids.insert(state.id, entity);
+                        entity
+                    }
+                };
+                targets.push(entity);
+            }
+            targets
+        };
+
+        for (entity, state) in targets.into_iter().zip(states) {
+            for (index, &(decode, _)) in self.components.iter().enumerate() {
+                let data = state
+                    .components
+                    .iter()
+                    .find(|&&(component, _)| component as usize == index)
+                    .map(|&(_, ref data)| &data[..]);
+                decode(res, entity, data)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn decode_component<C>(res: &Resources, entity: Entity, data: Option<&[u8]>) -> Result<()>
+where
+    C: Component + DeserializeOwned,
+{
+    let mut storage = WriteStorage::<C>::fetch(res);
+    match data {
+        Some(data) => {
+            let component: C = bincode::deserialize(data)?;
+            if storage.insert(entity, component).is_err() {
+                warn!("Failed to apply a replicated component to {:?}", entity);
+            }
+        }
+        None => {
+            storage.remove(entity);
+        }
+    }
+    Ok(())
+}
+
+fn setup_component<C>(res: &mut Resources)
+where
+    C: Component + DeserializeOwned,
+{
+    ReadStorage::<C>::setup(res);
+}
+
+impl<'a> RunNow<'a> for ReplicationClientSystem {
+    fn run_now(&mut self, res: &'a Resources) {
+        if self.disconnected {
+            return;
+        }
+        if let Err(err) = self.receive(res) {
+            match *err.kind() {
+                ErrorKind::Disconnected => info!("Disconnected from the replication server"),
+                _ => error!("Replication failed: {}", err),
+            }
+            self.disconnected = true;
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        <(Entities, ReadStorage<NetworkId>)>::setup(res);
+        res.entry::<NetworkIds>().or_insert_with(NetworkIds::default);
+        for &(_, setup) in &self.components {
+            setup(res);
+        }
+    }
+}
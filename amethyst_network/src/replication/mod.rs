@@ -0,0 +1,141 @@
+//! Server authoritative replication of components.
+//!
+//! The server marks the entities to replicate with the `Replicated` component. Every run, the
+//! `ReplicationServerSystem` assigns a `NetworkId` to new replicated entities and sends each
+//! client a snapshot of the entities which changed since the last snapshot the client
+//! acknowledged. Changes are detected through the `FlaggedStorage` of the registered components.
+//!
+//! The `ReplicationClientSystem` applies the snapshots to mirrored entities, which it looks up
+//! by network id in the `NetworkIds` resource.
+
+pub use self::bundle::{ReplicationClientBundle, ReplicationServerBundle};
+pub use self::client::ReplicationClientSystem;
+pub use self::server::ReplicationServerSystem;
+
+use amethyst_core::specs::prelude::{Component, DenseVecStorage, Entity, NullStorage};
+use fnv::FnvHashMap;
+
+mod bundle;
+mod client;
+mod server;
+
+/// Identifies a replicated entity across the server and its clients.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NetworkId(pub u64);
+
+impl Component for NetworkId {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Marks an entity of the server whose registered components are replicated to the clients.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Replicated;
+
+impl Component for Replicated {
+    type Storage = NullStorage<Self>;
+}
+
+/// Maps the network ids of the replicated entities to the local entities.
+///
+/// On the server, these are the entities marked `Replicated`. On a client, these are the
+/// entities mirroring them.
+#[derive(Debug, Default)]
+pub struct NetworkIds {
+    entities: FnvHashMap<u64, Entity>,
+}
+
+impl NetworkIds {
+    /// Returns the local entity with the given network id.
+    pub fn entity(&self, id: NetworkId) -> Option<Entity> {
+        self.entities.get(&id.0).cloned()
+    }
+
+    /// Returns the number of replicated entities.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns true if there is no replicated entity.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    fn insert(&mut self, id: u64, entity: Entity) {
+        self.entities.insert(id, entity);
+    }
+
+    fn remove(&mut self, id: u64) -> Option<Entity> {
+        self.entities.remove(&id)
+    }
+
+    /// Keeps the entities for which `f` returns true, and returns the ids of the others.
+    fn retain<F>(&mut self, mut f: F) -> Vec<u64>
+    where
+        F: FnMut(Entity) -> bool,
+    {
+        let mut removed = Vec::new();
+        self.entities.retain(|&id, &mut entity| {
+            let keep = f(entity);
+            if !keep {
+                removed.push(id);
+            }
+            keep
+        });
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::specs::prelude::{Builder, RunNow, World};
+    use amethyst_core::transform::Transform;
+    use transport::LoopbackListener;
+
+    fn position(world: &World, id: u64) -> Option<i32> {
+        let entity = world.read_resource::<NetworkIds>().entity(NetworkId(id))?;
+        world
+            .read_storage::<Transform>()
+            .get(entity)
+            .map(|transform| transform.translation.x as i32)
+    }
+
+    #[test]
+    fn mirrors_replicated_entities() {
+        let (listener, connector) = LoopbackListener::bind();
+        let mut server_world = World::new();
+        let mut server = ReplicationServerSystem::new(listener).with::<Transform>();
+        RunNow::setup(&mut server, &mut server_world.res);
+        let mut client_world = World::new();
+        let mut client =
+            ReplicationClientSystem::new(connector.connect().unwrap()).with::<Transform>();
+        RunNow::setup(&mut client, &mut client_world.res);
+
+        let entity = server_world
+            .create_entity()
+            .with(Transform::default())
+            .with(Replicated)
+            .build();
+        server_world.create_entity().with(Transform::default()).build();
+        server.run_now(&server_world.res);
+        client.run_now(&client_world.res);
+        assert_eq!(client_world.read_resource::<NetworkIds>().len(), 1);
+        assert_eq!(position(&client_world, 1), Some(0));
+
+        server_world
+            .write_storage::<Transform>()
+            .get_mut(entity)
+            .unwrap()
+            .translation
+            .x = 3.0;
+        server.run_now(&server_world.res);
+        client.run_now(&client_world.res);
+        assert_eq!(client.tick(), 2);
+        assert_eq!(position(&client_world, 1), Some(3));
+
+        server_world.delete_entity(entity).unwrap();
+        server.run_now(&server_world.res);
+        client.run_now(&client_world.res);
+        assert!(client_world.read_resource::<NetworkIds>().is_empty());
+    }
+}
@@ -0,0 +1,356 @@
+//! Server side of the state replication.
+
+use super::{NetworkId, NetworkIds, Replicated};
+use amethyst_core::specs::prelude::{
+    Component, Entities, Entity, InsertedFlag, Join, ModifiedFlag, ReadStorage, ReaderId,
+    RemovedFlag, Resources, RunNow, SystemData, Write, WriteStorage,
+};
+use amethyst_core::specs::storage::Tracked;
+use bincode;
+use error::{ErrorKind, Result};
+use hibitset::BitSet;
+use message::{EntityState, Message};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::mem;
+use transport::{Listener, Transport};
+
+/// Number of ticks of changes kept to compute deltas against.
+const HISTORY: usize = 64;
+
+type ComponentEncoder = fn(&Resources, &[Entity]) -> Result<Vec<Option<Vec<u8>>>>;
+type ComponentChanges = fn(&Resources, &mut ComponentReaders, &mut BitSet);
+type ComponentTracker = fn(&mut Resources) -> ComponentReaders;
+
+struct ComponentReaders {
+    inserted: ReaderId<InsertedFlag>,
+    modified: ReaderId<ModifiedFlag>,
+    removed: ReaderId<RemovedFlag>,
+}
+
+struct ReplicatedComponent {
+    encode: ComponentEncoder,
+    changes: ComponentChanges,
+    track: ComponentTracker,
+    readers: Option<ComponentReaders>,
+}
+
+/// The entities changed and removed in a tick.
+struct Changes {
+    tick: u64,
+    entities: BitSet,
+    removed: Vec<u64>,
+}
+
+struct Client {
+    transport: Box<Transport>,
+    acked: u64,
+}
+
+/// Sends the state of the replicated entities to the connected clients.
+///
+/// Every run is a tick of the replication. New entities marked `Replicated` are assigned a
+/// `NetworkId` and added to the `NetworkIds`. Each client is then sent the entities which
+/// changed since the last snapshot it acknowledged, along with the ones removed since. Clients
+/// which have not acknowledged any snapshot yet, or one too old to compute a delta against, are
+/// sent every replicated entity.
+///
+/// An entity changes when one of the registered components is inserted, modified or removed,
+/// which requires the components to use a `FlaggedStorage`. Changed entities are sent with all
+/// of their registered components.
+///
+/// This is a thread-local system, so that it runs after all the other systems of the
+/// dispatcher. Add it with the `ReplicationServerBundle`.
+pub struct ReplicationServerSystem {
+    listener: Box<Listener>,
+    clients: Vec<Client>,
+    components: Vec<ReplicatedComponent>,
+    history: VecDeque<Changes>,
+    next_id: u64,
+    tick: u64,
+}
+
+impl ReplicationServerSystem {
+    /// Creates a system accepting clients from the given listener, without any component.
+    pub fn new<L>(listener: L) -> Self
+    where
+        L: Listener + 'static,
+    {
+        ReplicationServerSystem {
+            listener: Box::new(listener),
+            clients: Vec::new(),
+            components: Vec::new(),
+            history: VecDeque::new(),
+            next_id: 1,
+            tick: 0,
+        }
+    }
+
+    /// Replicates the components of type `C`.
+    ///
+    /// Components are identified by the order they were registered in, so the clients need to
+    /// register the same components in the same order.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if more than 256 components are registered.
+    pub fn with<C>(mut self) -> Self
+    where
+        C: Component + Serialize,
+        C::Storage: Tracked,
+    {
+        assert!(
+            self.components.len() <= u8::max_value() as usize,
+            "Too many replicated components"
+        );
+        self.components.push(ReplicatedComponent {
+            encode: encode_components::<C>,
+            changes: component_changes::<C>,
+            track: track_components::<C>,
+            readers: None,
+        });
+        self
+    }
+
+    /// Returns the number of connected clients.
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Returns the number of the last replication tick.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Assigns network ids to the new replicated entities, and returns the changes of the tick.
+    fn collect_changes(&mut self, res: &Resources) -> Changes {
+        let mut changes = Changes {
+            tick: self.tick,
+            entities: BitSet::new(),
+            removed: Vec::new(),
+        };
+        for component in &mut self.components {
+            let readers = component
+                .readers
+                .as_mut()
+                .expect("`setup` was not called before `run_now`");
+            (component.changes)(res, readers, &mut changes.entities);
+        }
+
+        let (entities, replicated, mut network_ids, mut ids) = <(
+            Entities,
+            ReadStorage<Replicated>,
+            WriteStorage<NetworkId>,
+            Write<NetworkIds>,
+        )>::fetch(res);
+        changes.removed = ids.retain(|entity| replicated.get(entity).is_some());
+
+        let stale = (&*entities, &network_ids, !&replicated)
+            .join()
+            .map(|(entity, _, _)| entity)
+            .collect::<Vec<_>>();
+        for entity in stale {
+            network_ids.remove(entity);
+        }
+
+        let new = (&*entities, &replicated, !&network_ids)
+            .join()
+            .map(|(entity, _, _)| entity)
+            .collect::<Vec<_>>();
+        for entity in new {
+            let id = self.next_id;
+            self.next_id += 1;
+            network_ids
+                .insert(entity, NetworkId(id))
+                .expect("Failed to assign a network id to a live entity");
+            ids.insert(id, entity);
+            changes.entities.add(entity.id());
+        }
+        changes
+    }
+
+    /// Builds the snapshot of the current tick relative to the given baseline.
+    ///
+    /// The snapshot contains every replicated entity if the changes since the baseline are not
+    /// known.
+    fn snapshot(&self, baseline: u64, res: &Resources) -> Result<Message> {
+        let delta = baseline > 0 && self
+            .history
+            .front()
+            .map_or(false, |changes| changes.tick <= baseline + 1);
+
+        let mut changed = BitSet::new();
+        let mut removed = Vec::new();
+        if delta {
+            for changes in self.history.iter().filter(|changes| changes.tick > baseline) {
+                for index in (&changes.entities).join() {
+                    changed.add(index);
+                }
+                removed.extend(changes.removed.iter().cloned());
+            }
+        }
+
+        let sent = {
+            let (entities, replicated, network_ids) =
+                <(Entities, ReadStorage<Replicated>, ReadStorage<NetworkId>)>::fetch(res);
+            if delta {
+                (&*entities, &network_ids, &replicated, &changed)
+                    .join()
+                    .map(|(entity, id, _, _)| (entity, id.0))
+                    .collect::<Vec<_>>()
+            } else {
+                (&*entities, &network_ids, &replicated)
+                    .join()
+                    .map(|(entity, id, _)| (entity, id.0))
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        let targets = sent.iter().map(|&(entity, _)| entity).collect::<Vec<_>>();
+        let mut states = sent
+            .iter()
+            .map(|&(_, id)| EntityState {
+                id,
+                components: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        for (index, component) in self.components.iter().enumerate() {
+            let encoded = (component.encode)(res, &targets)?;
+            for (state, data) in states.iter_mut().zip(encoded) {
+                if let Some(data) = data {
+                    state.components.push((index as u8, data));
+                }
+            }
+        }
+
+        Ok(Message::Snapshot {
+            tick: self.tick,
+            baseline: if delta { baseline } else { 0 },
+            removed,
+            entities: states,
+        })
+    }
+}
+
+fn encode_components<C>(res: &Resources, entities: &[Entity]) -> Result<Vec<Option<Vec<u8>>>>
+where
+    C: Component + Serialize,
+{
+    let storage = ReadStorage::<C>::fetch(res);
+    let mut encoded = Vec::with_capacity(entities.len());
+    for &entity in entities {
+        encoded.push(match storage.get(entity) {
+            Some(component) => Some(bincode::serialize(component)?),
+            None => None,
+        });
+    }
+    Ok(encoded)
+}
+
+fn component_changes<C>(res: &Resources, readers: &mut ComponentReaders, changed: &mut BitSet)
+where
+    C: Component,
+    C::Storage: Tracked,
+{
+    let storage = ReadStorage::<C>::fetch(res);
+    storage.populate_inserted(&mut readers.inserted, changed);
+    storage.populate_modified(&mut readers.modified, changed);
+    storage.populate_removed(&mut readers.removed, changed);
+}
+
+fn track_components<C>(res: &mut Resources) -> ComponentReaders
+where
+    C: Component,
+    C::Storage: Tracked,
+{
+    ReadStorage::<C>::setup(res);
+    let mut storage = WriteStorage::<C>::fetch(res);
+    ComponentReaders {
+        inserted: storage.track_inserted(),
+        modified: storage.track_modified(),
+        removed: storage.track_removed(),
+    }
+}
+
+/// Collects the snapshot acknowledgements of a client.
+fn receive(client: &mut Client) -> Result<()> {
+    while let Some(bytes) = client.transport.recv()? {
+        match Message::decode(&bytes)? {
+            Message::SnapshotAck { tick } => client.acked = client.acked.max(tick),
+            message => warn!("Unexpected message from replication client: {:?}", message),
+        }
+    }
+    Ok(())
+}
+
+/// Applies `f` to every client, dropping the clients for which it fails.
+fn retain_clients<F>(clients: &mut Vec<Client>, mut f: F)
+where
+    F: FnMut(&mut Client) -> Result<()>,
+{
+    let mut i = 0;
+    while i < clients.len() {
+        match f(&mut clients[i]) {
+            Ok(()) => i += 1,
+            Err(err) => {
+                match *err.kind() {
+                    ErrorKind::Disconnected => info!("Replication client disconnected"),
+                    _ => warn!("Dropping replication client: {}", err),
+                }
+                clients.remove(i);
+            }
+        }
+    }
+}
+
+impl<'a> RunNow<'a> for ReplicationServerSystem {
+    fn run_now(&mut self, res: &'a Resources) {
+        self.tick += 1;
+        let changes = self.collect_changes(res);
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(changes);
+
+        loop {
+            match self.listener.accept() {
+                Ok(Some(transport)) => {
+                    info!("Replication client connected");
+                    self.clients.push(Client {
+                        transport,
+                        acked: 0,
+                    });
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    error!("Failed to accept replication clients: {}", err);
+                    break;
+                }
+            }
+        }
+        retain_clients(&mut self.clients, receive);
+
+        let mut clients = mem::replace(&mut self.clients, Vec::new());
+        let mut cached: Option<(u64, Vec<u8>)> = None;
+        retain_clients(&mut clients, |client| {
+            let hit = cached
+                .as_ref()
+                .map_or(false, |&(baseline, _)| baseline == client.acked);
+            if !hit {
+                let snapshot = self.snapshot(client.acked, res)?.encode()?;
+                cached = Some((client.acked, snapshot));
+            }
+            let bytes = &cached.as_ref().expect("unreachable").1;
+            client.transport.send(bytes)
+        });
+        self.clients = clients;
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        <(Entities, ReadStorage<Replicated>, WriteStorage<NetworkId>)>::setup(res);
+        res.entry::<NetworkIds>().or_insert_with(NetworkIds::default);
+        for component in &mut self.components {
+            component.readers = Some((component.track)(res));
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Prediction of frames ahead of the lockstep server, for rollback networking.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::mem;
+use std::time::Duration;
+
+/// Releases predicted frames ahead of the lockstep server, and detects mispredictions.
+///
+/// Instead of waiting for the server to confirm a frame, the simulation advances one tick per
+/// tick duration using the local input scheduled for that tick. The other players are predicted
+/// to keep their last input state, so no event of theirs is part of a predicted frame. At most
+/// `max_prediction` ticks are simulated ahead of the last confirmed frame.
+///
+/// When a confirmed frame does not match the events predicted for its tick, the simulation has
+/// to be rolled back to that tick, and the following ticks simulated again with the corrected
+/// frames returned by `take_rewind`. Confirmed frames which have not been predicted yet are
+/// released right away, to catch up with the server.
+///
+/// `E` is the type of the input events of a frame.
+#[derive(Debug)]
+pub struct RollbackScheduler<E> {
+    max_prediction: u64,
+    simulated: u64,
+    confirmed: u64,
+    unconfirmed: VecDeque<(u64, Vec<E>)>,
+    ready: VecDeque<(u64, Vec<E>)>,
+    local: BTreeMap<u64, Vec<E>>,
+    recent: Vec<(u64, Vec<E>)>,
+    rewind: Option<u64>,
+    elapsed: Duration,
+}
+
+impl<E> RollbackScheduler<E>
+where
+    E: Clone + PartialEq,
+{
+    /// Creates a scheduler predicting at most `max_prediction` ticks ahead of the server.
+    pub fn new(max_prediction: u64) -> Self {
+        RollbackScheduler {
+            max_prediction,
+            simulated: 0,
+            confirmed: 0,
+            unconfirmed: VecDeque::new(),
+            ready: VecDeque::new(),
+            local: BTreeMap::new(),
+            recent: Vec::new(),
+            rewind: None,
+            elapsed: Duration::from_secs(0),
+        }
+    }
+
+    /// Returns the last tick released to the simulation.
+    pub fn simulated(&self) -> u64 {
+        self.simulated
+    }
+
+    /// Returns the number of the last frame confirmed by the server.
+    pub fn confirmed(&self) -> u64 {
+        self.confirmed
+    }
+
+    /// Schedules local input events for the given tick, to be part of its predicted frame.
+    ///
+    /// Events for a tick which was already released are ignored; the server will confirm them
+    /// for a later frame.
+    pub fn schedule(&mut self, tick: u64, events: Vec<E>) {
+        if tick > self.simulated && !events.is_empty() {
+            self.local
+                .entry(tick)
+                .or_insert_with(Vec::new)
+                .extend(events);
+        }
+    }
+
+    /// Adds a frame confirmed by the server.
+    pub fn confirm(&mut self, frame: u64, events: Vec<E>) {
+        self.confirmed = frame;
+        if frame > self.simulated {
+            self.ready.push_back((frame, events));
+            return;
+        }
+        if let Some((tick, predicted)) = self.unconfirmed.pop_front() {
+            if tick != frame || predicted != events {
+                self.rewind = Some(self.rewind.map_or(frame, |rewind| rewind.min(frame)));
+            }
+        }
+        self.recent.push((frame, events));
+    }
+
+    /// Forgets all frames, and continues after the given tick, whose state was loaded from
+    /// another client.
+    pub fn reset(&mut self, tick: u64) {
+        self.simulated = tick;
+        self.confirmed = tick;
+        self.unconfirmed.clear();
+        self.ready.clear();
+        self.local.clear();
+        self.recent.clear();
+        self.rewind = None;
+        self.elapsed = Duration::from_secs(0);
+    }
+
+    /// Returns the tick to roll back to after a misprediction, along with the frames of that
+    /// tick and all the ticks released after it.
+    ///
+    /// Call it after confirming the frames received from the server.
+    pub fn take_rewind(&mut self) -> Option<(u64, Vec<(u64, Vec<E>)>)> {
+        let recent = mem::replace(&mut self.recent, Vec::new());
+        let tick = self.rewind.take()?;
+        let frames = recent
+            .into_iter()
+            .filter(|&(frame, _)| frame >= tick)
+            .chain(self.unconfirmed.iter().cloned())
+            .collect();
+        Some((tick, frames))
+    }
+
+    /// Returns the next frame to simulate, if any.
+    ///
+    /// `elapsed` is the time since the last call and `tick_time` the duration of a tick.
+    pub fn next(&mut self, elapsed: Duration, tick_time: Duration) -> Option<(u64, Vec<E>)> {
+        if let Some((tick, events)) = self.ready.pop_front() {
+            self.release(tick);
+            self.elapsed = Duration::from_secs(0);
+            return Some((tick, events));
+        }
+        if self.confirmed == 0 {
+            return None;
+        }
+
+        self.elapsed += elapsed;
+        if self.elapsed < tick_time {
+            return None;
+        }
+        if self.simulated - self.confirmed >= self.max_prediction {
+            self.elapsed = tick_time;
+            return None;
+        }
+        self.elapsed -= tick_time;
+
+        let tick = self.simulated + 1;
+        let events = self.local.remove(&tick).unwrap_or_default();
+        self.unconfirmed.push_back((tick, events.clone()));
+        self.release(tick);
+        Some((tick, events))
+    }
+
+    fn release(&mut self, tick: u64) {
+        self.simulated = tick;
+        self.local = self.local.split_off(&(tick + 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: u64 = 20;
+
+    fn next(scheduler: &mut RollbackScheduler<u32>, elapsed: u64) -> Option<(u64, Vec<u32>)> {
+        scheduler.next(
+            Duration::from_millis(elapsed),
+            Duration::from_millis(TICK),
+        )
+    }
+
+    #[test]
+    fn predicts_ahead_with_local_input() {
+        let mut scheduler = RollbackScheduler::new(2);
+        assert_eq!(next(&mut scheduler, TICK), None);
+        scheduler.confirm(1, vec![]);
+        assert_eq!(next(&mut scheduler, 0), Some((1, vec![])));
+
+        scheduler.schedule(3, vec![7]);
+        assert_eq!(next(&mut scheduler, TICK), Some((2, vec![])));
+        assert_eq!(next(&mut scheduler, TICK), Some((3, vec![7])));
+        assert_eq!(next(&mut scheduler, TICK), None);
+        assert!(scheduler.take_rewind().is_none());
+    }
+
+    #[test]
+    fn keeps_matching_predictions() {
+        let mut scheduler = RollbackScheduler::new(4);
+        scheduler.confirm(1, vec![]);
+        next(&mut scheduler, 0);
+        scheduler.schedule(2, vec![7]);
+        next(&mut scheduler, TICK);
+
+        scheduler.confirm(2, vec![7]);
+        assert!(scheduler.take_rewind().is_none());
+        assert_eq!(scheduler.confirmed(), 2);
+    }
+
+    #[test]
+    fn rewinds_on_misprediction() {
+        let mut scheduler = RollbackScheduler::new(4);
+        scheduler.confirm(1, vec![]);
+        next(&mut scheduler, 0);
+        for _ in 0..3 {
+            next(&mut scheduler, TICK);
+        }
+        assert_eq!(scheduler.simulated(), 4);
+
+        scheduler.confirm(2, vec![]);
+        scheduler.confirm(3, vec![5]);
+        assert_eq!(
+            scheduler.take_rewind(),
+            Some((3, vec![(3, vec![5]), (4, vec![])]))
+        );
+        assert!(scheduler.take_rewind().is_none());
+    }
+}
@@ -0,0 +1,151 @@
+//! Release of confirmed lockstep frames to the simulation.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Buffers the frames confirmed by the lockstep server and releases them at a steady rate.
+///
+/// Frames do not arrive at a steady rate over a real network. The scheduler keeps a jitter
+/// buffer of `jitter_frames` frames before starting to release them, and releases one frame per
+/// tick duration afterwards. When the buffer runs dry it waits until it is filled again instead
+/// of blocking, so everything but the simulation keeps running. When too many frames pile up,
+/// for example after joining a match in progress, every frame past the jitter buffer is released
+/// at once to catch up with the server.
+///
+/// `T` is the payload of a frame, usually the input events confirmed for it.
+#[derive(Debug)]
+pub struct LockstepScheduler<T> {
+    frames: VecDeque<(u64, T)>,
+    jitter_frames: usize,
+    playing: bool,
+    elapsed: Duration,
+}
+
+impl<T> LockstepScheduler<T> {
+    /// Creates a scheduler buffering `jitter_frames` frames before releasing any.
+    pub fn new(jitter_frames: usize) -> Self {
+        LockstepScheduler {
+            frames: VecDeque::new(),
+            jitter_frames,
+            playing: false,
+            elapsed: Duration::from_secs(0),
+        }
+    }
+
+    /// Returns the number of frames waiting to be released.
+    pub fn buffered(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Adds a frame received from the server.
+    pub fn push(&mut self, frame: u64, payload: T) {
+        self.frames.push_back((frame, payload));
+    }
+
+    /// Drops the buffered frames, and waits for the jitter buffer to fill again.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.playing = false;
+    }
+
+    /// Returns the frames to simulate now, in order.
+    ///
+    /// `elapsed` is the time since the last call and `tick_time` the duration of a frame.
+    pub fn release(&mut self, elapsed: Duration, tick_time: Duration) -> Vec<(u64, T)> {
+        if !self.playing {
+            if self.frames.len() <= self.jitter_frames {
+                return Vec::new();
+            }
+            self.playing = true;
+            self.elapsed = tick_time;
+        } else {
+            self.elapsed += elapsed;
+        }
+
+        if self.frames.len() > self.jitter_frames * 2 + 1 {
+            self.elapsed = Duration::from_secs(0);
+            let behind = self.frames.len() - self.jitter_frames;
+            return self.frames.drain(..behind).collect();
+        }
+        let mut released = Vec::new();
+        while self.elapsed >= tick_time {
+            match self.frames.pop_front() {
+                Some(frame) => {
+                    self.elapsed -= tick_time;
+                    released.push(frame);
+                }
+                None => {
+                    debug!("Lockstep jitter buffer ran dry, waiting for frames");
+                    self.playing = false;
+                    break;
+                }
+            }
+        }
+        released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: u64 = 20;
+
+    fn tick() -> Duration {
+        Duration::from_millis(TICK)
+    }
+
+    fn release(scheduler: &mut LockstepScheduler<()>, elapsed: u64) -> Vec<u64> {
+        scheduler
+            .release(Duration::from_millis(elapsed), tick())
+            .into_iter()
+            .map(|(frame, ())| frame)
+            .collect()
+    }
+
+    #[test]
+    fn waits_for_jitter_buffer() {
+        let mut scheduler = LockstepScheduler::new(2);
+        scheduler.push(1, ());
+        scheduler.push(2, ());
+        assert!(release(&mut scheduler, TICK).is_empty());
+        scheduler.push(3, ());
+        assert_eq!(release(&mut scheduler, TICK), vec![1]);
+    }
+
+    #[test]
+    fn releases_one_frame_per_tick() {
+        let mut scheduler = LockstepScheduler::new(1);
+        for frame in 1..4 {
+            scheduler.push(frame, ());
+        }
+        assert_eq!(release(&mut scheduler, 0), vec![1]);
+        assert!(release(&mut scheduler, TICK / 2).is_empty());
+        assert_eq!(release(&mut scheduler, TICK / 2), vec![2]);
+        for frame in 4..6 {
+            scheduler.push(frame, ());
+        }
+        assert_eq!(release(&mut scheduler, TICK * 2), vec![3, 4]);
+    }
+
+    #[test]
+    fn rebuffers_when_running_dry() {
+        let mut scheduler = LockstepScheduler::new(0);
+        scheduler.push(1, ());
+        assert_eq!(release(&mut scheduler, 0), vec![1]);
+        assert!(release(&mut scheduler, TICK).is_empty());
+        scheduler.push(2, ());
+        assert_eq!(release(&mut scheduler, 0), vec![2]);
+    }
+
+    #[test]
+    fn catches_up_when_behind() {
+        let mut scheduler = LockstepScheduler::new(1);
+        for frame in 1..41 {
+            scheduler.push(frame, ());
+        }
+        assert_eq!(release(&mut scheduler, 0), (1..40).collect::<Vec<_>>());
+        assert!(release(&mut scheduler, 0).is_empty());
+        assert_eq!(scheduler.buffered(), 1);
+    }
+}
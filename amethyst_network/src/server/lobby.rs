@@ -0,0 +1,470 @@
+//! Lobbies of the lockstep server, and the clients joining, rejoining and spectating their
+//! matches.
+
+use super::{broadcast, notify, retain_clients, Client, LockstepServer};
+use error::Result;
+use message::{LobbySlot, Message, PlayerInput};
+use std::collections::VecDeque;
+
+/// A match and the clients taking part in it.
+pub(crate) struct Lobby {
+    pub(crate) id: u32,
+    pub(crate) started: bool,
+    pub(crate) frame: u64,
+    pub(crate) players: Vec<LobbySlot>,
+    pub(crate) pending: Vec<(u64, PlayerInput)>,
+    pub(crate) relayed: Vec<Message>,
+    pub(crate) history: VecDeque<(u64, Vec<u8>)>,
+    pub(crate) dropped: Vec<LobbySlot>,
+    pub(crate) state_request: Option<u32>,
+    pub(crate) state: Option<(u64, Vec<u8>)>,
+}
+
+impl Lobby {
+    pub(crate) fn new(id: u32, started: bool) -> Self {
+        Lobby {
+            id,
+            started,
+            frame: 0,
+            players: Vec::new(),
+            pending: Vec::new(),
+            relayed: Vec::new(),
+            history: VecDeque::new(),
+            dropped: Vec::new(),
+            state_request: None,
+            state: None,
+        }
+    }
+
+    /// Updates the players of the lobby, remembering the ones which left its match so that they
+    /// can rejoin it.
+    fn set_players(&mut self, players: Vec<LobbySlot>) {
+        if self.started {
+            for player in &self.players {
+                if !players.iter().any(|other| other.player == player.player) {
+                    self.dropped.push(player.clone());
+                }
+            }
+            self.dropped
+                .retain(|dropped| !players.iter().any(|other| other.player == dropped.player));
+        }
+        self.players = players;
+    }
+}
+
+impl LockstepServer {
+    /// Handles a lobby request of a player.
+    pub(crate) fn handle_request(&mut self, player: u32, request: Message) {
+        let index = match self.clients.iter().position(|client| client.player == player) {
+            Some(index) => index,
+            None => return,
+        };
+        if let Message::Rejoin {
+            lobby,
+            player: previous,
+        } = request
+        {
+            self.rejoin(index, lobby, previous);
+            return;
+        }
+        if let Message::Spectate { lobby } = request {
+            self.spectate(index, lobby);
+            return;
+        }
+        let slots = match self.slots {
+            Some(slots) => slots,
+            None => {
+                warn!("Lobbies are disabled, ignoring {:?}", request);
+                return;
+            }
+        };
+
+        match request {
+            Message::CreateLobby => {
+                let lobby = self.new_lobby_id();
+                self.lobbies.push(Lobby::new(lobby, false));
+                self.leave(index);
+                self.join(index, lobby);
+            }
+            Message::JoinLobby { lobby } => {
+                let fresh = match lobby {
+                    Some(_) => None,
+                    None => Some(self.new_lobby_id()),
+                };
+                let target = {
+                    let clients = &self.clients;
+                    let open = |candidate: &Lobby| {
+                        let members = clients
+                            .iter()
+                            .filter(|client| {
+                                client.lobby == Some(candidate.id) && !client.spectator
+                            })
+                            .count();
+                        !candidate.started && members < slots as usize
+                    };
+                    let found = self
+                        .lobbies
+                        .iter()
+                        .find(|candidate| lobby.map_or(true, |id| candidate.id == id));
+                    match (lobby, found) {
+                        (_, Some(found)) if open(found) => Some(found.id),
+                        (Some(_), Some(_)) => None,
+                        (Some(id), None) => Some(id),
+                        (None, _) => self
+                            .lobbies
+                            .iter()
+                            .find(|candidate| open(candidate))
+                            .map(|candidate| candidate.id)
+                            .or(fresh),
+                    }
+                };
+                match target {
+                    Some(target) => {
+                        if self.clients[index].lobby == Some(target) {
+                            return;
+                        }
+                        if !self.lobbies.iter().any(|lobby| lobby.id == target) {
+                            self.lobbies.push(Lobby::new(target, false));
+                        }
+                        self.leave(index);
+                        self.join(index, target);
+                    }
+                    None => notify(&mut self.clients[index], &Message::LobbyRejected { lobby }),
+                }
+            }
+            Message::LeaveLobby => self.leave(index),
+            Message::Ready { ready } => self.clients[index].ready = ready,
+            request => warn!("Unexpected lobby request: {:?}", request),
+        }
+    }
+
+    /// Returns an id which is not used by any lobby.
+    fn new_lobby_id(&mut self) -> u32 {
+        loop {
+            let id = self.next_lobby;
+            self.next_lobby += 1;
+            if !self.lobbies.iter().any(|lobby| lobby.id == id) {
+                return id;
+            }
+        }
+    }
+
+    /// Returns the first slot of a lobby which neither a client nor a player who left its match
+    /// takes.
+    fn free_slot(&self, lobby: u32) -> u32 {
+        let dropped = self
+            .lobbies
+            .iter()
+            .find(|candidate| candidate.id == lobby)
+            .map_or(&[][..], |candidate| &candidate.dropped[..]);
+        (0..)
+            .find(|&slot| {
+                !self
+                    .clients
+                    .iter()
+                    .any(|client| {
+                        client.lobby == Some(lobby) && !client.spectator && client.slot == slot
+                    })
+                    && !dropped.iter().any(|player| player.slot == slot)
+            })
+            .expect("unreachable")
+    }
+
+    /// Puts a client in the first free slot of a lobby.
+    fn join(&mut self, index: usize, lobby: u32) {
+        let slot = self.free_slot(lobby);
+        let client = &mut self.clients[index];
+        client.lobby = Some(lobby);
+        client.slot = slot;
+        client.ready = false;
+        client.spectator = false;
+        info!("Player {} joined lobby {} in slot {}", client.player, lobby, slot);
+        notify(client, &Message::LobbyJoined { lobby, slot });
+    }
+
+    /// Lets a client join the match in progress of a lobby, taking back the player id and the
+    /// slot of `previous` if that player left the match.
+    fn rejoin(&mut self, index: usize, lobby: u32, previous: Option<u32>) {
+        let joined = self.clients[index].lobby == Some(lobby) && !self.clients[index].spectator;
+        let (started, dropped) = self
+            .lobbies
+            .iter()
+            .find(|candidate| candidate.id == lobby)
+            .map_or((false, 0), |candidate| {
+                (candidate.started, candidate.dropped.len())
+            });
+        let members = self
+            .clients
+            .iter()
+            .filter(|client| client.lobby == Some(lobby) && !client.spectator)
+            .count();
+        let full = self
+            .slots
+            .map_or(false, |slots| members + dropped >= slots as usize);
+
+        let mut reclaimed = None;
+        if let Some(player) = previous {
+            if started && !self.clients.iter().any(|client| client.player == player) {
+                if let Some(found) = self.lobbies.iter_mut().find(|found| found.id == lobby) {
+                    let position = found
+                        .dropped
+                        .iter()
+                        .position(|dropped| dropped.player == player);
+                    if let Some(position) = position {
+                        reclaimed = Some(found.dropped.remove(position));
+                    }
+                }
+            }
+        }
+        if !started || (reclaimed.is_none() && !joined && full) {
+            notify(
+                &mut self.clients[index],
+                &Message::LobbyRejected { lobby: Some(lobby) },
+            );
+            return;
+        }
+
+        if !joined {
+            self.leave(index);
+        }
+        let slot = match reclaimed {
+            Some(ref reclaimed) => reclaimed.slot,
+            None if joined => self.clients[index].slot,
+            None => self.free_slot(lobby),
+        };
+        let client = &mut self.clients[index];
+        if let Some(reclaimed) = reclaimed {
+            info!(
+                "Player {} takes back the slot of player {}",
+                client.player, reclaimed.player
+            );
+            client.player = reclaimed.player;
+            notify(
+                client,
+                &Message::Welcome {
+                    player: reclaimed.player,
+                },
+            );
+        }
+        client.syncing = client.syncing || !joined;
+        client.lobby = Some(lobby);
+        client.slot = slot;
+        client.ready = true;
+        client.spectator = false;
+        info!("Player {} rejoined lobby {} in slot {}", client.player, lobby, slot);
+        notify(client, &Message::LobbyJoined { lobby, slot });
+    }
+
+    /// Lets a client spectate the match of a lobby, and sends it the players of the lobby. A
+    /// client which joins the match in progress waits for the world state first.
+    fn spectate(&mut self, index: usize, lobby: u32) {
+        let frame = match self.lobbies.iter().find(|candidate| candidate.id == lobby) {
+            Some(found) => found.frame,
+            None => {
+                notify(
+                    &mut self.clients[index],
+                    &Message::LobbyRejected { lobby: Some(lobby) },
+                );
+                return;
+            }
+        };
+        if self.clients[index].lobby != Some(lobby) {
+            self.leave(index);
+            let client = &mut self.clients[index];
+            client.lobby = Some(lobby);
+            client.syncing = frame > 0;
+            client.sent = frame;
+            client.state = None;
+        }
+        let players = lobby_players(&self.clients, lobby);
+        let client = &mut self.clients[index];
+        if !client.spectator {
+            client.spectator = true;
+            client.sent = frame;
+        }
+        client.ready = false;
+        info!("Player {} spectates lobby {}", client.player, lobby);
+        notify(client, &Message::Spectating { lobby });
+        if self.slots.is_some() {
+            notify(client, &Message::LobbyUpdate { lobby, players });
+        }
+    }
+
+    /// Removes a client from its lobby.
+    fn leave(&mut self, index: usize) {
+        let client = &mut self.clients[index];
+        if let Some(lobby) = client.lobby.take() {
+            client.ready = false;
+            notify(client, &Message::LobbyLeft { lobby });
+        }
+    }
+
+    /// Closes the empty lobbies, sends the lobbies whose players changed to their clients, and
+    /// starts the matches of the full lobbies whose players are all ready.
+    pub(crate) fn update_lobbies(&mut self) -> Result<()> {
+        let clients = &mut self.clients;
+        let start_delay = self.start_delay;
+        let slots = match self.slots {
+            Some(slots) => slots as usize,
+            None => {
+                for lobby in &mut self.lobbies {
+                    lobby.set_players(lobby_players(clients, lobby.id));
+                }
+                return Ok(());
+            }
+        };
+
+        self.lobbies
+            .retain(|lobby| clients.iter().any(|client| client.lobby == Some(lobby.id)));
+        for lobby in &mut self.lobbies {
+            let players = lobby_players(clients, lobby.id);
+            if players == lobby.players {
+                continue;
+            }
+            lobby.set_players(players);
+            let update = Message::LobbyUpdate {
+                lobby: lobby.id,
+                players: lobby.players.clone(),
+            }.encode()?;
+            broadcast(clients, lobby.id, &update);
+
+            if !lobby.started
+                && lobby.players.len() == slots
+                && lobby.players.iter().all(|player| player.ready)
+            {
+                info!("Starting the match of lobby {}", lobby.id);
+                lobby.started = true;
+                let start = Message::MatchStart {
+                    lobby: lobby.id,
+                    tick: start_delay + 1,
+                }.encode()?;
+                broadcast(clients, lobby.id, &start);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the slots of the players in a lobby, in slot order.
+fn lobby_players(clients: &[Client], lobby: u32) -> Vec<LobbySlot> {
+    let mut players = clients
+        .iter()
+        .filter(|client| client.lobby == Some(lobby) && !client.spectator)
+        .map(|client| LobbySlot {
+            player: client.player,
+            slot: client.slot,
+            ready: client.ready,
+        })
+        .collect::<Vec<_>>();
+    players.sort_by_key(|player| player.slot);
+    players
+}
+
+/// Sends the world state received for a lobby to the clients joining its match, followed by the
+/// frames broadcast since. Spectators get it once it is as old as their delay. While clients are
+/// waiting for it, a player of the lobby is asked for its world state; if there is none left,
+/// the waiting clients go on without it.
+pub(crate) fn sync_clients(clients: &mut Vec<Client>, lobby: &mut Lobby) -> Result<()> {
+    let id = lobby.id;
+    if !clients
+        .iter()
+        .any(|client| client.lobby == Some(id) && client.syncing)
+    {
+        lobby.state_request = None;
+        lobby.state = None;
+        return Ok(());
+    }
+
+    if let Some((tick, data)) = lobby.state.take() {
+        lobby.state_request = None;
+        let first = lobby
+            .history
+            .front()
+            .map_or(lobby.frame + 1, |&(frame, _)| frame);
+        if tick <= lobby.frame && first <= tick + 1 {
+            info!("Sending the world state of tick {} to lobby {}", tick, id);
+            let state = Message::WorldState { tick, data }.encode()?;
+            let history = &lobby.history;
+            retain_clients(clients, |client| {
+                if client.lobby != Some(id) || !client.syncing {
+                    return Ok(());
+                }
+                client.syncing = false;
+                if client.spectator {
+                    client.state = Some((tick, state.clone()));
+                    client.sent = tick;
+                    return Ok(());
+                }
+                client.transport.send(&state)?;
+                for &(frame, ref bytes) in history {
+                    if frame > tick {
+                        client.transport.send(bytes)?;
+                    }
+                }
+                Ok(())
+            });
+            return Ok(());
+        }
+        warn!("World state of tick {} is older than the frame history", tick);
+    }
+
+    let asked = lobby.state_request.map_or(false, |player| {
+        clients
+            .iter()
+            .any(|client| client.player == player && client.lobby == Some(id))
+    });
+    if asked {
+        return Ok(());
+    }
+    match clients
+        .iter_mut()
+        .find(|client| client.lobby == Some(id) && !client.syncing && !client.spectator)
+    {
+        Some(peer) => {
+            debug!("Asking player {} for the world state of lobby {}", peer.player, id);
+            lobby.state_request = Some(peer.player);
+            notify(peer, &Message::StateRequest);
+        }
+        None => {
+            warn!("No client of lobby {} is left to send its world state", id);
+            lobby.state_request = None;
+            for client in clients.iter_mut().filter(|client| client.lobby == Some(id)) {
+                if client.syncing {
+                    client.syncing = false;
+                    client.sent = lobby.frame;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sends the spectators of a lobby their pending world state and the frames of the match,
+/// `delay` frames after they were broadcast, dropping the clients for which it fails.
+pub(crate) fn send_delayed(clients: &mut Vec<Client>, lobby: &Lobby, delay: u64) {
+    let first = match lobby.history.front() {
+        Some(&(frame, _)) => frame,
+        None => return,
+    };
+    retain_clients(clients, |client| {
+        if client.lobby != Some(lobby.id) || !client.spectator || client.syncing {
+            return Ok(());
+        }
+        if let Some((tick, state)) = client.state.take() {
+            if tick + delay > lobby.frame {
+                client.state = Some((tick, state));
+                return Ok(());
+            }
+            client.transport.send(&state)?;
+        }
+        while client.sent + delay < lobby.frame {
+            client.sent += 1;
+            if client.sent < first {
+                continue;
+            }
+            let index = (client.sent - first) as usize;
+            client.transport.send(&lobby.history[index].1)?;
+        }
+        Ok(())
+    });
+}
@@ -0,0 +1,699 @@
+//! Lockstep relay server.
+
+use self::lobby::{send_delayed, sync_clients, Lobby};
+use error::{Error, ErrorKind, Result};
+use message::{Message, PlayerInput};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use transport::{Listener, Transport};
+
+mod lobby;
+
+/// Default number of frames between the announcement of a match start and its first tick.
+const START_DELAY: u64 = 10;
+/// Number of broadcast frames kept per match, for the clients joining it in progress.
+const FRAME_HISTORY: usize = 1024;
+
+/// Relays the input of all clients in lockstep.
+///
+/// The server collects the inputs sent by its clients and broadcasts them as one frame to every
+/// client on each `tick`. Clients only advance their game on such frames, which keeps them in
+/// step with each other.
+///
+/// Every client is assigned a player id in the order they connect, starting at 0, which is sent
+/// to it in a `Welcome` message. The inputs of a client are tagged with its player id, so the
+/// clients can tell the players apart.
+///
+/// Clients can send their input ahead of time for a later frame, in which case the server holds
+/// it back until that frame is broadcast. The checksums of the world state sent by the clients
+/// are relayed to every client, so they can detect when they drift apart. Pings are answered
+/// right away with the number of the last broadcast frame.
+///
+/// By default, all clients play in a single match which runs from the moment the first client
+/// connects. With `with_lobbies`, clients create and join lobbies instead, and are assigned the
+/// first free slot of the lobby they join. The match of a lobby starts once all of its slots are
+/// taken and every player is ready, and its frames, inputs and checksums only go to the clients
+/// in the lobby. A `MatchStart` message announces the tick on which the match begins, so that all
+/// clients have switched to the game before it does.
+///
+/// Clients can join a match in progress, by connecting to the single match or by sending a
+/// `Rejoin` request. The server then asks another client of the match for its world state, and
+/// forwards it to the joining client along with the frames broadcast since, of which the last
+/// 1024 are kept. Until then, the joining client doesn't get any frame. A client rejoining with
+/// the id of a player which left the match takes the player id and the slot of that player back.
+///
+/// Clients sending a `Spectate` request watch the match of a lobby without taking part in it.
+/// They take no slot, their input and checksums are ignored, and they get the frames of the
+/// match `with_spectator_delay` frames late.
+///
+/// `run` keeps going until the flag returned by `stop_handle` is set, for example from a signal
+/// handler. Clients sending a `Disconnect` message are dropped right away.
+pub struct LockstepServer {
+    listener: Box<Listener>,
+    clients: Vec<Client>,
+    lobbies: Vec<Lobby>,
+    slots: Option<u32>,
+    start_delay: u64,
+    spectator_delay: u64,
+    next_player: u32,
+    next_lobby: u32,
+    stop: Arc<AtomicBool>,
+}
+
+struct Client {
+    player: u32,
+    transport: Box<Transport>,
+    lobby: Option<u32>,
+    slot: u32,
+    ready: bool,
+    syncing: bool,
+    spectator: bool,
+    sent: u64,
+    state: Option<(u64, Vec<u8>)>,
+}
+
+impl LockstepServer {
+    /// Creates a server accepting clients from the given listener.
+    pub fn new<L>(listener: L) -> Self
+    where
+        L: Listener + 'static,
+    {
+        LockstepServer {
+            listener: Box::new(listener),
+            clients: Vec::new(),
+            lobbies: vec![Lobby::new(0, true)],
+            slots: None,
+            start_delay: START_DELAY,
+            spectator_delay: 0,
+            next_player: 0,
+            next_lobby: 1,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Lets the clients create and join lobbies of `slots` players, instead of playing in a
+    /// single match.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `slots` is 0.
+    pub fn with_lobbies(mut self, slots: u32) -> Self {
+        assert!(slots > 0, "A lobby needs at least one slot");
+        self.slots = Some(slots);
+        self.lobbies.clear();
+        self
+    }
+
+    /// Sets the number of frames between the announcement of a match start and the first tick
+    /// of the match. Defaults to 10.
+    pub fn with_start_delay(mut self, frames: u64) -> Self {
+        self.start_delay = frames;
+        self
+    }
+
+    /// Sets the number of frames by which the frames sent to spectators lag behind the match.
+    /// Defaults to 0.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `frames` is not less than the 1024 frames kept per match.
+    pub fn with_spectator_delay(mut self, frames: u64) -> Self {
+        assert!(
+            frames < FRAME_HISTORY as u64,
+            "The spectator delay must be shorter than the frame history"
+        );
+        self.spectator_delay = frames;
+        self
+    }
+
+    /// Returns the number of connected clients.
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Returns the number of open lobbies.
+    pub fn lobby_count(&self) -> usize {
+        self.lobbies.len()
+    }
+
+    /// Returns the number of the last frame broadcast in the single match without lobbies.
+    pub fn frame(&self) -> u64 {
+        self.lobby_frame(0).unwrap_or(0)
+    }
+
+    /// Returns the number of the last frame broadcast in the given lobby.
+    pub fn lobby_frame(&self, lobby: u32) -> Option<u64> {
+        self.lobbies
+            .iter()
+            .find(|candidate| candidate.id == lobby)
+            .map(|lobby| lobby.frame)
+    }
+
+    /// Accepts new clients, collects the inputs received since the last tick and broadcasts
+    /// them as the next frame. Checksums received from the clients are relayed before the frame.
+    ///
+    /// No frame is broadcast while no client is connected.
+    pub fn tick(&mut self) -> Result<()> {
+        while let Some(mut transport) = self.listener.accept()? {
+            let syncing = self.slots.is_none() && self.frame() > 0;
+            let player = self.next_player;
+            self.next_player += 1;
+            let welcome = Message::Welcome { player }.encode()?;
+            match transport.send(&welcome) {
+                Ok(()) => {
+                    info!("Lockstep client connected as player {}", player);
+                    let lobby = if self.slots.is_none() { Some(0) } else { None };
+                    self.clients.push(Client {
+                        player,
+                        transport,
+                        lobby,
+                        slot: player,
+                        ready: false,
+                        syncing,
+                        spectator: false,
+                        sent: 0,
+                        state: None,
+                    });
+                }
+                Err(err) => drop_client(&err),
+            }
+        }
+
+        let mut requests = Vec::new();
+        {
+            let lobbies = &mut self.lobbies;
+            let requests = &mut requests;
+            let spectator_delay = self.spectator_delay;
+            retain_clients(&mut self.clients, |client| {
+                receive(client, lobbies, requests, spectator_delay)
+            });
+        }
+        for (player, request) in requests {
+            self.handle_request(player, request);
+        }
+        self.update_lobbies()?;
+
+        let clients = &mut self.clients;
+        let spectator_delay = self.spectator_delay;
+        for lobby in &mut self.lobbies {
+            for message in lobby.relayed.drain(..) {
+                broadcast_synced(clients, lobby.id, &message.encode()?);
+            }
+            sync_clients(clients, lobby)?;
+            if lobby.started && !lobby.players.is_empty() {
+                lobby.frame += 1;
+                let frame = Message::DataFrame {
+                    frame: lobby.frame,
+                    inputs: take_inputs(&mut lobby.pending, lobby.frame),
+                }.encode()?;
+                broadcast_synced(clients, lobby.id, &frame);
+                lobby.history.push_back((lobby.frame, frame));
+                if lobby.history.len() > FRAME_HISTORY {
+                    lobby.history.pop_front();
+                }
+            }
+            send_delayed(clients, lobby, spectator_delay);
+        }
+        Ok(())
+    }
+
+    /// Returns the flag which stops `run` once set. The clients are disconnected when the
+    /// server is dropped.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// Runs the server until it is stopped through its `stop_handle`, broadcasting a frame every
+    /// `frame_duration`.
+    pub fn run(mut self, frame_duration: Duration) -> Result<()> {
+        while !self.stop.load(Ordering::SeqCst) {
+            let start = Instant::now();
+            self.tick()?;
+            let elapsed = start.elapsed();
+            if elapsed < frame_duration {
+                thread::sleep(frame_duration - elapsed);
+            }
+        }
+        info!("Stopping lockstep server");
+        Ok(())
+    }
+}
+
+/// Collects all inputs a client has sent, the messages to relay to the clients of its lobby,
+/// and its lobby requests. Pings are answered with the number of the last frame broadcast in
+/// its lobby, or sent to it for a spectator. The input and checksums of spectators are ignored.
+/// A `Disconnect` message fails with `ErrorKind::Disconnected`, so the client gets dropped.
+fn receive(
+    client: &mut Client,
+    lobbies: &mut [Lobby],
+    requests: &mut Vec<(u32, Message)>,
+    spectator_delay: u64,
+) -> Result<()> {
+    while let Some(bytes) = client.transport.recv()? {
+        let lobby = match client.lobby {
+            Some(id) => lobbies.iter_mut().find(|lobby| lobby.id == id),
+            None => None,
+        };
+        match (Message::decode(&bytes)?, lobby) {
+            (Message::DataInput { frame, data }, Some(lobby)) => {
+                if lobby.started && !client.spectator {
+                    lobby.pending.push((
+                        frame,
+                        PlayerInput {
+                            player: client.player,
+                            data,
+                        },
+                    ));
+                }
+            }
+            (Message::Checksum { tick, checksum }, Some(lobby)) => {
+                if !client.spectator {
+                    lobby.relayed.push(Message::PlayerChecksum {
+                        player: client.player,
+                        tick,
+                        checksum,
+                    })
+                }
+            }
+            (Message::WorldState { tick, data }, Some(lobby)) => {
+                if lobby.state_request == Some(client.player) {
+                    lobby.state = Some((tick, data));
+                }
+            }
+            (Message::Ping { id }, lobby) => {
+                let delay = if client.spectator { spectator_delay } else { 0 };
+                let frame = lobby.map_or(0, |lobby| lobby.frame.saturating_sub(delay));
+                let pong = Message::Pong { id, frame }.encode()?;
+                client.transport.send(&pong)?;
+            }
+            (Message::Disconnect, _) => bail!(ErrorKind::Disconnected),
+            (request @ Message::CreateLobby, _)
+            | (request @ Message::JoinLobby { .. }, _)
+            | (request @ Message::LeaveLobby, _)
+            | (request @ Message::Ready { .. }, _)
+            | (request @ Message::Rejoin { .. }, _)
+            | (request @ Message::Spectate { .. }, _) => requests.push((client.player, request)),
+            (message, _) => warn!("Unexpected message from lockstep client: {:?}", message),
+        }
+    }
+    Ok(())
+}
+
+/// Sends a message to every client in a lobby, dropping the clients for which it fails.
+fn broadcast(clients: &mut Vec<Client>, lobby: u32, bytes: &[u8]) {
+    retain_clients(clients, |client| {
+        if client.lobby == Some(lobby) {
+            client.transport.send(bytes)
+        } else {
+            Ok(())
+        }
+    });
+}
+
+/// Sends a frame or a relayed message to every player in a lobby which is not waiting for the
+/// world state, dropping the clients for which it fails.
+fn broadcast_synced(clients: &mut Vec<Client>, lobby: u32, bytes: &[u8]) {
+    retain_clients(clients, |client| {
+        if client.lobby == Some(lobby) && !client.syncing && !client.spectator {
+            client.transport.send(bytes)
+        } else {
+            Ok(())
+        }
+    });
+}
+
+/// Sends a message to a single client. A client for which it fails is dropped on its next
+/// receive.
+fn notify(client: &mut Client, message: &Message) {
+    let result = message
+        .encode()
+        .and_then(|bytes| client.transport.send(&bytes));
+    if let Err(err) = result {
+        debug!("Failed to notify player {}: {}", client.player, err);
+    }
+}
+
+/// Removes the inputs meant for the given frame or an earlier one, in the order they arrived.
+fn take_inputs(pending: &mut Vec<(u64, PlayerInput)>, frame: u64) -> Vec<PlayerInput> {
+    let mut inputs = Vec::new();
+    let mut i = 0;
+    while i < pending.len() {
+        if pending[i].0 <= frame {
+            inputs.push(pending.remove(i).1);
+        } else {
+            i += 1;
+        }
+    }
+    inputs
+}
+
+/// Applies `f` to every client, dropping the clients for which it fails.
+fn retain_clients<F>(clients: &mut Vec<Client>, mut f: F)
+where
+    F: FnMut(&mut Client) -> Result<()>,
+{
+    let mut i = 0;
+    while i < clients.len() {
+        match f(&mut clients[i]) {
+            Ok(()) => i += 1,
+            Err(err) => {
+                drop_client(&err);
+                clients.remove(i);
+            }
+        }
+    }
+}
+
+fn drop_client(err: &Error) {
+    match *err.kind() {
+        ErrorKind::Disconnected => info!("Lockstep client disconnected"),
+        _ => warn!("Dropping lockstep client: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use message::LobbySlot;
+    use transport::{LoopbackListener, LoopbackTransport};
+
+    fn input(frame: u64, data: &[u8]) -> Vec<u8> {
+        Message::DataInput {
+            frame,
+            data: data.to_vec(),
+        }.encode()
+            .unwrap()
+    }
+
+    fn send(client: &mut LoopbackTransport, message: Message) {
+        client.send(&message.encode().unwrap()).unwrap();
+    }
+
+    fn message(client: &mut LoopbackTransport) -> Message {
+        Message::decode(&client.recv().unwrap().expect("No message received")).unwrap()
+    }
+
+    #[test]
+    fn relays_input_to_all_clients() {
+        let (listener, connector) = LoopbackListener::bind();
+        let mut server = LockstepServer::new(listener);
+        let mut first = connector.connect().unwrap();
+        let mut second = connector.connect().unwrap();
+
+        server.tick().unwrap();
+        assert_eq!(server.client_count(), 2);
+        assert_eq!(message(&mut first), Message::Welcome { player: 0 });
+        assert_eq!(message(&mut second), Message::Welcome { player: 1 });
+        let empty = Message::DataFrame {
+            frame: 1,
+            inputs: vec![],
+        };
+        assert_eq!(message(&mut first), empty);
+        assert_eq!(message(&mut second), empty);
+
+        first.send(&input(2, &[13])).unwrap();
+        second.send(&input(0, &[126])).unwrap();
+        server.tick().unwrap();
+        let expected = Message::DataFrame {
+            frame: 2,
+            inputs: vec![
+                PlayerInput {
+                    player: 0,
+                    data: vec![13],
+                },
+                PlayerInput {
+                    player: 1,
+                    data: vec![126],
+                },
+            ],
+        };
+        assert_eq!(message(&mut first), expected);
+        assert_eq!(message(&mut second), expected);
+    }
+
+    #[test]
+    fn holds_input_back_until_its_frame() {
+        let (listener, connector) = LoopbackListener::bind();
+        let mut server = LockstepServer::new(listener);
+        let mut client = connector.connect().unwrap();
+
+        client.send(&input(2, &[1])).unwrap();
+        server.tick().unwrap();
+        assert_eq!(message(&mut client), Message::Welcome { player: 0 });
+        assert_eq!(
+            message(&mut client),
+            Message::DataFrame {
+                frame: 1,
+                inputs: vec![],
+            }
+        );
+
+        server.tick().unwrap();
+        assert_eq!(
+            message(&mut client),
+            Message::DataFrame {
+                frame: 2,
+                inputs: vec![PlayerInput {
+                    player: 0,
+                    data: vec![1],
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn drops_disconnecting_clients_until_stopped() {
+        let (listener, connector) = LoopbackListener::bind();
+        let mut server = LockstepServer::new(listener);
+        let mut first = connector.connect().unwrap();
+        let _second = connector.connect().unwrap();
+
+        server.tick().unwrap();
+        send(&mut first, Message::Disconnect);
+        server.tick().unwrap();
+        assert_eq!(server.client_count(), 1);
+
+        server.stop_handle().store(true, Ordering::SeqCst);
+        server.run(Duration::from_millis(1)).unwrap();
+    }
+
+    #[test]
+    fn relays_checksums_with_player() {
+        let (listener, connector) = LoopbackListener::bind();
+        let mut server = LockstepServer::new(listener);
+        let mut client = connector.connect().unwrap();
+
+        let checksum = Message::Checksum {
+            tick: 10,
+            checksum: 42,
+        };
+        client.send(&checksum.encode().unwrap()).unwrap();
+        server.tick().unwrap();
+        assert_eq!(message(&mut client), Message::Welcome { player: 0 });
+        assert_eq!(
+            message(&mut client),
+            Message::PlayerChecksum {
+                player: 0,
+                tick: 10,
+                checksum: 42,
+            }
+        );
+    }
+
+    #[test]
+    fn answers_pings() {
+        let (listener, connector) = LoopbackListener::bind();
+        let mut server = LockstepServer::new(listener);
+        let mut client = connector.connect().unwrap();
+
+        server.tick().unwrap();
+        client
+            .send(&Message::Ping { id: 3 }.encode().unwrap())
+            .unwrap();
+        server.tick().unwrap();
+        assert_eq!(message(&mut client), Message::Welcome { player: 0 });
+        message(&mut client);
+        assert_eq!(message(&mut client), Message::Pong { id: 3, frame: 1 });
+    }
+
+    #[test]
+    fn starts_full_lobbies_once_ready() {
+        let (listener, connector) = LoopbackListener::bind();
+        let mut server = LockstepServer::new(listener)
+            .with_lobbies(2)
+            .with_start_delay(5);
+        let mut first = connector.connect().unwrap();
+        let mut second = connector.connect().unwrap();
+        server.tick().unwrap();
+        assert_eq!(message(&mut first), Message::Welcome { player: 0 });
+        assert_eq!(message(&mut second), Message::Welcome { player: 1 });
+
+        send(&mut first, Message::JoinLobby { lobby: Some(8) });
+        send(&mut second, Message::JoinLobby { lobby: None });
+        send(&mut second, Message::Ready { ready: true });
+        server.tick().unwrap();
+        assert_eq!(server.lobby_count(), 1);
+        assert_eq!(
+            message(&mut first),
+            Message::LobbyJoined { lobby: 8, slot: 0 }
+        );
+        assert_eq!(
+            message(&mut second),
+            Message::LobbyJoined { lobby: 8, slot: 1 }
+        );
+        let players = vec![
+            LobbySlot {
+                player: 0,
+                slot: 0,
+                ready: false,
+            },
+            LobbySlot {
+                player: 1,
+                slot: 1,
+                ready: true,
+            },
+        ];
+        assert_eq!(
+            message(&mut first),
+            Message::LobbyUpdate { lobby: 8, players }
+        );
+        assert!(first.recv().unwrap().is_none());
+
+        send(&mut first, Message::Ready { ready: true });
+        server.tick().unwrap();
+        message(&mut first);
+        assert_eq!(
+            message(&mut first),
+            Message::MatchStart { lobby: 8, tick: 6 }
+        );
+        assert_eq!(
+            message(&mut first),
+            Message::DataFrame {
+                frame: 1,
+                inputs: vec![],
+            }
+        );
+        assert_eq!(server.lobby_frame(8), Some(1));
+    }
+
+    #[test]
+    fn sends_world_state_to_rejoining_players() {
+        let (listener, connector) = LoopbackListener::bind();
+        let mut server = LockstepServer::new(listener);
+        let mut first = connector.connect().unwrap();
+        let second = connector.connect().unwrap();
+        server.tick().unwrap();
+        drop(second);
+        server.tick().unwrap();
+
+        let mut third = connector.connect().unwrap();
+        send(
+            &mut third,
+            Message::Rejoin {
+                lobby: 0,
+                player: Some(1),
+            },
+        );
+        server.tick().unwrap();
+        assert_eq!(message(&mut third), Message::Welcome { player: 2 });
+        assert_eq!(message(&mut third), Message::Welcome { player: 1 });
+        assert_eq!(
+            message(&mut third),
+            Message::LobbyJoined { lobby: 0, slot: 1 }
+        );
+        assert!(third.recv().unwrap().is_none());
+        assert_eq!(message(&mut first), Message::Welcome { player: 0 });
+        message(&mut first);
+        message(&mut first);
+        assert_eq!(message(&mut first), Message::StateRequest);
+
+        send(
+            &mut first,
+            Message::WorldState {
+                tick: 2,
+                data: vec![7],
+            },
+        );
+        server.tick().unwrap();
+        assert_eq!(
+            message(&mut third),
+            Message::WorldState {
+                tick: 2,
+                data: vec![7],
+            }
+        );
+        for frame in 3..5 {
+            assert_eq!(
+                message(&mut third),
+                Message::DataFrame {
+                    frame,
+                    inputs: vec![],
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn delays_frames_for_spectators() {
+        let (listener, connector) = LoopbackListener::bind();
+        let mut server = LockstepServer::new(listener).with_spectator_delay(2);
+        let mut first = connector.connect().unwrap();
+        server.tick().unwrap();
+
+        let mut spectator = connector.connect().unwrap();
+        send(&mut spectator, Message::Spectate { lobby: 0 });
+        server.tick().unwrap();
+        assert_eq!(message(&mut spectator), Message::Welcome { player: 1 });
+        assert_eq!(message(&mut spectator), Message::Spectating { lobby: 0 });
+        assert!(spectator.recv().unwrap().is_none());
+        assert_eq!(message(&mut first), Message::Welcome { player: 0 });
+        message(&mut first);
+        assert_eq!(message(&mut first), Message::StateRequest);
+
+        send(
+            &mut first,
+            Message::WorldState {
+                tick: 1,
+                data: vec![7],
+            },
+        );
+        server.tick().unwrap();
+        assert_eq!(
+            message(&mut spectator),
+            Message::WorldState {
+                tick: 1,
+                data: vec![7],
+            }
+        );
+        assert!(spectator.recv().unwrap().is_none());
+
+        send(&mut spectator, Message::Ping { id: 5 });
+        server.tick().unwrap();
+        assert_eq!(message(&mut spectator), Message::Pong { id: 5, frame: 1 });
+        assert_eq!(
+            message(&mut spectator),
+            Message::DataFrame {
+                frame: 2,
+                inputs: vec![],
+            }
+        );
+        assert!(spectator.recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn drops_disconnected_clients() {
+        let (listener, connector) = LoopbackListener::bind();
+        let mut server = LockstepServer::new(listener);
+        let mut first = connector.connect().unwrap();
+        let second = connector.connect().unwrap();
+
+        server.tick().unwrap();
+        drop(second);
+        server.tick().unwrap();
+        assert_eq!(server.client_count(), 1);
+        assert_eq!(message(&mut first), Message::Welcome { player: 0 });
+        assert!(first.recv().unwrap().is_some());
+    }
+}
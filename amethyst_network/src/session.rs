@@ -0,0 +1,158 @@
+//! Client side of the lobby session.
+
+use event::NetworkEvent;
+use message::{LobbySlot, Message};
+use std::mem;
+
+/// The lobby session of this client, when the lockstep server uses lobbies.
+///
+/// Requests made through this resource are sent to the server by the `LockstepSystem` on its
+/// next run. Their outcome is written as `NetworkEvent`s to the custom event channel, which the
+/// states receive as `StateEvent::Custom`. A lobby state can for example join a lobby on start,
+/// and switch to the game state on `NetworkEvent::MatchStarted`.
+#[derive(Debug, Default)]
+pub struct Session {
+    lobby: Option<u32>,
+    slot: Option<u32>,
+    players: Vec<LobbySlot>,
+    start_tick: Option<u64>,
+    spectator: bool,
+    requests: Vec<Message>,
+}
+
+impl Session {
+    /// Creates a session outside of any lobby.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the lobby this client is in.
+    pub fn lobby(&self) -> Option<u32> {
+        self.lobby
+    }
+
+    /// Returns the slot of the local player in its lobby.
+    pub fn slot(&self) -> Option<u32> {
+        self.slot
+    }
+
+    /// Returns the players in the lobby, in slot order.
+    pub fn players(&self) -> &[LobbySlot] {
+        &self.players
+    }
+
+    /// Returns the id of the player in the given slot of the lobby.
+    pub fn player_in_slot(&self, slot: u32) -> Option<u32> {
+        self.players
+            .iter()
+            .find(|player| player.slot == slot)
+            .map(|player| player.player)
+    }
+
+    /// Returns true if the server acknowledged that the local player is ready.
+    pub fn is_ready(&self) -> bool {
+        let slot = self.slot;
+        self.players
+            .iter()
+            .any(|player| Some(player.slot) == slot && player.ready)
+    }
+
+    /// Returns true if this client spectates the match of its lobby.
+    pub fn is_spectator(&self) -> bool {
+        self.spectator
+    }
+
+    /// Returns the first tick of the match, once it started.
+    pub fn start_tick(&self) -> Option<u64> {
+        self.start_tick
+    }
+
+    /// Creates a new lobby and joins it.
+    pub fn create_lobby(&mut self) {
+        self.requests.push(Message::CreateLobby);
+    }
+
+    /// Joins the given lobby, creating it if it doesn't exist yet.
+    pub fn join_lobby(&mut self, lobby: u32) {
+        self.requests.push(Message::JoinLobby { lobby: Some(lobby) });
+    }
+
+    /// Joins the first lobby with a free slot, or creates a new one.
+    pub fn find_lobby(&mut self) {
+        self.requests.push(Message::JoinLobby { lobby: None });
+    }
+
+    /// Joins the match in progress of the given lobby, or of the single match with lobby 0 if
+    /// the server doesn't use lobbies.
+    ///
+    /// Pass the player id this client had in the match to take its slot back, for example after
+    /// restarting the game. The `LockstepSystem` sends this request on its own when it
+    /// reconnects. The world state of another client is then loaded, and
+    /// `NetworkEvent::MatchJoined` written once it is.
+    pub fn rejoin_lobby(&mut self, lobby: u32, player: Option<u32>) {
+        self.requests.push(Message::Rejoin { lobby, player });
+    }
+
+    /// Watches the match of the given lobby, or the single match with lobby 0 if the server
+    /// doesn't use lobbies, without taking part in it.
+    ///
+    /// The `LockstepSystem` of a spectator sends this request on its own when it reconnects. If
+    /// the match is in progress, the world state of another client is loaded first.
+    pub fn spectate(&mut self, lobby: u32) {
+        self.requests.push(Message::Spectate { lobby });
+    }
+
+    /// Leaves the current lobby.
+    pub fn leave_lobby(&mut self) {
+        self.requests.push(Message::LeaveLobby);
+    }
+
+    /// Tells the server whether the local player is ready for the match to start. Joining a
+    /// lobby resets it.
+    pub fn set_ready(&mut self, ready: bool) {
+        self.requests.push(Message::Ready { ready });
+    }
+
+    /// Removes the requests which have not been sent yet.
+    pub(crate) fn drain_requests(&mut self) -> Vec<Message> {
+        mem::replace(&mut self.requests, Vec::new())
+    }
+
+    /// Updates the session with an event received from the server.
+    pub(crate) fn apply(&mut self, event: &NetworkEvent) {
+        match *event {
+            NetworkEvent::LobbyJoined { lobby, slot } => {
+                self.lobby = Some(lobby);
+                self.slot = Some(slot);
+                self.players.clear();
+                self.start_tick = None;
+                self.spectator = false;
+            }
+            NetworkEvent::Spectating { lobby } => {
+                self.lobby = Some(lobby);
+                self.slot = None;
+                self.players.clear();
+                self.start_tick = None;
+                self.spectator = true;
+            }
+            NetworkEvent::LobbyLeft { .. } => {
+                self.lobby = None;
+                self.slot = None;
+                self.players.clear();
+                self.start_tick = None;
+                self.spectator = false;
+            }
+            NetworkEvent::LobbyUpdated { lobby, ref players } => {
+                if self.lobby == Some(lobby) {
+                    self.players = players.clone();
+                }
+            }
+            NetworkEvent::MatchStarted { lobby, tick } => {
+                if self.lobby == Some(lobby) {
+                    self.start_tick = Some(tick);
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Latency statistics of the connection to the server.
+
+use amethyst_core::timing::{duration_to_nanos, nanos_to_duration};
+use std::time::Duration;
+
+/// Weight of a new sample in the smoothed round-trip time.
+const RTT_GAIN: f64 = 1.0 / 8.0;
+/// Weight of a new sample in the jitter.
+const JITTER_GAIN: f64 = 1.0 / 16.0;
+/// Weight of a new ping in the packet loss.
+const LOSS_GAIN: f32 = 1.0 / 16.0;
+
+/// Statistics of the connection to the lockstep server, measured by exchanging ping messages.
+///
+/// The `LockstepSystem` keeps this resource up to date. The round-trip time is smoothed the
+/// same way TCP does, and the jitter is the smoothed variation between consecutive round-trip
+/// times. The system also reports the state of the lockstep simulation here: the confirmed
+/// tick, the input delay, the rollback depth and the desyncs detected.
+#[derive(Clone, Debug, Default)]
+pub struct NetworkStats {
+    rtt: u64,
+    last_rtt: u64,
+    jitter: u64,
+    packet_loss: f32,
+    tick_offset: i64,
+    samples: u64,
+    confirmed_tick: u64,
+    input_delay: u64,
+    rollback_depth: u64,
+    desyncs: u64,
+    last_desync: Option<u64>,
+}
+
+impl NetworkStats {
+    /// Creates statistics without any sample.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the smoothed round-trip time to the server.
+    pub fn rtt(&self) -> Duration {
+        nanos_to_duration(self.rtt)
+    }
+
+    /// Returns the variation of the round-trip time.
+    pub fn jitter(&self) -> Duration {
+        nanos_to_duration(self.jitter)
+    }
+
+    /// Returns the fraction of recent pings which were not answered, between 0 and 1.
+    pub fn packet_loss(&self) -> f32 {
+        self.packet_loss
+    }
+
+    /// Returns the estimated number of ticks the server is ahead of the local simulation.
+    pub fn tick_offset(&self) -> i64 {
+        self.tick_offset
+    }
+
+    /// Returns the number of round-trip times measured.
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Returns the last tick whose input was confirmed by the server.
+    pub fn confirmed_tick(&self) -> u64 {
+        self.confirmed_tick
+    }
+
+    /// Returns the number of frames the local input is delayed by.
+    pub fn input_delay(&self) -> u64 {
+        self.input_delay
+    }
+
+    /// Returns the number of ticks simulated ahead of the confirmed tick with predicted input,
+    /// in rollback mode.
+    pub fn rollback_depth(&self) -> u64 {
+        self.rollback_depth
+    }
+
+    /// Returns the number of desyncs detected since the system started.
+    pub fn desyncs(&self) -> u64 {
+        self.desyncs
+    }
+
+    /// Returns the tick of the last desync detected, if any.
+    pub fn last_desync(&self) -> Option<u64> {
+        self.last_desync
+    }
+
+    /// Returns the input delay, in frames, which lets the local input reach the server before
+    /// the frame it is meant for is broadcast: half the round-trip time plus twice the jitter.
+    ///
+    /// Returns 0 until a round-trip time was measured.
+    pub fn recommended_input_delay(&self, frame_duration: Duration) -> u64 {
+        let frame = duration_to_nanos(frame_duration).max(1);
+        let latency = self.rtt / 2 + self.jitter * 2;
+        (latency + frame - 1) / frame
+    }
+
+    /// Adds a measured round-trip time.
+    pub fn record_rtt(&mut self, rtt: Duration) {
+        let rtt = duration_to_nanos(rtt);
+        if self.samples == 0 {
+            self.rtt = rtt;
+        } else {
+            self.rtt = smooth(self.rtt, rtt, RTT_GAIN);
+            let variation = if rtt > self.last_rtt {
+                rtt - self.last_rtt
+            } else {
+                self.last_rtt - rtt
+            };
+            self.jitter = smooth(self.jitter, variation, JITTER_GAIN);
+        }
+        self.last_rtt = rtt;
+        self.samples += 1;
+        self.packet_loss -= self.packet_loss * LOSS_GAIN;
+    }
+
+    /// Records a ping which was not answered in time.
+    pub fn record_loss(&mut self) {
+        self.packet_loss += (1.0 - self.packet_loss) * LOSS_GAIN;
+    }
+
+    /// Sets the estimated number of ticks the server is ahead of the local simulation.
+    pub fn set_tick_offset(&mut self, tick_offset: i64) {
+        self.tick_offset = tick_offset;
+    }
+
+    /// Sets the last tick whose input was confirmed by the server.
+    pub fn set_confirmed_tick(&mut self, tick: u64) {
+        self.confirmed_tick = tick;
+    }
+
+    /// Sets the number of frames the local input is delayed by.
+    pub fn set_input_delay(&mut self, frames: u64) {
+        self.input_delay = frames;
+    }
+
+    /// Sets the number of ticks simulated ahead of the confirmed tick.
+    pub fn set_rollback_depth(&mut self, ticks: u64) {
+        self.rollback_depth = ticks;
+    }
+
+    /// Sets the number of desyncs detected and the tick of the last one.
+    pub fn set_desyncs(&mut self, desyncs: u64, last_desync: Option<u64>) {
+        self.desyncs = desyncs;
+        self.last_desync = last_desync;
+    }
+}
+
+fn smooth(average: u64, sample: u64, gain: f64) -> u64 {
+    (average as f64 + (sample as f64 - average as f64) * gain) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smooths_round_trip_times() {
+        let mut stats = NetworkStats::new();
+        stats.record_rtt(Duration::from_millis(40));
+        assert_eq!(stats.rtt(), Duration::from_millis(40));
+        assert_eq!(stats.jitter(), Duration::from_millis(0));
+
+        stats.record_rtt(Duration::from_millis(120));
+        assert_eq!(stats.rtt(), Duration::from_millis(50));
+        assert_eq!(stats.jitter(), Duration::from_millis(5));
+        assert_eq!(stats.recommended_input_delay(Duration::from_millis(20)), 2);
+    }
+
+    #[test]
+    fn tracks_packet_loss() {
+        let mut stats = NetworkStats::new();
+        stats.record_loss();
+        assert!(stats.packet_loss() > 0.0);
+        for _ in 0..100 {
+            stats.record_rtt(Duration::from_millis(10));
+        }
+        assert!(stats.packet_loss() < 0.01);
+    }
+}
@@ -0,0 +1,482 @@
+//! Lockstep system
+
+use amethyst_core::checksum::WorldChecksum;
+use amethyst_core::shrev::EventChannel;
+use amethyst_core::specs::prelude::{Read, Resources, System, SystemData, Write};
+use amethyst_core::timing::{SimulationTime, TickSource, Time};
+use amethyst_input::{InputRelay, NetInputEvent, PlayerInputEvent, PlayerInputs};
+use connection::Connection;
+use error::{ErrorKind, Result};
+use event::NetworkEvent;
+use rollback::RollbackScheduler;
+use scheduler::LockstepScheduler;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use session::Session;
+use stats::NetworkStats;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::time::Duration;
+use transport::Transport;
+use world_state::StateTransfer;
+
+/// Seconds between two attempts to reconnect to the lockstep server.
+const RECONNECT_INTERVAL: u64 = 1;
+
+type Reconnect = Box<Fn() -> Result<Box<Transport>> + Send + Sync>;
+
+/// Relays the local input to the lockstep server and releases the confirmed frames.
+///
+/// Input events queued in the `InputRelay` by the `InputSystem` are sent for the frame
+/// `input_delay` frames after the next one. The ones queued for a given frame are sent for that
+/// frame, unless it is earlier than the frame the other events are sent for. The frames
+/// received from the server go through a `LockstepScheduler`, and the events of the released
+/// frames are handed back to the `InputRelay`, to be applied tick by tick by the
+/// `TickInputSystem`. The `SimulationTime` is
+/// advanced to the number of the last released frame, with the frame duration as its tick
+/// duration, so every client simulates the same ticks. In frames in which no confirmed frame is
+/// ready, the `SimulationTime` stalls and the `Application` skips the simulation stage of the
+/// game data; this system never blocks.
+///
+/// The checksums recorded in the `WorldChecksum` are sent to the other clients, and compared
+/// with the ones they sent. A `NetworkEvent::DesyncDetected` is written to the custom event
+/// channel `EventChannel<E>` when they differ.
+///
+/// Lobby requests made through the `Session` resource are sent to the server, and the lobby
+/// events it answers with are written to the custom event channel as `NetworkEvent`s.
+///
+/// Once the server has assigned a player id to this client, it is stored as the local player of
+/// the `PlayerInputs`.
+///
+/// In rollback mode, the simulation does not wait for the server. Ticks are predicted ahead of
+/// it by a `RollbackScheduler`, and when a confirmed frame differs from its prediction, the
+/// `SimulationTime` is rewound and the corrected frames are pushed to the `InputRelay` again.
+/// Only the checksums of confirmed ticks are exchanged.
+///
+/// The server is pinged every `ping_interval` to measure the latency, which is reported in the
+/// `NetworkStats` resource. The time between runs is the real frame time of the `Time` resource,
+/// so the frames are released and the pings sent the same way for the same frame times. With an
+/// adaptive input delay, the input delay follows the delay recommended by the `NetworkStats`.
+///
+/// When the server asks for the world state because another client joins the match, it is
+/// saved by the `WorldStateSystem` after the last tick simulated with confirmed input, and sent
+/// back on the next run. A world state received from the server is loaded before the frames
+/// following it are released. Those pile up in the `LockstepScheduler`, which releases them all
+/// at once, so the `Application` simulates them within a single frame to catch up with the
+/// server, and a `NetworkEvent::MatchJoined` is written. If the state fails to load, the
+/// simulation stays stalled and the match is joined again for a new state. With a reconnect
+/// function, the system reconnects when the connection is lost and rejoins the match that way.
+///
+/// A spectator never sends input or checksums to the server, and spectates the match again
+/// instead of rejoining it when it reconnects. The server sends it the frames of the match
+/// with its spectator delay.
+pub struct LockstepSystem<AX, AC, E> {
+    connection: Connection<AX, AC>,
+    frame_duration: Duration,
+    input_delay: u64,
+    max_input_delay: Option<u64>,
+    ping_interval: Duration,
+    scheduler: LockstepScheduler<Vec<PlayerInputEvent<AX, AC>>>,
+    rollback: Option<RollbackScheduler<PlayerInputEvent<AX, AC>>>,
+    sent_checksums: u64,
+    remote_checksums: Vec<(u64, u64)>,
+    disconnected: bool,
+    reconnect: Option<Reconnect>,
+    since_reconnect: Option<Duration>,
+    state_requested: bool,
+    awaiting_state: bool,
+    spectator: bool,
+    desyncs: u64,
+    last_desync: Option<u64>,
+    _marker: PhantomData<E>,
+}
+
+impl<AX, AC, E> LockstepSystem<AX, AC, E> {
+    /// Creates a new lockstep system using the given connection.
+    ///
+    /// There is no input delay and a jitter buffer of one frame by default.
+    pub fn new(connection: Connection<AX, AC>, frame_duration: Duration) -> Self {
+        LockstepSystem {
+            connection,
+            frame_duration,
+            input_delay: 0,
+            max_input_delay: None,
+            ping_interval: Duration::from_millis(250),
+            scheduler: LockstepScheduler::new(1),
+            rollback: None,
+            sent_checksums: 0,
+            remote_checksums: Vec::new(),
+            disconnected: false,
+            reconnect: None,
+            since_reconnect: None,
+            state_requested: false,
+            awaiting_state: false,
+            spectator: false,
+            desyncs: 0,
+            last_desync: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the number of frames between the frame in which the local input happened and the
+    /// frame it is applied in.
+    pub fn with_input_delay(mut self, frames: u64) -> Self {
+        self.input_delay = frames;
+        self
+    }
+
+    /// Adapts the input delay to the latency measured with pings, up to `max_frames`.
+    pub fn with_adaptive_input_delay(mut self, max_frames: u64) -> Self {
+        self.max_input_delay = Some(max_frames);
+        self
+    }
+
+    /// Sets the time between two pings of the server.
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+
+    /// Sets the number of frames buffered before the simulation starts or resumes.
+    pub fn with_jitter_buffer(mut self, frames: usize) -> Self {
+        self.scheduler = LockstepScheduler::new(frames);
+        self
+    }
+
+    /// Predicts up to `max_prediction` ticks ahead of the server instead of waiting for it, and
+    /// rolls the simulation back on mispredictions.
+    pub fn with_rollback(mut self, max_prediction: u64) -> Self {
+        self.rollback = Some(RollbackScheduler::new(max_prediction));
+        self
+    }
+
+    /// Calls `connect` every second while the connection to the server is lost, and rejoins the
+    /// match through the `Session` once it succeeds.
+    pub fn with_reconnect<F, T>(mut self, connect: F) -> Self
+    where
+        F: Fn() -> Result<T> + Send + Sync + 'static,
+        T: Transport + 'static,
+    {
+        self.reconnect = Some(Box::new(move || {
+            connect().map(|transport| Box::new(transport) as Box<Transport>)
+        }));
+        self
+    }
+
+    /// Watches the match as a spectator, without taking part in it.
+    pub fn with_spectator(mut self) -> Self {
+        self.spectator = true;
+        self
+    }
+}
+
+impl<AX, AC, E> LockstepSystem<AX, AC, E>
+where
+    AX: Clone + PartialEq + Serialize + DeserializeOwned,
+    AC: Clone + PartialEq + Serialize + DeserializeOwned,
+    E: From<NetworkEvent>,
+{
+    /// Returns the last tick which was simulated with confirmed input, in rollback mode.
+    fn settled_tick(&self) -> Option<u64> {
+        self.rollback
+            .as_ref()
+            .map(|rollback| rollback.confirmed().min(rollback.simulated()))
+    }
+
+    fn exchange(
+        &mut self,
+        relay: &mut InputRelay<AX, AC>,
+        checksums: &mut WorldChecksum,
+        session: &mut Session,
+        transfer: &mut StateTransfer,
+        elapsed: Duration,
+    ) -> Result<()> {
+        self.connection.ping(self.ping_interval, elapsed)?;
+        if let Some((tick, data)) = transfer.take_captured() {
+            self.connection.send_world_state(tick, data)?;
+        }
+        for request in session.drain_requests() {
+            self.connection.send_request(&request)?;
+        }
+        let outgoing = relay.drain_outgoing().collect::<Vec<_>>();
+        let scheduled = relay.drain_scheduled().collect::<Vec<_>>();
+        let new_checksums = checksums.drain_new();
+        if self.spectator {
+            // Spectators don't take part in the match, their input and checksums are dropped.
+            return self.receive(relay, transfer);
+        }
+        let last_frame = match self.rollback {
+            Some(ref rollback) => rollback.simulated(),
+            None => self.connection.last_frame(),
+        };
+        let frame = last_frame + 1 + self.input_delay;
+        self.send_input(outgoing, frame)?;
+        for (scheduled, event) in scheduled {
+            self.send_input(vec![event], scheduled.max(frame))?;
+        }
+
+        match self.settled_tick() {
+            Some(settled) => {
+                for tick in (self.sent_checksums + 1)..(settled + 1) {
+                    if let Some(checksum) = checksums.get(tick) {
+                        self.connection.send_checksum(tick, checksum)?;
+                    }
+                }
+                self.sent_checksums = self.sent_checksums.max(settled);
+            }
+            None => {
+                for (tick, checksum) in new_checksums {
+                    self.connection.send_checksum(tick, checksum)?;
+                }
+            }
+        }
+        self.receive(relay, transfer)
+    }
+
+    /// Sends local input events for a frame, and schedules them in rollback mode.
+    fn send_input(&mut self, events: Vec<NetInputEvent<AX, AC>>, frame: u64) -> Result<()> {
+        self.connection.send(&events, frame)?;
+        if let (Some(rollback), Some(player)) = (self.rollback.as_mut(), self.connection.player())
+        {
+            let events = events
+                .into_iter()
+                .map(|event| PlayerInputEvent { player, event })
+                .collect();
+            rollback.schedule(frame, events);
+        }
+        Ok(())
+    }
+
+    /// Receives the frames, checksums and world states sent by the server.
+    fn receive(
+        &mut self,
+        relay: &mut InputRelay<AX, AC>,
+        transfer: &mut StateTransfer,
+    ) -> Result<()> {
+        while let Some((frame, events)) = self.connection.try_frame()? {
+            match self.rollback {
+                Some(ref mut rollback) => rollback.confirm(frame, events),
+                None => self.scheduler.push(frame, events),
+            }
+        }
+        self.remote_checksums.extend(self.connection.drain_checksums());
+        if self.connection.take_state_request() {
+            self.state_requested = true;
+        }
+        if let Some((tick, data)) = self.connection.take_world_state() {
+            self.scheduler.clear();
+            if let Some(ref mut rollback) = self.rollback {
+                rollback.reset(tick);
+            }
+            self.sent_checksums = tick;
+            self.remote_checksums.clear();
+            relay.clear_frames();
+            transfer.load(tick, data);
+            self.awaiting_state = false;
+        }
+        Ok(())
+    }
+
+    /// Asks to rejoin the match of the lobby in the `Session`, or to spectate it again, which
+    /// makes the server send a new world state.
+    fn rejoin(&self, session: &mut Session, player: Option<u32>) {
+        let lobby = session.lobby().unwrap_or(0);
+        if self.spectator {
+            session.spectate(lobby);
+        } else {
+            session.rejoin_lobby(lobby, player);
+        }
+    }
+
+    /// Tries to connect to the server again, at most once per `RECONNECT_INTERVAL`, and asks to
+    /// rejoin or spectate the match once connected.
+    fn try_reconnect(&mut self, session: &mut Session, elapsed: Duration) {
+        if let Some(ref mut since) = self.since_reconnect {
+            *since += elapsed;
+            if *since < Duration::from_secs(RECONNECT_INTERVAL) {
+                return;
+            }
+        }
+        self.since_reconnect = Some(Duration::from_secs(0));
+        let result = match self.reconnect {
+            Some(ref connect) => connect(),
+            None => return,
+        };
+        match result {
+            Ok(transport) => {
+                info!("Reconnected to the lockstep server");
+                let player = self.connection.player();
+                self.connection = Connection::new(transport);
+                self.disconnected = false;
+                self.rejoin(session, player);
+            }
+            Err(err) => debug!("Failed to reconnect to the lockstep server: {}", err),
+        }
+    }
+
+    fn compare_checksums(
+        &mut self,
+        checksums: &WorldChecksum,
+        settled: Option<u64>,
+        events: &mut EventChannel<E>,
+    ) {
+        let oldest = checksums.oldest_tick();
+        let (desyncs, last_desync) = (&mut self.desyncs, &mut self.last_desync);
+        self.remote_checksums.retain(|&(tick, remote)| {
+            if settled.map_or(false, |settled| tick > settled) {
+                return true;
+            }
+            match checksums.get(tick) {
+                Some(local) => {
+                    if local != remote {
+                        warn!(
+                            "Desync detected on tick {}: local checksum {:x}, remote {:x}",
+                            tick, local, remote
+                        );
+                        *desyncs += 1;
+                        *last_desync = Some(tick);
+                        let event = NetworkEvent::DesyncDetected {
+                            tick,
+                            local,
+                            remote,
+                        };
+                        events.single_write(event.into());
+                    }
+                    false
+                }
+                None => oldest.map_or(true, |oldest| tick >= oldest),
+            }
+        });
+    }
+}
+
+impl<'a, AX, AC, E> System<'a> for LockstepSystem<AX, AC, E>
+where
+    AX: Hash + Eq + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    AC: Hash + Eq + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    E: From<NetworkEvent> + Send + Sync + 'static,
+{
+    type SystemData = (
+        Read<'a, Time>,
+        Write<'a, InputRelay<AX, AC>>,
+        Write<'a, PlayerInputs<AX, AC>>,
+        Write<'a, SimulationTime>,
+        Write<'a, WorldChecksum>,
+        Write<'a, EventChannel<E>>,
+        Write<'a, NetworkStats>,
+        Write<'a, Session>,
+        Write<'a, StateTransfer>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            frame_time,
+            mut relay,
+            mut players,
+            mut time,
+            mut checksums,
+            mut network_events,
+            mut stats,
+            mut session,
+            mut transfer,
+        ): Self::SystemData,
+    ) {
+        let settled = self.settled_tick();
+        let state_tick = time.tick();
+        let elapsed = frame_time.delta_real_time();
+        if transfer.take_failed() {
+            warn!("Asking the lockstep server for the world state again");
+            let player = self.connection.player();
+            self.rejoin(&mut session, player);
+            self.awaiting_state = true;
+        }
+        if self.disconnected {
+            self.try_reconnect(&mut session, elapsed);
+        }
+        if !self.disconnected {
+            let result = self.exchange(
+                &mut relay,
+                &mut checksums,
+                &mut session,
+                &mut transfer,
+                elapsed,
+            );
+            if let Err(err) = result {
+                match *err.kind() {
+                    ErrorKind::Disconnected => error!("Lost connection to the lockstep server"),
+                    _ => error!("Lockstep connection failed: {}", err),
+                }
+                self.disconnected = true;
+            }
+        }
+        if self.state_requested && settled.map_or(true, |settled| settled >= state_tick) {
+            transfer.capture(state_tick);
+            self.state_requested = false;
+        }
+        self.compare_checksums(&checksums, settled, &mut network_events);
+        for event in self.connection.drain_events() {
+            session.apply(&event);
+            network_events.single_write(event.into());
+        }
+
+        let released = match self.rollback {
+            _ if transfer.is_loading() || self.awaiting_state => Vec::new(),
+            Some(ref mut rollback) => {
+                if let Some((tick, frames)) = rollback.take_rewind() {
+                    debug!("Misprediction, rolling back to tick {}", tick);
+                    relay.clear_frames();
+                    for (frame, events) in frames {
+                        relay.push_frame(frame, events);
+                    }
+                    time.rewind(tick);
+                }
+                rollback.next(elapsed, time.tick_time()).into_iter().collect()
+            }
+            None => self.scheduler.release(elapsed, time.tick_time()),
+        };
+        match released.last().map(|&(frame, _)| frame) {
+            Some(last) => {
+                for (frame, events) in released {
+                    relay.push_frame(frame, events);
+                }
+                time.advance_to(last);
+            }
+            None => time.stall(),
+        }
+
+        *stats = self.connection.stats().clone();
+        if let Some(frame) = self.connection.estimated_server_frame(self.frame_duration) {
+            stats.set_tick_offset(frame as i64 - time.target() as i64);
+        }
+        let (confirmed, depth) = match self.rollback {
+            Some(ref rollback) => (
+                rollback.confirmed(),
+                rollback.simulated().saturating_sub(rollback.confirmed()),
+            ),
+            None => (self.connection.last_frame(), 0),
+        };
+        stats.set_confirmed_tick(confirmed);
+        stats.set_input_delay(self.input_delay);
+        stats.set_rollback_depth(depth);
+        stats.set_desyncs(self.desyncs, self.last_desync);
+        if let Some(max) = self.max_input_delay {
+            if stats.samples() > 0 {
+                self.input_delay = stats.recommended_input_delay(self.frame_duration).min(max);
+            }
+        }
+
+        if let Some(player) = self.connection.player() {
+            if players.local_player() != Some(player) {
+                players.set_local_player(player);
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        let mut time = res.fetch_mut::<SimulationTime>();
+        time.set_source(TickSource::Network);
+        time.set_tick_time(self.frame_duration);
+    }
+}
@@ -0,0 +1,86 @@
+//! In-process loopback transport.
+
+use super::{Listener, Transport};
+use error::{ErrorKind, Result};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+
+/// Transport connecting two ends within the same process through channels.
+///
+/// Useful to run a server and its clients inside a single test.
+pub struct LoopbackTransport {
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+}
+
+impl LoopbackTransport {
+    /// Creates two transports connected to each other.
+    pub fn pair() -> (LoopbackTransport, LoopbackTransport) {
+        let (a_sender, b_receiver) = channel();
+        let (b_sender, a_receiver) = channel();
+        (
+            LoopbackTransport {
+                sender: a_sender,
+                receiver: a_receiver,
+            },
+            LoopbackTransport {
+                sender: b_sender,
+                receiver: b_receiver,
+            },
+        )
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, payload: &[u8]) -> Result<()> {
+        self.sender
+            .send(payload.to_vec())
+            .map_err(|_| ErrorKind::Disconnected.into())
+    }
+
+    fn recv(&mut self) -> Result<Option<Vec<u8>>> {
+        match self.receiver.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(ErrorKind::Disconnected.into()),
+        }
+    }
+}
+
+/// Accepts loopback connections made through its `LoopbackConnector`s.
+pub struct LoopbackListener {
+    incoming: Receiver<LoopbackTransport>,
+}
+
+impl LoopbackListener {
+    /// Creates a listener and a connector to connect to it.
+    pub fn bind() -> (LoopbackListener, LoopbackConnector) {
+        let (sender, incoming) = channel();
+        (LoopbackListener { incoming }, LoopbackConnector { sender })
+    }
+}
+
+impl Listener for LoopbackListener {
+    fn accept(&mut self) -> Result<Option<Box<Transport>>> {
+        match self.incoming.try_recv() {
+            Ok(transport) => Ok(Some(Box::new(transport))),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// Connects new clients to a `LoopbackListener`.
+#[derive(Clone)]
+pub struct LoopbackConnector {
+    sender: Sender<LoopbackTransport>,
+}
+
+impl LoopbackConnector {
+    /// Connects a new client, returning the client side transport.
+    pub fn connect(&self) -> Result<LoopbackTransport> {
+        let (client, server) = LoopbackTransport::pair();
+        self.sender
+            .send(server)
+            .map_err(|_| ErrorKind::Disconnected)?;
+        Ok(client)
+    }
+}
@@ -0,0 +1,76 @@
+//! Transports carrying messages between the lockstep server and its clients.
+//!
+//! The networking layer only talks to the `Transport` and `Listener` traits, so that the
+//! underlying protocol can be exchanged. TCP, UDP and in-process loopback implementations are
+//! provided, along with a `SimulatedTransport` adding latency, loss and reordering to another
+//! transport for tests.
+
+pub use self::loopback::{LoopbackConnector, LoopbackListener, LoopbackTransport};
+pub use self::simulated::{LinkClock, LinkConditions, SimulatedListener, SimulatedTransport};
+pub use self::tcp::{TcpListener, TcpTransport};
+pub use self::udp::{UdpListener, UdpTransport};
+
+use error::Result;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+
+mod loopback;
+mod simulated;
+mod tcp;
+mod udp;
+
+/// Maximum size of a single message in bytes.
+pub const MAX_MESSAGE_SIZE: usize = 65_507;
+
+/// A message based connection to a single peer.
+///
+/// Implementations never block: messages are queued on `send` and `recv` only returns the
+/// messages which have already arrived.
+pub trait Transport: Send {
+    /// Sends a message to the peer.
+    fn send(&mut self, payload: &[u8]) -> Result<()>;
+
+    /// Returns the next message received from the peer, or `None` if there is none yet.
+    fn recv(&mut self) -> Result<Option<Vec<u8>>>;
+}
+
+/// Accepts connections of new peers on the server side.
+pub trait Listener: Send {
+    /// Returns the transport of a newly connected peer, or `None` if no peer is waiting.
+    ///
+    /// Errors concerning a single incoming connection are logged and the connection skipped.
+    /// An error is only returned if the listener can't accept connections anymore.
+    fn accept(&mut self) -> Result<Option<Box<Transport>>>;
+}
+
+/// Error codes of the operating system for running out of file descriptors or sockets.
+#[cfg(unix)]
+const OUT_OF_FILES: &[i32] = &[23, 24];
+#[cfg(windows)]
+const OUT_OF_FILES: &[i32] = &[10024];
+#[cfg(not(any(unix, windows)))]
+const OUT_OF_FILES: &[i32] = &[];
+
+/// Returns true if an error of a listener only concerns a single connection, or is a shortage of
+/// resources which may be over on the next try, so that the listener can keep accepting.
+fn is_connection_error(err: &IoError) -> bool {
+    match err.kind() {
+        IoErrorKind::ConnectionAborted
+        | IoErrorKind::ConnectionRefused
+        | IoErrorKind::ConnectionReset
+        | IoErrorKind::Interrupted
+        | IoErrorKind::TimedOut => true,
+        _ => err
+            .raw_os_error()
+            .map_or(false, |code| OUT_OF_FILES.contains(&code)),
+    }
+}
+
+impl Transport for Box<Transport> {
+    fn send(&mut self, payload: &[u8]) -> Result<()> {
+        (**self).send(payload)
+    }
+
+    fn recv(&mut self) -> Result<Option<Vec<u8>>> {
+        (**self).recv()
+    }
+}
@@ -0,0 +1,261 @@
+//! Simulated network conditions on top of another transport.
+
+use super::{Listener, Transport};
+use error::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Network conditions applied to the messages sent through a `SimulatedTransport`.
+///
+/// Delays are counted in steps of the `LinkClock` shared by the transports. The default is a
+/// perfect link, delivering every message on the step it was sent in.
+#[derive(Clone, Debug, Default)]
+pub struct LinkConditions {
+    /// Number of steps every message takes to arrive.
+    pub latency: u64,
+    /// Maximum number of steps added at random to the latency of a message.
+    pub jitter: u64,
+    /// Probability for a message to be lost, from 0 to 1.
+    pub loss: f32,
+    /// Probability for a message to be held back until the messages sent after it arrived, from
+    /// 0 to 1. On a reliable link, the messages sent after it wait for it instead.
+    pub reorder: f32,
+    /// Keeps the messages in order and sends lost messages again after a round trip, like TCP
+    /// does: lost and reordered messages hold back the ones sent after them. The lockstep
+    /// protocol needs a reliable link.
+    pub reliable: bool,
+}
+
+/// Step counter shared by simulated transports, advanced by the code driving the simulation.
+#[derive(Clone, Debug, Default)]
+pub struct LinkClock {
+    steps: Arc<AtomicUsize>,
+}
+
+impl LinkClock {
+    /// Creates a clock at step 0.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Advances the clock by one step.
+    pub fn advance(&self) {
+        self.steps.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Returns the current step.
+    pub fn now(&self) -> u64 {
+        self.steps.load(Ordering::SeqCst) as u64
+    }
+}
+
+/// Transport applying `LinkConditions` to the messages sent through another transport.
+///
+/// Sent messages are held back until they are due, and handed to the inner transport on the
+/// next `send` or `recv` once the `LinkClock` reached their step. Random decisions come from a
+/// generator seeded at creation, so a simulation driven by the same steps behaves the same way
+/// on every run.
+///
+/// Only the messages sent from this end are affected; wrap both ends to affect both directions.
+pub struct SimulatedTransport<T> {
+    inner: T,
+    conditions: LinkConditions,
+    clock: LinkClock,
+    random: u64,
+    in_flight: Vec<(u64, u64, Vec<u8>)>,
+    sent: u64,
+    last_arrival: u64,
+}
+
+impl<T> SimulatedTransport<T>
+where
+    T: Transport,
+{
+    /// Wraps `inner`, with the random decisions seeded by `seed`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if a reliable link loses every message.
+    pub fn new(inner: T, conditions: LinkConditions, clock: LinkClock, seed: u64) -> Self {
+        assert!(
+            !conditions.reliable || conditions.loss < 1.0,
+            "A reliable link can't lose every message"
+        );
+        SimulatedTransport {
+            inner,
+            conditions,
+            clock,
+            random: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+            in_flight: Vec::new(),
+            sent: 0,
+            last_arrival: 0,
+        }
+    }
+
+    /// Returns the number of messages sent but not arrived yet.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Returns the next number of the xorshift generator.
+    fn next_random(&mut self) -> u64 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        self.random
+    }
+
+    /// Returns true with the given probability.
+    fn chance(&mut self, probability: f32) -> bool {
+        probability > 0.0 && ((self.next_random() >> 40) as f32 / (1 << 24) as f32) < probability
+    }
+
+    /// Hands the messages which arrived to the inner transport.
+    fn deliver(&mut self) -> Result<()> {
+        let now = self.clock.now();
+        self.in_flight.sort_by_key(|&(arrival, sent, _)| (arrival, sent));
+        while self
+            .in_flight
+            .first()
+            .map_or(false, |&(arrival, _, _)| arrival <= now)
+        {
+            let (_, _, payload) = self.in_flight.remove(0);
+            self.inner.send(&payload)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Transport for SimulatedTransport<T>
+where
+    T: Transport,
+{
+    fn send(&mut self, payload: &[u8]) -> Result<()> {
+        let mut arrival = self.clock.now() + self.conditions.latency;
+        if self.conditions.jitter > 0 {
+            arrival += self.next_random() % (self.conditions.jitter + 1);
+        }
+        let (loss, reorder) = (self.conditions.loss, self.conditions.reorder);
+        if self.conditions.reliable {
+            while self.chance(loss) {
+                arrival += self.conditions.latency * 2 + 1;
+            }
+        } else if self.chance(loss) {
+            return self.deliver();
+        }
+        if self.chance(reorder) {
+            arrival += self.conditions.jitter + 1;
+        }
+        if self.conditions.reliable {
+            arrival = arrival.max(self.last_arrival);
+            self.last_arrival = arrival;
+        }
+        self.in_flight.push((arrival, self.sent, payload.to_vec()));
+        self.sent += 1;
+        self.deliver()
+    }
+
+    fn recv(&mut self) -> Result<Option<Vec<u8>>> {
+        self.deliver()?;
+        self.inner.recv()
+    }
+}
+
+/// Listener applying `LinkConditions` to the messages sent to the peers it accepts.
+pub struct SimulatedListener<L> {
+    inner: L,
+    conditions: LinkConditions,
+    clock: LinkClock,
+    seed: u64,
+}
+
+impl<L> SimulatedListener<L>
+where
+    L: Listener,
+{
+    /// Wraps `inner`. The transport of every accepted peer is seeded with the next number after
+    /// `seed`.
+    pub fn new(inner: L, conditions: LinkConditions, clock: LinkClock, seed: u64) -> Self {
+        SimulatedListener {
+            inner,
+            conditions,
+            clock,
+            seed,
+        }
+    }
+}
+
+impl<L> Listener for SimulatedListener<L>
+where
+    L: Listener,
+{
+    fn accept(&mut self) -> Result<Option<Box<Transport>>> {
+        Ok(match self.inner.accept()? {
+            Some(transport) => {
+                self.seed = self.seed.wrapping_add(1);
+                let transport = SimulatedTransport::new(
+                    transport,
+                    self.conditions.clone(),
+                    self.clock.clone(),
+                    self.seed,
+                );
+                Some(Box::new(transport))
+            }
+            None => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transport::LoopbackTransport;
+
+    fn receive_all(transport: &mut LoopbackTransport) -> Vec<u8> {
+        let mut received = Vec::new();
+        while let Some(payload) = transport.recv().unwrap() {
+            received.extend(payload);
+        }
+        received
+    }
+
+    #[test]
+    fn delays_messages_by_the_latency() {
+        let (a, mut b) = LoopbackTransport::pair();
+        let clock = LinkClock::new();
+        let conditions = LinkConditions {
+            latency: 2,
+            ..Default::default()
+        };
+        let mut a = SimulatedTransport::new(a, conditions, clock.clone(), 0);
+        a.send(&[1]).unwrap();
+        clock.advance();
+        a.recv().unwrap();
+        assert!(receive_all(&mut b).is_empty());
+        clock.advance();
+        a.recv().unwrap();
+        assert_eq!(receive_all(&mut b), vec![1]);
+    }
+
+    #[test]
+    fn keeps_reliable_messages_in_order() {
+        let (a, mut b) = LoopbackTransport::pair();
+        let clock = LinkClock::new();
+        let conditions = LinkConditions {
+            latency: 1,
+            jitter: 3,
+            loss: 0.5,
+            reorder: 0.5,
+            reliable: true,
+        };
+        let mut a = SimulatedTransport::new(a, conditions, clock.clone(), 7);
+        for message in 0..20 {
+            a.send(&[message]).unwrap();
+        }
+        while a.in_flight() > 0 {
+            clock.advance();
+            a.recv().unwrap();
+        }
+        assert_eq!(receive_all(&mut b), (0..20).collect::<Vec<_>>());
+    }
+}
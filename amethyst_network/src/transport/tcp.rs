@@ -0,0 +1,161 @@
+//! TCP transport.
+
+use super::{is_connection_error, Listener, Transport, MAX_MESSAGE_SIZE};
+use error::{ErrorKind, Result};
+use std::io::{ErrorKind as IoErrorKind, Read, Write};
+use std::net::{self, SocketAddr, TcpStream, ToSocketAddrs};
+
+/// Transport over a TCP stream.
+///
+/// Messages are framed by a 4 byte big endian length prefix.
+pub struct TcpTransport {
+    stream: TcpStream,
+    read_buf: Vec<u8>,
+    write_buf: Vec<u8>,
+    closed: bool,
+}
+
+impl TcpTransport {
+    /// Connects to the peer at the given address.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        TcpTransport::from_stream(TcpStream::connect(addr)?)
+    }
+
+    /// Creates a transport from an already connected stream.
+    pub fn from_stream(stream: TcpStream) -> Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(TcpTransport {
+            stream,
+            read_buf: Vec::new(),
+            write_buf: Vec::new(),
+            closed: false,
+        })
+    }
+
+    /// Returns the address of the peer.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.stream.peer_addr()?)
+    }
+
+    /// Writes as much of the pending data as the socket accepts.
+    fn flush(&mut self) -> Result<()> {
+        while !self.write_buf.is_empty() {
+            match self.stream.write(&self.write_buf) {
+                Ok(0) => return Err(ErrorKind::Disconnected.into()),
+                Ok(n) => {
+                    self.write_buf.drain(..n);
+                }
+                Err(ref e) if e.kind() == IoErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == IoErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads everything available on the socket into the read buffer.
+    fn fill(&mut self) -> Result<()> {
+        let mut buf = [0; 4096];
+        while !self.closed {
+            match self.stream.read(&mut buf) {
+                Ok(0) => self.closed = true,
+                Ok(n) => self.read_buf.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == IoErrorKind::WouldBlock => return Ok(()),
+                Err(ref e) if e.kind() == IoErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the next complete message out of the read buffer.
+    fn take_message(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.read_buf.len() < 4 {
+            return Ok(None);
+        }
+        let len = (self.read_buf[0] as usize) << 24
+            | (self.read_buf[1] as usize) << 16
+            | (self.read_buf[2] as usize) << 8
+            | self.read_buf[3] as usize;
+        if len > MAX_MESSAGE_SIZE {
+            return Err(ErrorKind::MessageTooLarge(len).into());
+        }
+        if self.read_buf.len() < 4 + len {
+            return Ok(None);
+        }
+        let message = self.read_buf[4..4 + len].to_vec();
+        self.read_buf.drain(..4 + len);
+        Ok(Some(message))
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, payload: &[u8]) -> Result<()> {
+        let len = payload.len();
+        if len > MAX_MESSAGE_SIZE {
+            return Err(ErrorKind::MessageTooLarge(len).into());
+        }
+        self.write_buf.extend_from_slice(&[
+            (len >> 24) as u8,
+            (len >> 16) as u8,
+            (len >> 8) as u8,
+            len as u8,
+        ]);
+        self.write_buf.extend_from_slice(payload);
+        self.flush()
+    }
+
+    fn recv(&mut self) -> Result<Option<Vec<u8>>> {
+        self.flush()?;
+        if let Some(message) = self.take_message()? {
+            return Ok(Some(message));
+        }
+        self.fill()?;
+        match self.take_message()? {
+            None if self.closed => Err(ErrorKind::Disconnected.into()),
+            message => Ok(message),
+        }
+    }
+}
+
+/// Accepts TCP connections.
+pub struct TcpListener {
+    listener: net::TcpListener,
+}
+
+impl TcpListener {
+    /// Starts listening on the given address.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        let listener = net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(TcpListener { listener })
+    }
+
+    /// Returns the address the listener is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+}
+
+impl Listener for TcpListener {
+    fn accept(&mut self) -> Result<Option<Box<Transport>>> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => match TcpTransport::from_stream(stream) {
+                    Ok(transport) => {
+                        debug!("Accepted TCP connection from {}", addr);
+                        return Ok(Some(Box::new(transport)));
+                    }
+                    Err(err) => warn!("Dropped TCP connection from {}: {}", addr, err),
+                },
+                Err(ref e) if e.kind() == IoErrorKind::WouldBlock => return Ok(None),
+                Err(ref e) if is_connection_error(e) => {
+                    warn!("Failed to accept a TCP connection: {}", e);
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! UDP transport.
+
+use super::{is_connection_error, Listener, Transport, MAX_MESSAGE_SIZE};
+use error::{ErrorKind, Result};
+use fnv::FnvHashMap as HashMap;
+use std::collections::VecDeque;
+use std::io::ErrorKind as IoErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+
+/// Transport over UDP, every message is sent as a single datagram.
+///
+/// UDP does not retransmit lost datagrams nor keep them in order, so messages may be lost or
+/// arrive out of order. A lockstep `Connection` fails with `ErrorKind::FrameGap` as soon as a
+/// frame is lost or reordered, so only use it for lockstep on networks which don't lose
+/// datagrams, and set `LockstepBundle::with_reconnect` to recover from a gap.
+pub struct UdpTransport {
+    socket: UdpSocket,
+}
+
+impl UdpTransport {
+    /// Binds a socket to `local` and connects it to the peer at `peer`.
+    pub fn connect<A, B>(local: A, peer: B) -> Result<Self>
+    where
+        A: ToSocketAddrs,
+        B: ToSocketAddrs,
+    {
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        socket.set_nonblocking(true)?;
+        Ok(UdpTransport { socket })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, payload: &[u8]) -> Result<()> {
+        if payload.len() > MAX_MESSAGE_SIZE {
+            return Err(ErrorKind::MessageTooLarge(payload.len()).into());
+        }
+        self.socket.send(payload)?;
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut buf = [0; MAX_MESSAGE_SIZE];
+        match self.socket.recv(&mut buf) {
+            Ok(n) => Ok(Some(buf[..n].to_vec())),
+            Err(ref e) if e.kind() == IoErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// State shared by a `UdpListener` and the transports of its peers.
+struct UdpShared {
+    socket: UdpSocket,
+    queues: HashMap<SocketAddr, VecDeque<Vec<u8>>>,
+    new_peers: VecDeque<SocketAddr>,
+}
+
+impl UdpShared {
+    /// Sorts every datagram waiting on the socket into the queue of its sender.
+    ///
+    /// Errors reported for a single peer, like the connection resets Windows reports on the
+    /// shared socket when a peer is gone, are logged and skipped.
+    fn pump(&mut self) -> Result<()> {
+        let mut buf = [0; MAX_MESSAGE_SIZE];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((n, addr)) => {
+                    let new_peers = &mut self.new_peers;
+                    self.queues
+                        .entry(addr)
+                        .or_insert_with(|| {
+                            new_peers.push_back(addr);
+                            VecDeque::new()
+                        })
+                        .push_back(buf[..n].to_vec());
+                }
+                Err(ref e) if e.kind() == IoErrorKind::WouldBlock => return Ok(()),
+                Err(ref e) if e.kind() == IoErrorKind::ConnectionReset => {
+                    debug!("UDP peer unreachable: {}", e);
+                }
+                Err(ref e) if is_connection_error(e) => {
+                    warn!("Failed to receive UDP datagrams: {}", e);
+                    return Ok(());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Accepts UDP peers on a single socket.
+///
+/// A peer is accepted when its first datagram arrives.
+pub struct UdpListener {
+    shared: Arc<Mutex<UdpShared>>,
+}
+
+impl UdpListener {
+    /// Starts listening on the given address.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(UdpListener {
+            shared: Arc::new(Mutex::new(UdpShared {
+                socket,
+                queues: HashMap::default(),
+                new_peers: VecDeque::new(),
+            })),
+        })
+    }
+
+    /// Returns the address the listener is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.shared.lock().unwrap().socket.local_addr()?)
+    }
+}
+
+impl Listener for UdpListener {
+    fn accept(&mut self) -> Result<Option<Box<Transport>>> {
+        let mut shared = self.shared.lock().unwrap();
+        shared.pump()?;
+        Ok(shared.new_peers.pop_front().map(|addr| {
+            debug!("Accepted UDP peer {}", addr);
+            Box::new(UdpPeer {
+                shared: self.shared.clone(),
+                addr,
+            }) as Box<Transport>
+        }))
+    }
+}
+
+/// Server side transport of a peer accepted by a `UdpListener`.
+struct UdpPeer {
+    shared: Arc<Mutex<UdpShared>>,
+    addr: SocketAddr,
+}
+
+impl Transport for UdpPeer {
+    fn send(&mut self, payload: &[u8]) -> Result<()> {
+        if payload.len() > MAX_MESSAGE_SIZE {
+            return Err(ErrorKind::MessageTooLarge(payload.len()).into());
+        }
+        let shared = self.shared.lock().unwrap();
+        shared.socket.send_to(payload, self.addr)?;
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut shared = self.shared.lock().unwrap();
+        shared.pump()?;
+        Ok(shared
+            .queues
+            .get_mut(&self.addr)
+            .and_then(|queue| queue.pop_front()))
+    }
+}
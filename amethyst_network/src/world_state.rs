@@ -0,0 +1,302 @@
+//! Transfer of the world state to clients joining a match in progress.
+
+use amethyst_core::specs::prelude::{
+    Component, Entities, Join, ReadStorage, Resources, RunNow, SystemData, WriteStorage,
+};
+use amethyst_core::snapshot::RollbackReset;
+use amethyst_core::timing::SimulationTime;
+use bincode;
+use error::{ErrorKind, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::mem;
+
+type SaveFn = fn(&Resources) -> Result<Vec<u8>>;
+type LoadFn = fn(&Resources, &[u8]) -> Result<()>;
+type SetupFn = fn(&mut Resources);
+
+/// World states handed between the `LockstepSystem` and the `WorldStateSystem`.
+#[derive(Debug, Default)]
+pub struct StateTransfer {
+    capture: Option<u64>,
+    captured: Option<(u64, Vec<u8>)>,
+    received: Option<(u64, Vec<u8>)>,
+    failed: bool,
+}
+
+impl StateTransfer {
+    /// Asks for the current world state, which is the state after the given tick.
+    pub(crate) fn capture(&mut self, tick: u64) {
+        self.capture = Some(tick);
+    }
+
+    /// Takes the world state saved since it was asked for, along with its tick.
+    pub(crate) fn take_captured(&mut self) -> Option<(u64, Vec<u8>)> {
+        self.captured.take()
+    }
+
+    /// Hands over a world state received from the server, to be loaded.
+    pub(crate) fn load(&mut self, tick: u64, data: Vec<u8>) {
+        self.received = Some((tick, data));
+    }
+
+    /// Returns true if a world state received from the server was not loaded yet.
+    pub(crate) fn is_loading(&self) -> bool {
+        self.received.is_some()
+    }
+
+    /// Returns true once after a world state received from the server failed to load.
+    pub(crate) fn take_failed(&mut self) -> bool {
+        mem::replace(&mut self.failed, false)
+    }
+}
+
+/// Saves and loads the world state of a lockstep match, for the clients joining it in progress.
+///
+/// When a client joins a match in progress, the server asks another client for its world state.
+/// That client saves the registered components and resources, and the server forwards them to
+/// the joining client, along with the frames it broadcast since. The joining client loads the
+/// state, jumps its `SimulationTime` to the tick of the state, and simulates the frames which
+/// follow it to catch up. The snapshots of the `Rollback` are reset, as they are from before the
+/// jump. If the state fails to load, the `SimulationTime` is left alone and the
+/// `LockstepSystem` keeps the client stalled while it asks the server for the state again.
+///
+/// Register every component and resource the simulation depends on, in the same order on every
+/// client. Components are matched to entities by their index: the joining client needs to have
+/// created the same entities in the same order as the other clients, for example by loading the
+/// same level. Entities are not created or deleted by loading a state, so components of entities
+/// missing on the joining client are dropped.
+///
+/// This is a thread-local system, which the `LockstepBundle` adds.
+#[derive(Default)]
+pub struct WorldStateSystem {
+    entries: Vec<(SaveFn, LoadFn, SetupFn)>,
+}
+
+impl WorldStateSystem {
+    /// Creates a system without any component or resource.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers a component.
+    pub fn with<C>(mut self) -> Self
+    where
+        C: Component + Serialize + DeserializeOwned,
+    {
+        self.entries.push((
+            save_component::<C>,
+            load_component::<C>,
+            setup_component::<C>,
+        ));
+        self
+    }
+
+    /// Registers a resource.
+    pub fn with_resource<R>(mut self) -> Self
+    where
+        R: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+    {
+        self.entries.push((
+            save_resource::<R>,
+            load_resource::<R>,
+            setup_resource::<R>,
+        ));
+        self
+    }
+
+    /// Saves the registered components and resources of the world.
+    pub fn save(&self, res: &Resources) -> Result<Vec<u8>> {
+        let data = self
+            .entries
+            .iter()
+            .map(|&(save, _, _)| save(res))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(bincode::serialize(&data)?)
+    }
+
+    /// Loads a state saved by a system with the same registrations into the world.
+    pub fn load(&self, res: &Resources, state: &[u8]) -> Result<()> {
+        let data: Vec<Vec<u8>> = bincode::deserialize(state)?;
+        if data.len() != self.entries.len() {
+            bail!(ErrorKind::StateMismatch);
+        }
+        for (&(_, load, _), data) in self.entries.iter().zip(&data) {
+            load(res, data)?;
+        }
+        Ok(())
+    }
+}
+
+fn save_component<C>(res: &Resources) -> Result<Vec<u8>>
+where
+    C: Component + Serialize + DeserializeOwned,
+{
+    let (entities, storage) = <(Entities, ReadStorage<C>)>::fetch(res);
+    let saved = (&*entities, &storage)
+        .join()
+        .map(|(entity, component)| (entity.id(), component))
+        .collect::<Vec<_>>();
+    Ok(bincode::serialize(&saved)?)
+}
+
+fn load_component<C>(res: &Resources, data: &[u8]) -> Result<()>
+where
+    C: Component + Serialize + DeserializeOwned,
+{
+    let saved: Vec<(u32, C)> = bincode::deserialize(data)?;
+    let (entities, mut storage) = <(Entities, WriteStorage<C>)>::fetch(res);
+    let current = (&*entities, &storage)
+        .join()
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    for entity in current {
+        storage.remove(entity);
+    }
+    for (id, component) in saved {
+        let entity = entities.entity(id);
+        if entities.is_alive(entity) {
+            storage
+                .insert(entity, component)
+                .expect("Failed to load the component of a live entity");
+        }
+    }
+    Ok(())
+}
+
+fn setup_component<C>(res: &mut Resources)
+where
+    C: Component + Serialize + DeserializeOwned,
+{
+    <(Entities, ReadStorage<C>)>::setup(res);
+}
+
+fn save_resource<R>(res: &Resources) -> Result<Vec<u8>>
+where
+    R: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+{
+    Ok(bincode::serialize(&*res.fetch::<R>())?)
+}
+
+fn load_resource<R>(res: &Resources, data: &[u8]) -> Result<()>
+where
+    R: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+{
+    *res.fetch_mut::<R>() = bincode::deserialize(data)?;
+    Ok(())
+}
+
+fn setup_resource<R>(res: &mut Resources)
+where
+    R: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+{
+    res.entry::<R>().or_insert_with(R::default);
+}
+
+impl<'a> RunNow<'a> for WorldStateSystem {
+    fn run_now(&mut self, res: &'a Resources) {
+        let mut transfer = res.fetch_mut::<StateTransfer>();
+        if let Some(tick) = transfer.capture.take() {
+            match self.save(res) {
+                Ok(data) => transfer.captured = Some((tick, data)),
+                Err(err) => error!("Failed to save the world state: {}", err),
+            }
+        }
+        if let Some((tick, data)) = transfer.received.take() {
+            match self.load(res, &data) {
+                Ok(()) => {
+                    info!("Loaded the world state of tick {}", tick);
+                    res.fetch_mut::<SimulationTime>().jump_to(tick);
+                    res.fetch_mut::<RollbackReset>().request();
+                }
+                Err(err) => {
+                    error!("Failed to load the world state of tick {}: {}", tick, err);
+                    transfer.failed = true;
+                }
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        res.entry::<StateTransfer>()
+            .or_insert_with(StateTransfer::default);
+        res.entry::<SimulationTime>()
+            .or_insert_with(SimulationTime::default);
+        res.entry::<RollbackReset>()
+            .or_insert_with(RollbackReset::default);
+        for &(_, _, setup) in &self.entries {
+            setup(res);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::specs::prelude::{Builder, World};
+    use amethyst_core::transform::Transform;
+
+    #[derive(Default, Serialize, Deserialize)]
+    struct Score(u32);
+
+    fn world(system: &mut WorldStateSystem) -> World {
+        let mut world = World::new();
+        RunNow::setup(system, &mut world.res);
+        world.create_entity().build();
+        world.create_entity().build();
+        world
+    }
+
+    #[test]
+    fn loads_the_state_of_another_world() {
+        let mut system = WorldStateSystem::new()
+            .with::<Transform>()
+            .with_resource::<Score>();
+        let source = world(&mut system);
+        let mut transform = Transform::default();
+        transform.translation.x = 5.0;
+        let entity = source.entities().entity(1);
+        source
+            .write_storage::<Transform>()
+            .insert(entity, transform)
+            .unwrap();
+        source.write_resource::<Score>().0 = 3;
+        source.write_resource::<StateTransfer>().capture(12);
+        system.run_now(&source.res);
+        let (tick, data) = source
+            .write_resource::<StateTransfer>()
+            .take_captured()
+            .expect("No state was saved");
+        assert_eq!(tick, 12);
+
+        let target = world(&mut system);
+        target.write_resource::<StateTransfer>().load(tick, data);
+        system.run_now(&target.res);
+        let entity = target.entities().entity(1);
+        assert_eq!(
+            target
+                .read_storage::<Transform>()
+                .get(entity)
+                .map(|transform| transform.translation.x as i32),
+            Some(5)
+        );
+        assert_eq!(target.read_resource::<Score>().0, 3);
+        assert_eq!(target.read_resource::<SimulationTime>().tick(), 12);
+        assert!(target.write_resource::<RollbackReset>().take());
+    }
+
+    #[test]
+    fn keeps_the_tick_when_loading_fails() {
+        let mut system = WorldStateSystem::new()
+            .with::<Transform>()
+            .with_resource::<Score>();
+        let target = world(&mut system);
+        target.write_resource::<StateTransfer>().load(12, vec![1, 2, 3]);
+        system.run_now(&target.res);
+        assert_eq!(target.read_resource::<SimulationTime>().tick(), 0);
+        assert!(!target.write_resource::<RollbackReset>().take());
+        let mut transfer = target.write_resource::<StateTransfer>();
+        assert!(transfer.take_failed());
+        assert!(!transfer.take_failed());
+    }
+}
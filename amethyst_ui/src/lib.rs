@@ -7,7 +7,11 @@ extern crate amethyst_assets;
 extern crate amethyst_audio;
 extern crate amethyst_core;
 extern crate amethyst_input;
+#[cfg(feature = "network_stats")]
+extern crate amethyst_network;
 extern crate amethyst_renderer;
+#[cfg(feature = "network_stats")]
+extern crate amethyst_utils;
 extern crate clipboard;
 #[macro_use]
 extern crate derivative;
@@ -41,6 +45,7 @@ mod focused;
 mod format;
 mod image;
 mod layout;
+mod network_stats;
 mod pass;
 mod prefab;
 mod resize;
@@ -55,6 +60,9 @@ pub use self::focused::UiFocused;
 pub use self::format::{FontAsset, FontFormat, FontHandle, OtfFormat, TtfFormat};
 pub use self::image::UiImage;
 pub use self::layout::{Anchor, ScaleMode, Stretch, UiTransformSystem};
+pub use self::network_stats::UiNetworkStat;
+#[cfg(feature = "network_stats")]
+pub use self::network_stats::{NetworkStatsBundle, UiNetworkStatsSystem};
 pub use self::pass::DrawUi;
 pub use self::prefab::{
     UiCreator, UiFormat, UiImageBuilder, UiLoader, UiLoaderSystem, UiPrefab, UiTextBuilder,
@@ -0,0 +1,183 @@
+//! Network debug overlay
+//!
+//! The lines of the overlay are always available to `UiPrefab`s, the system filling them needs
+//! the `network_stats` feature.
+
+use amethyst_assets::{PrefabData, PrefabError};
+#[cfg(feature = "network_stats")]
+use amethyst_core::bundle::{Result as BundleResult, SystemBundle};
+use amethyst_core::specs::prelude::{Component, DenseVecStorage, Entity, WriteStorage};
+#[cfg(feature = "network_stats")]
+use amethyst_core::specs::prelude::{DispatcherBuilder, Join, Read, ReadStorage, System};
+#[cfg(feature = "network_stats")]
+use amethyst_core::timing::{duration_to_nanos, SimulationTime};
+#[cfg(feature = "network_stats")]
+use amethyst_network::NetworkStats;
+#[cfg(feature = "network_stats")]
+use amethyst_utils::fps_counter::FPSCounter;
+#[cfg(feature = "network_stats")]
+use std::time::Duration;
+#[cfg(feature = "network_stats")]
+use UiText;
+
+/// Color of the desync line once a desync was detected.
+#[cfg(feature = "network_stats")]
+const DESYNC_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+
+/// A line of the network debug overlay.
+///
+/// The `UiNetworkStatsSystem` replaces the `UiText` of every entity with this component by the
+/// statistic it names. The `NetworkStats` widget of a `UiPrefab` creates one per line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum UiNetworkStat {
+    /// Frame rate sampled by the `FPSCounter`
+    Fps,
+    /// Round-trip time, jitter and packet loss
+    Latency,
+    /// Current and confirmed simulation tick
+    Tick,
+    /// Input delay and rollback depth
+    Delay,
+    /// Desyncs detected, turning red on the first one
+    Desyncs,
+}
+
+impl UiNetworkStat {
+    /// Returns every line, in the default order of the overlay.
+    pub fn all() -> Vec<UiNetworkStat> {
+        vec![
+            UiNetworkStat::Fps,
+            UiNetworkStat::Latency,
+            UiNetworkStat::Tick,
+            UiNetworkStat::Delay,
+            UiNetworkStat::Desyncs,
+        ]
+    }
+}
+
+#[cfg(feature = "network_stats")]
+impl UiNetworkStat {
+    /// Returns the text of this line.
+    ///
+    /// ### Parameters:
+    ///
+    /// - `stats`: Statistics of the lockstep client
+    /// - `tick`: Last simulated tick
+    /// - `fps`: Sampled frame rate, if an `FPSCounter` is available
+    pub fn text(&self, stats: &NetworkStats, tick: u64, fps: Option<f32>) -> String {
+        match *self {
+            UiNetworkStat::Fps => match fps {
+                Some(fps) => format!("FPS {:.0}", fps),
+                None => "FPS N/A".to_string(),
+            },
+            UiNetworkStat::Latency => format!(
+                "RTT {} ms, jitter {} ms, loss {:.0}%",
+                millis(stats.rtt()),
+                millis(stats.jitter()),
+                stats.packet_loss() * 100.0
+            ),
+            UiNetworkStat::Tick => format!("Tick {}, confirmed {}", tick, stats.confirmed_tick()),
+            UiNetworkStat::Delay => format!(
+                "Input delay {}, rollback {}",
+                stats.input_delay(),
+                stats.rollback_depth()
+            ),
+            UiNetworkStat::Desyncs => match stats.last_desync() {
+                Some(last) => format!("DESYNC x{}, last on tick {}", stats.desyncs(), last),
+                None => "No desync".to_string(),
+            },
+        }
+    }
+}
+
+impl Component for UiNetworkStat {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl<'a> PrefabData<'a> for UiNetworkStat {
+    type SystemData = WriteStorage<'a, UiNetworkStat>;
+    type Result = ();
+
+    fn load_prefab(
+        &self,
+        entity: Entity,
+        storage: &mut Self::SystemData,
+        _: &[Entity],
+    ) -> Result<(), PrefabError> {
+        storage.insert(entity, *self)?;
+        Ok(())
+    }
+}
+
+/// Keeps the text of the `UiNetworkStat` lines up to date.
+///
+/// Reads the `NetworkStats` written by the `LockstepSystem`, the `SimulationTime` and, if the
+/// resource exists, the `FPSCounter` of the `FPSCounterSystem`.
+#[cfg(feature = "network_stats")]
+#[derive(Default)]
+pub struct UiNetworkStatsSystem;
+
+#[cfg(feature = "network_stats")]
+impl UiNetworkStatsSystem {
+    /// Creates a new `UiNetworkStatsSystem`.
+    pub fn new() -> Self {
+        UiNetworkStatsSystem
+    }
+}
+
+#[cfg(feature = "network_stats")]
+impl<'a> System<'a> for UiNetworkStatsSystem {
+    type SystemData = (
+        Read<'a, NetworkStats>,
+        Read<'a, SimulationTime>,
+        Option<Read<'a, FPSCounter>>,
+        ReadStorage<'a, UiNetworkStat>,
+        WriteStorage<'a, UiText>,
+    );
+
+    fn run(&mut self, (stats, time, fps, lines, mut texts): Self::SystemData) {
+        let fps = fps.map(|fps| fps.sampled_fps());
+        for (line, text) in (&lines, &mut texts).join() {
+            let new_text = line.text(&stats, time.tick(), fps);
+            if text.text != new_text {
+                text.text = new_text;
+            }
+            if *line == UiNetworkStat::Desyncs && stats.desyncs() > 0 {
+                text.color = DESYNC_COLOR;
+            }
+        }
+    }
+}
+
+/// Network stats bundle
+///
+/// Adds the `UiNetworkStatsSystem`, which fills the lines of the `NetworkStats` widgets. Add it
+/// along with the `UiBundle` to show the network debug overlay.
+///
+/// ## Errors
+///
+/// No errors will be returned by this bundle.
+#[cfg(feature = "network_stats")]
+#[derive(Default)]
+pub struct NetworkStatsBundle;
+
+#[cfg(feature = "network_stats")]
+impl NetworkStatsBundle {
+    /// Creates a new network stats bundle.
+    pub fn new() -> Self {
+        NetworkStatsBundle
+    }
+}
+
+#[cfg(feature = "network_stats")]
+impl<'a, 'b> SystemBundle<'a, 'b> for NetworkStatsBundle {
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> BundleResult<()> {
+        builder.add(UiNetworkStatsSystem::new(), "ui_network_stats", &[]);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "network_stats")]
+fn millis(duration: Duration) -> u64 {
+    duration_to_nanos(duration) / 1_000_000
+}
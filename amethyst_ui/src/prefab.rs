@@ -8,7 +8,7 @@ use amethyst_renderer::{Texture, TextureFormat, TextureMetadata, TexturePrefab};
 use serde::de::DeserializeOwned;
 use {
     Anchor, FontAsset, FontFormat, MouseReactive, OnUiActionImage, OnUiActionSound, Stretch,
-    TextEditing, UiButton, UiFocused, UiImage, UiText, UiTransform,
+    TextEditing, UiButton, UiFocused, UiImage, UiNetworkStat, UiText, UiTransform,
 };
 
 /// Loadable `UiTransform` data
@@ -456,6 +456,16 @@ where
         /// Button
         button: UiButtonBuilder<A, I, F>,
     },
+    /// Network debug overlay, one line of text per statistic
+    NetworkStats {
+        /// Spatial information for the overlay
+        transform: UiTransformBuilder,
+        /// Style of the lines, the text itself is replaced by the statistics
+        text: UiTextBuilder<F>,
+        /// Statistics to show, all of them by default
+        #[serde(default = "UiNetworkStat::all")]
+        lines: Vec<UiNetworkStat>,
+    },
 }
 
 fn default_container_image<I>() -> Option<UiImageBuilder<I>>
@@ -470,6 +480,7 @@ type UiPrefabData<A = AudioFormat, I = TextureFormat, F = FontFormat> = (
     Option<UiImageBuilder<I>>,
     Option<UiTextBuilder<F>>,
     Option<UiButtonBuilder<A, I, F>>,
+    Option<UiNetworkStat>,
 );
 
 /// Ui prefab
@@ -528,6 +539,7 @@ fn walk_ui_tree<A, I, F>(
                 Some(image),
                 None,
                 None,
+                None,
             ));
         }
 
@@ -537,6 +549,7 @@ fn walk_ui_tree<A, I, F>(
                 None,
                 Some(text),
                 None,
+                None,
             ));
         }
 
@@ -550,6 +563,7 @@ fn walk_ui_tree<A, I, F>(
                 background,
                 None,
                 None,
+                None,
             ));
             for child_widget in children {
                 let child_index = prefab.add(Some(current_index), None);
@@ -574,12 +588,40 @@ fn walk_ui_tree<A, I, F>(
                 }),
                 None,
                 Some(button),
+                None,
             ));
             prefab.add(
                 Some(current_index),
-                Some((Some(button_text_transform(id)), None, Some(text), None)),
+                Some((Some(button_text_transform(id)), None, Some(text), None, None)),
             );
         }
+
+        UiWidget::NetworkStats {
+            transform,
+            text,
+            lines,
+        } => {
+            let id = transform.id.clone();
+            prefab
+                .entity(current_index)
+                .unwrap()
+                .set_data((Some(transform), None, None, None, None));
+            for (index, line) in lines.into_iter().enumerate() {
+                let line_text = UiTextBuilder {
+                    color: text.color,
+                    editable: None,
+                    font: text.font.clone(),
+                    password: false,
+                    text: String::new(),
+                    font_size: text.font_size,
+                };
+                let line_transform = network_stat_transform(&id, index, text.font_size);
+                prefab.add(
+                    Some(current_index),
+                    Some((Some(line_transform), None, Some(line_text), None, Some(line))),
+                );
+            }
+        }
     }
 }
 
@@ -708,3 +750,14 @@ fn button_text_transform(mut id: String) -> UiTransformBuilder {
         })
         .transparent()
 }
+
+fn network_stat_transform(id: &str, index: usize, font_size: f32) -> UiTransformBuilder {
+    let height = font_size * 1.25;
+    UiTransformBuilder::default()
+        .with_id(format!("{}_line_{}", id, index))
+        .with_position(0., height * (index as f32 + 0.5), -1.)
+        .with_size(0., height)
+        .with_anchor(Anchor::TopMiddle)
+        .with_stretch(Stretch::X { x_margin: 0. })
+        .transparent()
+}
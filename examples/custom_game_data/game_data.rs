@@ -1,6 +1,6 @@
 use amethyst::core::{SystemBundle, ThreadPool};
 use amethyst::ecs::prelude::{Dispatcher, DispatcherBuilder, System, World};
-use amethyst::{DataInit, Error, Result};
+use amethyst::{DataDispose, DataInit, DataSimulate, Error, Result};
 
 pub struct CustomGameData<'a, 'b> {
     pub base: Dispatcher<'a, 'b>,
@@ -17,6 +17,10 @@ impl<'a, 'b> CustomGameData<'a, 'b> {
     }
 }
 
+impl<'a, 'b> DataSimulate for CustomGameData<'a, 'b> {}
+
+impl<'a, 'b> DataDispose for CustomGameData<'a, 'b> {}
+
 pub struct CustomGameDataBuilder<'a, 'b> {
     pub base: DispatcherBuilder<'a, 'b>,
     pub running: DispatcherBuilder<'a, 'b>,
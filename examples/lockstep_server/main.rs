@@ -0,0 +1,40 @@
+//! Relays the input of lockstep clients, such as the pong example, in lobbies of the configured
+//! number of players, until it receives SIGINT or SIGTERM.
+
+extern crate amethyst;
+extern crate ctrlc;
+extern crate serde_json;
+
+use amethyst::network::{LockstepServer, TcpListener};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+fn main() -> amethyst::network::Result<()> {
+    let conf = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "./config.json".to_string());
+    let conf_file = fs::File::open(conf).expect("Failed to read config file");
+    let config: Value = serde_json::from_reader(conf_file).expect("Failed to parse config file");
+    let addr = config["server"]
+        .as_str()
+        .expect("Missing lockstep server address in config file");
+    let players = config["players"]
+        .as_u64()
+        .expect("Missing number of players in config file");
+    let spectator_delay = config["spectator_delay"].as_u64().unwrap_or(0);
+
+    amethyst::start_logger(Default::default());
+
+    let listener = TcpListener::bind(addr)?;
+    println!("Lockstep server listening on {}", listener.local_addr()?);
+    let server = LockstepServer::new(listener)
+        .with_lobbies(players as u32)
+        .with_spectator_delay(spectator_delay);
+    let stop = server.stop_handle();
+    ctrlc::set_handler(move || stop.store(true, Ordering::SeqCst))
+        .expect("Failed to set the signal handler");
+    server.run(Duration::from_millis(20))
+}
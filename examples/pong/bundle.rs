@@ -4,11 +4,14 @@ use systems::{BounceSystem, MoveBallsSystem, PaddleSystem, WinnerSystem};
 
 /// A bundle is a convenient way to initialise related resources, components and systems in a
 /// world. This bundle prepares the world for a game of pong.
+///
+/// It is added to the simulation stage of the game data, so its systems only run on the frames
+/// confirmed by the lockstep server. They can't depend on any system outside of the bundle.
 pub struct PongBundle;
 
 impl<'a, 'b> SystemBundle<'a, 'b> for PongBundle {
     fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
-        builder.add(PaddleSystem, "paddle_system", &["input_system"]);
+        builder.add(PaddleSystem, "paddle_system", &[]);
         builder.add(MoveBallsSystem, "ball_system", &[]);
         builder.add(
             BounceSystem,
@@ -0,0 +1,89 @@
+use amethyst::input::is_close_requested;
+use amethyst::network::{NetworkEvent, Session};
+use amethyst::prelude::*;
+use pong::Pong;
+
+/// Waits in a lobby of the lockstep server until the match starts.
+pub struct Lobby {
+    lobby: Option<u32>,
+    rejoin: Option<u32>,
+    spectate: bool,
+}
+
+impl Lobby {
+    /// Joins the given lobby, or the first lobby with a free slot if `None`.
+    pub fn new(lobby: Option<u32>) -> Self {
+        Lobby {
+            lobby,
+            rejoin: None,
+            spectate: false,
+        }
+    }
+
+    /// Rejoins the match in progress of the given lobby as `player`, for example after a crash.
+    pub fn rejoin(lobby: Option<u32>, player: u32) -> Self {
+        Lobby {
+            lobby,
+            rejoin: Some(player),
+            spectate: false,
+        }
+    }
+
+    /// Watches the match of the given lobby, or of the single match if `None`.
+    pub fn spectate(lobby: Option<u32>) -> Self {
+        Lobby {
+            lobby,
+            rejoin: None,
+            spectate: true,
+        }
+    }
+}
+
+impl<'a, 'b> State<GameData<'a, 'b>, NetworkEvent> for Lobby {
+    fn on_start(&mut self, data: StateData<GameData>) {
+        let mut session = data.world.write_resource::<Session>();
+        if let Some(player) = self.rejoin {
+            session.rejoin_lobby(self.lobby.unwrap_or(0), Some(player));
+            return;
+        }
+        if self.spectate {
+            session.spectate(self.lobby.unwrap_or(0));
+            return;
+        }
+        match self.lobby {
+            Some(lobby) => session.join_lobby(lobby),
+            None => session.find_lobby(),
+        }
+        session.set_ready(true);
+    }
+
+    fn handle_event(
+        &mut self,
+        _: StateData<GameData>,
+        event: StateEvent<NetworkEvent>,
+    ) -> Trans<GameData<'a, 'b>, NetworkEvent> {
+        match event {
+            StateEvent::Window(ref event) if is_close_requested(event) => Trans::Quit,
+            // The game entities need to exist before the world state of the match is loaded.
+            StateEvent::Custom(NetworkEvent::LobbyJoined { .. }) if self.rejoin.is_some() => {
+                Trans::Switch(Box::new(Pong))
+            }
+            StateEvent::Custom(NetworkEvent::Spectating { .. }) => Trans::Switch(Box::new(Pong)),
+            StateEvent::Custom(NetworkEvent::LobbyJoined { lobby, slot }) => {
+                println!("Waiting for the other player in lobby {}, slot {}", lobby, slot);
+                Trans::None
+            }
+            StateEvent::Custom(NetworkEvent::LobbyRejected { .. }) => {
+                eprintln!("The lobby is full, its match already started or it doesn't exist");
+                Trans::Quit
+            }
+            StateEvent::Custom(NetworkEvent::MatchStarted { .. }) => Trans::Switch(Box::new(Pong)),
+            _ => Trans::None,
+        }
+    }
+
+    fn update(&mut self, data: StateData<GameData>) -> Trans<GameData<'a, 'b>, NetworkEvent> {
+        data.data.update(&data.world);
+        Trans::None
+    }
+}
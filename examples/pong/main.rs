@@ -1,47 +1,37 @@
 //! TODO: Rewrite for new renderer.
 
 extern crate amethyst;
+#[macro_use]
+extern crate serde_derive;
 extern crate serde_json;
-extern crate futures;
-extern crate tokio;
-extern crate tokio_codec;
-extern crate bytes;
-extern crate tokio_io;
-extern crate winit;
-extern crate moba_proto;
 
 mod audio;
 mod bundle;
+mod lobby;
 mod pong;
 mod systems;
 
-use winit::VirtualKeyCode;
-
 use amethyst::audio::AudioBundle;
 use amethyst::core::frame_limiter::FrameRateLimitStrategy;
-use amethyst::core::transform::TransformBundle;
+use amethyst::core::checksum::ChecksumBundle;
+use amethyst::core::transform::{Transform, TransformBundle};
 use amethyst::ecs::prelude::{Component, DenseVecStorage};
-use amethyst::input::{InputBundle, InputEvent};
+use amethyst::input::{InputBundle, InputSource, TickInputSystem};
+use amethyst::network::{LockstepBundle, NetworkEvent, TcpTransport};
 use amethyst::prelude::*;
 use amethyst::renderer::{DisplayConfig, DrawSprite, Pipeline, RenderBundle, Stage};
-use amethyst::ui::{DrawUi, UiBundle};
+use amethyst::ui::{DrawUi, NetworkStatsBundle, UiBundle};
+use amethyst::utils::fps_counter::FPSCounterBundle;
 
 use audio::Music;
 use bundle::PongBundle;
+use lobby::Lobby;
 use std::time::Duration;
 
 use serde_json::Value;
-use moba_proto::Service;
-use moba_proto::Message;
-use moba_proto::Client;
-use moba_proto::GameAction;
 
-use std::fs;
 use std::env;
-use std::sync::{Arc, Mutex};
-use std::sync::mpsc;
-use futures::{Stream, future, Sink};
-use futures::sync::mpsc::{unbounded};
+use std::fs;
 
 const ARENA_HEIGHT: f32 = 100.0;
 const ARENA_WIDTH: f32 = 100.0;
@@ -79,81 +69,20 @@ fn main() -> amethyst::Result<()> {
     println!("Loading configuration from {}", conf);
 
     let conf_file = fs::File::open(conf).expect("Failed to read config file");
-    let config: Value= serde_json::from_reader(conf_file).expect("Failed to parse config file");
-    let service = Service::new(config);
-    let service_ref = service.clone();
-    let (sink, stream) = unbounded();
-    let (upnet_tx, upnet_rx) = unbounded();
-    let upnet_tx = Arc::new(Mutex::new(upnet_tx));
-    let (tx, rx) = mpsc::channel();
-    let sink = Arc::new(Mutex::new(sink));
-    let tx = Arc::new(Mutex::new(tx));
-    let listen_addr = service.addr;
-    println!("Client is connecting to {}", listen_addr);
-    let mut rt = tokio::runtime::Builder::new().build().unwrap();
-    let (up_tx, up_rx) = unbounded();
-    rt.spawn(up_rx.for_each(move |event: InputEvent<String>| {
-        println!("Sending event {:?}", event.clone());
-        let mut tx = upnet_tx.lock().unwrap();
-        match event {
-            InputEvent::KeyPressed {
-                scancode, ..
-            } => {
-                let actions = vec![GameAction { player: 0, code: scancode as u8, action: 0 }];
-                let _ = tx.start_send(Message::DataInput { battle: 0, player: 0, actions}).unwrap();
-            },
-            InputEvent::KeyReleased {
-                scancode, ..
-            } => {
-                let actions = vec![GameAction { player: 0, code: scancode as u8, action: 1 }];
-                let _ = tx.start_send(Message::DataInput { battle: 0, player: 0, actions}).unwrap();
-            },
-            _ => {},
-        }
-        Ok(()) 
-    }));
-
-    rt.spawn(stream.for_each(move |msg| {
-        match msg {
-            Message::DataFrame { actions, .. } => {
-                let mut events = Vec::new();
-                for action in actions {
-                    let _player = action.player;
-                    let scancode = action.code as u32;
-                    let key_code = match scancode {
-                        1 => VirtualKeyCode::S,
-                        13 => VirtualKeyCode::W,
-                        125 => VirtualKeyCode::Down,
-                        126 => VirtualKeyCode::Up,
-                        _ => VirtualKeyCode::A,
-                    };
-                    let event: InputEvent<String> = match action.action {
-                        0 => InputEvent::KeyPressed { key_code, scancode },
-                        1 => InputEvent::KeyReleased { key_code, scancode },
-                        _ => InputEvent::KeyReleased { key_code, scancode },
-                    };
-                    events.push(event);
-                }
-                let tx = tx.lock().unwrap();
-                tx.send(events).unwrap();
-            },
-            _ => {}
-        }
-        Ok(())
-    }));
-
-    rt.spawn(future::lazy(move || -> Result<(), ()> {
-        let client = Client::new(upnet_rx);
-        let client_clone = client.clone();
-
-        service_ref.connect(sink, client_clone);
-        Ok(())
-    }));
+    let network_config: Value =
+        serde_json::from_reader(conf_file).expect("Failed to parse config file");
+    let server = network_config["server"]
+        .as_str()
+        .expect("Missing lockstep server address in config file");
+    let lobby = network_config["battle"].as_u64().map(|lobby| lobby as u32);
+    let rejoin = network_config["rejoin"].as_u64().map(|player| player as u32);
+    let spectate = network_config["spectate"].as_bool().unwrap_or(false);
+    println!("Client is connecting to {}", server);
+    let transport = TcpTransport::connect(server).expect("Failed to connect to lockstep server");
+    let address = server.to_string();
 
     amethyst::start_logger(Default::default());
 
-    use pong::Pong;
-
     let display_config_path = format!(
         "{}/examples/pong/resources/display.ron",
         env!("CARGO_MANIFEST_DIR")
@@ -183,20 +112,42 @@ fn main() -> amethyst::Result<()> {
 
     let assets_dir = format!("{}/examples/assets/", env!("CARGO_MANIFEST_DIR"));
 
+    let mut network = LockstepBundle::<String, String, NetworkEvent>::new(transport)
+        .with_input_delay(2)
+        .with_adaptive_input_delay(6)
+        .with_jitter_buffer(2)
+        .with_state::<Transform>()
+        .with_state::<Ball>()
+        .with_state_resource::<ScoreBoard>()
+        .with_reconnect(move || TcpTransport::connect(address.as_str()));
+    let mut source = InputSource::Lockstep;
+    if spectate {
+        network = network.with_spectator();
+        source = InputSource::Spectator;
+    }
+
     let game_data = GameDataBuilder::default()
+        .with_bundle(network)?
         .with_bundle(
-            InputBundle::<String, String>::new().with_bindings_from_file(&key_bindings_path)?,
+            InputBundle::<String, String>::new()
+                .with_bindings_from_file(&key_bindings_path)?
+                .with_source(source),
         )?
-        .with_bundle(PongBundle)?
         .with_bundle(RenderBundle::new(pipe, Some(config)).with_sprite_sheet_processor())?
-        .with_bundle(TransformBundle::new().with_dep(&["ball_system", "paddle_system"]))?
+        .with_sim(TickInputSystem::<String, String>::new(), "tick_input_system", &[])
+        .with_sim_bundle(PongBundle)?
+        .with_sim_bundle(ChecksumBundle::new().with::<Transform>())?
+        .with_bundle(TransformBundle::new())?
         .with_bundle(AudioBundle::new(|music: &mut Music| music.music.next()))?
-        .with_bundle(UiBundle::<String, String>::new())?;
-    let rx = Some(Arc::new(Mutex::new(rx)));
-    let tx = Some(Arc::new(Mutex::new(up_tx)));
-    let mut game = Application::build(assets_dir, Pong)?
-        .with_resource(rx)
-        .with_resource(tx)
+        .with_bundle(UiBundle::<String, String>::new())?
+        .with_bundle(NetworkStatsBundle::new())?
+        .with_bundle(FPSCounterBundle::default())?;
+    let lobby = match rejoin {
+        _ if spectate => Lobby::spectate(lobby),
+        Some(player) => Lobby::rejoin(lobby, player),
+        None => Lobby::new(lobby),
+    };
+    let mut game = Application::build(assets_dir, lobby)?
         .with_frame_limit(
             FrameRateLimitStrategy::SleepAndYield(Duration::from_millis(20)),
             10,
@@ -206,6 +157,7 @@ fn main() -> amethyst::Result<()> {
     Ok(())
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Ball {
     pub velocity: [f32; 2],
     pub radius: f32,
@@ -243,7 +195,7 @@ impl Component for Paddle {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct ScoreBoard {
     score_left: i32,
     score_right: i32,
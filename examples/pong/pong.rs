@@ -2,19 +2,21 @@ use amethyst::assets::{AssetStorage, Loader};
 use amethyst::core::cgmath::Vector3;
 use amethyst::core::transform::{GlobalTransform, Transform};
 use amethyst::ecs::prelude::World;
+use amethyst::input::is_close_requested;
+use amethyst::network::NetworkEvent;
 use amethyst::prelude::*;
 use amethyst::renderer::{
     Camera, MaterialTextureSet, PngFormat, Projection, Sprite, SpriteRender, SpriteSheet,
     SpriteSheetHandle, Texture, TextureCoordinates, WindowMessages,
 };
-use amethyst::ui::{Anchor, TtfFormat, UiText, UiTransform};
+use amethyst::ui::{Anchor, TtfFormat, UiCreator, UiText, UiTransform};
 use systems::ScoreText;
 use {Ball, Paddle, Side};
 use {ARENA_HEIGHT, ARENA_WIDTH, SPRITESHEET_SIZE};
 
 pub struct Pong;
 
-impl<'a, 'b> SimpleState<'a, 'b> for Pong {
+impl<'a, 'b> State<GameData<'a, 'b>, NetworkEvent> for Pong {
     fn on_start(&mut self, data: StateData<GameData>) {
         let StateData { world, .. } = data;
         use audio::initialise_audio;
@@ -30,8 +32,29 @@ impl<'a, 'b> SimpleState<'a, 'b> for Pong {
         initialise_camera(world);
         initialise_audio(world);
         initialise_score(world);
+        initialise_network_stats(world);
         hide_cursor(world);
     }
+
+    fn handle_event(
+        &mut self,
+        _: StateData<GameData>,
+        event: StateEvent<NetworkEvent>,
+    ) -> Trans<GameData<'a, 'b>, NetworkEvent> {
+        match event {
+            StateEvent::Window(ref event) if is_close_requested(event) => Trans::Quit,
+            StateEvent::Custom(NetworkEvent::DesyncDetected { tick, .. }) => {
+                eprintln!("The game went out of sync with the other player on tick {}", tick);
+                Trans::Quit
+            }
+            _ => Trans::None,
+        }
+    }
+
+    fn update(&mut self, data: StateData<GameData>) -> Trans<GameData<'a, 'b>, NetworkEvent> {
+        data.data.update(&data.world);
+        Trans::None
+    }
 }
 
 fn load_sprite_sheet(world: &mut World) -> SpriteSheetHandle {
@@ -273,3 +296,10 @@ fn initialise_score(world: &mut World) {
         .build();
     world.add_resource(ScoreText { p1_score, p2_score });
 }
+
+/// Shows the network debug overlay in the top right corner.
+fn initialise_network_stats(world: &mut World) {
+    world.exec(|mut creator: UiCreator| {
+        creator.create("ui/network_stats.ron", ());
+    });
+}
@@ -1,4 +1,4 @@
-use amethyst::core::timing::Time;
+use amethyst::core::timing::SimulationTime;
 use amethyst::core::transform::Transform;
 use amethyst::ecs::prelude::{Join, Read, ReadStorage, System, WriteStorage};
 use Ball;
@@ -11,7 +11,7 @@ impl<'s> System<'s> for MoveBallsSystem {
     type SystemData = (
         ReadStorage<'s, Ball>,
         WriteStorage<'s, Transform>,
-        Read<'s, Time>,
+        Read<'s, SimulationTime>,
     );
 
     fn run(&mut self, (balls, mut locals, time): Self::SystemData) {
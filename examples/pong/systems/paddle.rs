@@ -1,22 +1,27 @@
-use amethyst::core::timing::Time;
+use amethyst::core::timing::SimulationTime;
 use amethyst::core::transform::Transform;
 use amethyst::ecs::prelude::{Join, Read, ReadStorage, System, WriteStorage};
-use amethyst::input::InputHandler;
+use amethyst::input::PlayerInputs;
+use amethyst::network::Session;
 use Paddle;
 
 /// This system is responsible for moving all the paddles according to the user
 /// provided input.
+///
+/// The left paddle is controlled by the player in the first slot of the lobby, the right paddle
+/// by the player in the second one.
 pub struct PaddleSystem;
 
 impl<'s> System<'s> for PaddleSystem {
     type SystemData = (
         ReadStorage<'s, Paddle>,
         WriteStorage<'s, Transform>,
-        Read<'s, Time>,
-        Read<'s, InputHandler<String, String>>,
+        Read<'s, SimulationTime>,
+        Read<'s, PlayerInputs<String, String>>,
+        Read<'s, Session>,
     );
 
-    fn run(&mut self, (paddles, mut transforms, time, input): Self::SystemData) {
+    fn run(&mut self, (paddles, mut transforms, time, input, session): Self::SystemData) {
         use Side;
 
         // Iterate over all planks and move them according to the input the user
@@ -25,8 +30,8 @@ impl<'s> System<'s> for PaddleSystem {
         println!("Paddle system Tick with time delta: {}", time.delta_seconds());
         for (paddle, transform) in (&paddles, &mut transforms).join() {
             let opt_movement = match paddle.side {
-                Side::Left => input.axis_value("left_paddle"),
-                Side::Right => input.axis_value("right_paddle"),
+                Side::Left => input.axis_value(player(&session, 0), "left_paddle"),
+                Side::Right => input.axis_value(player(&session, 1), "right_paddle"),
             };
 
             if let Some(movement) = opt_movement {
@@ -43,3 +48,8 @@ impl<'s> System<'s> for PaddleSystem {
         }
     }
 }
+
+/// Returns the player in the given slot of the lobby.
+fn player(session: &Session, slot: u32) -> u32 {
+    session.player_in_slot(slot).unwrap_or(slot)
+}
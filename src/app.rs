@@ -2,21 +2,27 @@
 
 use amethyst_ui::UiEvent;
 use assets::{Loader, Source};
+use config::Config;
 use core::frame_limiter::{FrameLimiter, FrameRateLimitConfig, FrameRateLimitStrategy};
 use core::shrev::{EventChannel, ReaderId};
-use core::timing::{Stopwatch, Time};
+use core::timing::{SimulationTime, Stopwatch, Time};
+use core::SystemError;
+use ctrlc;
 use ecs::common::Errors;
 use ecs::prelude::{Component, World};
 use error::{Error, Result};
-use game_data::DataInit;
+use game_data::{DataDispose, DataInit, DataSimulate};
 use log::Level;
 use rayon::ThreadPoolBuilder;
+use renderer::{DisplayConfig, ScreenDimensions};
 use shred::Resource;
-use state::{State, StateData, StateMachine};
+use state::{State, StateData, StateMachine, Trans, TransQueue};
 use state_event::StateEvent;
 use std::error::Error as StdError;
 use std::marker::PhantomData;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 #[cfg(feature = "profiler")]
@@ -99,14 +105,43 @@ pub struct Application<'a, T, E: Send + Sync + 'static> {
     /// The world
     #[derivative(Debug = "ignore")]
     world: World,
-    window_reader_id: ReaderId<Event>,
-    ui_reader_id: ReaderId<UiEvent>,
+    window_reader_id: Option<ReaderId<Event>>,
+    ui_reader_id: Option<ReaderId<UiEvent>>,
     custom_reader_id: ReaderId<E>,
     states: StateMachine<'a, T, E>,
     ignore_window_close: bool,
+    error_policy: ErrorPolicy,
+    #[derivative(Debug = "ignore")]
+    shutdown_hooks: Vec<ShutdownHook>,
+    signaled: Option<Arc<AtomicBool>>,
     data: T,
 }
 
+/// Function run by the `Application` when it shuts down, after the game data was disposed.
+pub type ShutdownHook = Box<FnMut(&mut World)>;
+
+/// What the `Application` does with the errors systems report through the `Errors` resource.
+///
+/// The errors are collected at the end of every frame, as `SystemError`s carrying the name of
+/// the system when it reported one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Logs the errors and keeps running.
+    Log,
+    /// Delivers every error to the active `State` as a `StateEvent::Error`, which decides how to
+    /// recover from it.
+    Escalate,
+    /// Logs the errors, stops the states and shuts the application down like on a quit, then
+    /// exits the process. This is the default.
+    Abort,
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> Self {
+        ErrorPolicy::Abort
+    }
+}
+
 impl<'a, T, E: Send + Sync + Clone + 'static> Application<'a, T, E> {
     /// Creates a new Application with the given initial game state.
     /// This will create and allocate all the needed resources for
@@ -182,14 +217,26 @@ impl<'a, T, E: Send + Sync + Clone + 'static> Application<'a, T, E> {
     /// `Trans::Pop` on the last state in from the stack. See full
     /// documentation on this in [State](trait.State.html) documentation.
     ///
+    /// The transitions queued by systems in the `TransQueue` are applied after the states are
+    /// updated. Then the simulation stage of the game data, followed by the one of the active
+    /// state's game data, is run once for every tick of the `SimulationTime` which is due.
+    ///
     /// # Examples
     ///
     /// See the example supplied in the
     /// [`new`](struct.Application.html#examples) method.
-    pub fn run(&mut self) {
+    pub fn run(&mut self)
+    where
+        T: DataSimulate + DataDispose + 'static,
+    {
         self.initialize();
         self.world.write_resource::<Stopwatch>().start();
         while self.states.is_running() {
+            if self.signaled() {
+                info!("Termination signal received, stopping the states");
+                self.states.stop(StateData::new(&mut self.world, &mut self.data));
+                break;
+            }
             self.advance_frame();
             println!("Game frame Tick!");
             self.world.write_resource::<FrameLimiter>().wait();
@@ -216,7 +263,10 @@ impl<'a, T, E: Send + Sync + Clone + 'static> Application<'a, T, E> {
     }
 
     /// Advances the game world by one tick.
-    fn advance_frame(&mut self) {
+    fn advance_frame(&mut self)
+    where
+        T: DataSimulate + DataDispose + 'static,
+    {
         trace!("Advancing frame (`Application::advance_frame`)");
 
         {
@@ -225,12 +275,15 @@ impl<'a, T, E: Send + Sync + Clone + 'static> Application<'a, T, E> {
             #[cfg(feature = "profiler")]
             profile_scope!("handle_event");
 
-            let window_events = world
-                .read_resource::<EventChannel<Event>>()
-                .read(&mut self.window_reader_id)
-                .cloned()
-                .map(|e| StateEvent::Window(e))
-                .collect::<Vec<_>>();
+            let window_events = match self.window_reader_id {
+                Some(ref mut reader_id) => world
+                    .read_resource::<EventChannel<Event>>()
+                    .read(reader_id)
+                    .cloned()
+                    .map(|e| StateEvent::Window(e))
+                    .collect::<Vec<_>>(),
+                None => Vec::new(),
+            };
 
             for event in window_events {
                 if !self.ignore_window_close {
@@ -255,12 +308,15 @@ impl<'a, T, E: Send + Sync + Clone + 'static> Application<'a, T, E> {
                 states.handle_event(StateData::new(world, &mut self.data), event);
             }
 
-            let ui_events = world
-                .read_resource::<EventChannel<UiEvent>>()
-                .read(&mut self.ui_reader_id)
-                .cloned()
-                .map(|e| StateEvent::Ui(e))
-                .collect::<Vec<_>>();
+            let ui_events = match self.ui_reader_id {
+                Some(ref mut reader_id) => world
+                    .read_resource::<EventChannel<UiEvent>>()
+                    .read(reader_id)
+                    .cloned()
+                    .map(|e| StateEvent::Ui(e))
+                    .collect::<Vec<_>>(),
+                None => Vec::new(),
+            };
             for event in ui_events {
                 states.handle_event(StateData::new(world, &mut self.data), event);
             }
@@ -292,23 +348,92 @@ impl<'a, T, E: Send + Sync + Clone + 'static> Application<'a, T, E> {
             profile_scope!("update");
             self.states
                 .update(StateData::new(&mut self.world, &mut self.data));
+
+            let requests = self
+                .world
+                .res
+                .entry::<TransQueue<T, E>>()
+                .or_insert_with(TransQueue::new)
+                .take();
+            if !requests.is_empty() {
+                self.states.transition(
+                    Trans::Sequence(requests),
+                    StateData::new(&mut self.world, &mut self.data),
+                );
+            }
+        }
+        {
+            #[cfg(feature = "profiler")]
+            profile_scope!("simulate");
+            let delta = self.world.read_resource::<Time>().delta_real_time();
+            self.world.write_resource::<SimulationTime>().accumulate(delta);
+            while self.world.write_resource::<SimulationTime>().next_tick() {
+                self.data.simulate(&self.world);
+                self.states.simulate(&self.world);
+            }
         }
 
         #[cfg(feature = "profiler")]
         profile_scope!("maintain");
         self.world.maintain();
 
-        // TODO: replace this with a more customizable method.
-        // TODO: effectively, the user should have more control over error handling here
-        // TODO: because right now the app will just exit in case of an error.
-        self.world.write_resource::<Errors>().print_and_exit();
+        self.handle_errors();
+    }
+
+    /// Applies the `ErrorPolicy` to the errors reported by systems during the frame.
+    fn handle_errors(&mut self)
+    where
+        T: DataDispose,
+    {
+        let errors = self.world.write_resource::<Errors>().collect();
+        if errors.is_empty() {
+            return;
+        }
+        for error in errors.into_iter().map(SystemError::from) {
+            match self.error_policy {
+                ErrorPolicy::Escalate => {
+                    let event = StateEvent::Error(error);
+                    self.states
+                        .handle_event(StateData::new(&mut self.world, &mut self.data), event);
+                }
+                ErrorPolicy::Log | ErrorPolicy::Abort => error!("{}", error),
+            }
+        }
+        if self.error_policy == ErrorPolicy::Abort {
+            error!("Exiting because of system errors");
+            self.states.stop(StateData::new(&mut self.world, &mut self.data));
+            self.shutdown();
+            process::exit(1);
+        }
+    }
+
+    /// Returns true if a termination signal was received since `handle_signals` was called.
+    fn signaled(&self) -> bool {
+        self.signaled
+            .as_ref()
+            .map_or(false, |signaled| signaled.load(Ordering::SeqCst))
     }
 
     /// Cleans up after the quit signal is received.
-    fn shutdown(&mut self) {
+    ///
+    /// Runs the teardowns of the bundles, waits for the asset imports in flight, then runs the
+    /// shutdown hooks.
+    fn shutdown(&mut self)
+    where
+        T: DataDispose,
+    {
         info!("Engine is shutting down");
 
-        // Placeholder.
+        self.data.dispose(&mut self.world);
+        // Waits so no worker thread is cut off while reading from a source. The imported data is
+        // discarded, since the `Processor`s which would turn it into assets don't run anymore.
+        let timeout = Duration::from_secs(5);
+        if !self.world.read_resource::<Loader>().flush(timeout) {
+            warn!("Asset imports still running after {:?}, exiting anyway", timeout);
+        }
+        for hook in &mut self.shutdown_hooks {
+            hook(&mut self.world);
+        }
     }
 }
 
@@ -332,6 +457,10 @@ pub struct ApplicationBuilder<S, E> {
     /// Used by bundles to access the world directly
     pub world: World,
     ignore_window_close: bool,
+    headless: bool,
+    error_policy: ErrorPolicy,
+    shutdown_hooks: Vec<ShutdownHook>,
+    signaled: Option<Arc<AtomicBool>>,
     phantom: PhantomData<E>,
 }
 
@@ -439,11 +568,16 @@ impl<S, E: Send + Sync + 'static> ApplicationBuilder<S, E> {
         world.add_resource(FrameLimiter::default());
         world.add_resource(Stopwatch::default());
         world.add_resource(Time::default());
+        world.add_resource(SimulationTime::default());
 
         Ok(ApplicationBuilder {
             initial_state,
             world,
             ignore_window_close: false,
+            headless: false,
+            error_policy: ErrorPolicy::default(),
+            shutdown_hooks: Vec::new(),
+            signaled: None,
             phantom: PhantomData,
         })
     }
@@ -680,6 +814,143 @@ impl<S, E: Send + Sync + 'static> ApplicationBuilder<S, E> {
         self
     }
 
+    /// Runs the application without a window, for example for a dedicated server or for
+    /// integration tests on machines without a display.
+    ///
+    /// States, game data, `Time`, `SimulationTime` and asset loading work as usual, but window
+    /// and UI events are not passed to the states. Don't add the bundles which need a window or
+    /// an audio output to the game data, like the `RenderBundle`, `UiBundle` and `AudioBundle`.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Examples
+    ///
+    /// ~~~no_run
+    /// use amethyst::prelude::*;
+    ///
+    /// struct ServerState;
+    /// impl EmptyState for ServerState {}
+    ///
+    /// let mut game = Application::build("assets/", ServerState)
+    ///     .expect("Failed to initialize")
+    ///     .headless()
+    ///     .build(())
+    ///     .expect("Failed to create Application");
+    /// game.run();
+    /// ~~~
+    pub fn headless(mut self) -> Self {
+        self.headless = true;
+        self
+    }
+
+    /// Sets what the application does with the errors systems report through the `Errors`
+    /// resource. By default, it logs them and exits.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Examples
+    ///
+    /// ~~~no_run
+    /// use amethyst::prelude::*;
+    /// use amethyst::ErrorPolicy;
+    ///
+    /// struct Menu;
+    ///
+    /// impl<'a, 'b> SimpleState<'a, 'b> for Menu {
+    ///     fn handle_event(
+    ///         &mut self,
+    ///         _: StateData<GameData>,
+    ///         event: StateEvent<()>,
+    ///     ) -> SimpleTrans<'a, 'b> {
+    ///         if let StateEvent::Error(error) = event {
+    ///             eprintln!("Going back to the previous menu: {}", error);
+    ///             return Trans::Pop;
+    ///         }
+    ///         Trans::None
+    ///     }
+    /// }
+    ///
+    /// let mut game = Application::build("assets/", Menu)
+    ///     .expect("Failed to initialize")
+    ///     .with_error_policy(ErrorPolicy::Escalate)
+    ///     .build(GameDataBuilder::default())
+    ///     .expect("Failed to create Application");
+    /// game.run();
+    /// ~~~
+    pub fn with_error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Adds a function to run when the application shuts down.
+    ///
+    /// Shutdown hooks run in the order they were added, once the states stopped, the teardowns
+    /// of the bundles ran and the asset imports in flight finished.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_shutdown_hook<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&mut World) + 'static,
+    {
+        self.shutdown_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Saves the size of the window to the `DisplayConfig` file at `path` when the application
+    /// shuts down, so the next run opens a window of the same size.
+    ///
+    /// The other settings of the file are kept. Nothing is written without a window.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn persist_display_config<P>(self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        self.with_shutdown_hook(move |world| {
+            let dimensions = match world.res.try_fetch::<ScreenDimensions>() {
+                Some(screen) => (screen.width() as u32, screen.height() as u32),
+                None => return,
+            };
+            let mut config = DisplayConfig::load(&path);
+            config.dimensions = Some(dimensions);
+            if let Err(err) = config.write(&path) {
+                error!("Failed to save the display config to {:?}: {}", path, err);
+            }
+        })
+    }
+
+    /// Stops the application gracefully when the process receives SIGINT or SIGTERM, or Ctrl-C
+    /// on Windows.
+    ///
+    /// On the next frame, every state is stopped and the application shuts down as if the last
+    /// state was popped, running the teardowns and shutdown hooks. A dedicated server can close
+    /// its network sessions in `State::on_stop`.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it, wrapped in a `Result`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a signal handler was already installed in this process.
+    pub fn handle_signals(mut self) -> Result<Self> {
+        let signaled = Arc::new(AtomicBool::new(false));
+        let handler_signaled = signaled.clone();
+        ctrlc::set_handler(move || handler_signaled.store(true, Ordering::SeqCst))
+            .map_err(|err| Error::Core(err.to_string().into()))?;
+        self.signaled = Some(signaled);
+        Ok(self)
+    }
+
     /// Build an `Application` object using the `ApplicationBuilder` as configured.
     ///
     /// # Returns
@@ -712,14 +983,19 @@ impl<S, E: Send + Sync + 'static> ApplicationBuilder<S, E> {
         #[cfg(feature = "profiler")]
         profile_scope!("new");
 
-        let window_reader_id = self
-            .world
-            .write_resource::<EventChannel<Event>>()
-            .register_reader();
-        let ui_reader_id = self
-            .world
-            .write_resource::<EventChannel<UiEvent>>()
-            .register_reader();
+        let (window_reader_id, ui_reader_id) = if self.headless {
+            (None, None)
+        } else {
+            let window_reader_id = self
+                .world
+                .write_resource::<EventChannel<Event>>()
+                .register_reader();
+            let ui_reader_id = self
+                .world
+                .write_resource::<EventChannel<UiEvent>>()
+                .register_reader();
+            (Some(window_reader_id), Some(ui_reader_id))
+        };
         let custom_reader_id = self
             .world
             .write_resource::<EventChannel<E>>()
@@ -734,7 +1010,176 @@ impl<S, E: Send + Sync + 'static> ApplicationBuilder<S, E> {
             ui_reader_id,
             custom_reader_id,
             ignore_window_close: self.ignore_window_close,
+            error_policy: self.error_policy,
+            shutdown_hooks: self.shutdown_hooks,
+            signaled: self.signaled,
             data,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use assets::{
+        Asset, AssetStorage, Directory, Handle, Loader, ProcessingState, Processor, Result,
+        SimpleFormat,
+    };
+    use core::timing::Time;
+    use ecs::prelude::{System, VecStorage, Write};
+    use prelude::*;
+
+    #[derive(Default)]
+    struct Frames(u32);
+
+    struct CountFrames;
+
+    impl<'a> System<'a> for CountFrames {
+        type SystemData = Write<'a, Frames>;
+
+        fn run(&mut self, mut frames: Self::SystemData) {
+            frames.0 += 1;
+        }
+    }
+
+    struct QuitAfter(u32);
+
+    impl<'a, 'b> SimpleState<'a, 'b> for QuitAfter {
+        fn update(&mut self, data: &mut StateData<GameData>) -> SimpleTrans<'a, 'b> {
+            if data.world.read_resource::<Frames>().0 >= self.0 {
+                Trans::Quit
+            } else {
+                Trans::None
+            }
+        }
+    }
+
+    #[test]
+    fn headless_application_runs_its_states() {
+        let game_data = GameDataBuilder::default().with(CountFrames, "count_frames", &[]);
+        let mut game = Application::build("assets/", QuitAfter(3))
+            .unwrap()
+            .headless()
+            .build(game_data)
+            .unwrap();
+        game.run();
+
+        // The state quits in the fourth frame, which still runs to its end.
+        assert_eq!(game.world.read_resource::<Frames>().0, 4);
+        assert_eq!(game.world.read_resource::<Time>().frame_number(), 4);
+    }
+
+    /// Asks for the `Pushed` state through the `TransQueue`, on its first run.
+    struct PushFromSystem(bool);
+
+    impl<'a> System<'a> for PushFromSystem {
+        type SystemData = Write<'a, TransQueue<GameData<'static, 'static>, ()>>;
+
+        fn run(&mut self, mut queue: Self::SystemData) {
+            if !self.0 {
+                self.0 = true;
+                queue.push(|| Trans::Push(Box::new(Pushed)));
+            }
+        }
+    }
+
+    struct Idle;
+
+    impl<'a, 'b> SimpleState<'a, 'b> for Idle {
+        fn update(&mut self, data: &mut StateData<GameData>) -> SimpleTrans<'a, 'b> {
+            let frame = data.world.read_resource::<Time>().frame_number();
+            assert!(frame < 100, "Transition of the TransQueue was not applied");
+            Trans::None
+        }
+    }
+
+    struct Pushed;
+
+    struct PushedStarted;
+
+    impl<'a, 'b> SimpleState<'a, 'b> for Pushed {
+        fn on_start(&mut self, data: StateData<GameData>) {
+            data.world.add_resource(PushedStarted);
+        }
+
+        fn update(&mut self, _: &mut StateData<GameData>) -> SimpleTrans<'a, 'b> {
+            Trans::Quit
+        }
+    }
+
+    #[test]
+    fn applies_transitions_queued_by_systems() {
+        let game_data =
+            GameDataBuilder::default().with(PushFromSystem(false), "push_from_system", &[]);
+        let mut game = Application::build("assets/", Idle)
+            .unwrap()
+            .headless()
+            .build(game_data)
+            .unwrap();
+        game.run();
+        assert!(game.world.res.has_value::<PushedStarted>());
+    }
+
+    #[derive(Debug)]
+    struct Text(String);
+
+    impl Asset for Text {
+        const NAME: &'static str = "test::Text";
+        type Data = Self;
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    impl Into<Result<ProcessingState<Text>>> for Text {
+        fn into(self) -> Result<ProcessingState<Text>> {
+            Ok(ProcessingState::Loaded(self))
+        }
+    }
+
+    #[derive(Clone)]
+    struct TextFormat;
+
+    impl SimpleFormat<Text> for TextFormat {
+        const NAME: &'static str = "TEXT";
+
+        type Options = ();
+
+        fn import(&self, bytes: Vec<u8>, _: ()) -> Result<Text> {
+            Ok(Text(String::from_utf8(bytes)?))
+        }
+    }
+
+    struct LoadManifest(Option<Handle<Text>>);
+
+    impl<'a, 'b> SimpleState<'a, 'b> for LoadManifest {
+        fn on_start(&mut self, data: StateData<GameData>) {
+            let loader = data.world.read_resource::<Loader>();
+            let storage = data.world.read_resource::<AssetStorage<Text>>();
+            let handle = loader.load_from("Cargo.toml", TextFormat, (), "crate", (), &storage);
+            self.0 = Some(handle);
+        }
+
+        fn update(&mut self, data: &mut StateData<GameData>) -> SimpleTrans<'a, 'b> {
+            let frame = data.world.read_resource::<Time>().frame_number();
+            assert!(frame < 1_000, "Asset not loaded after {} frames", frame);
+            let storage = data.world.read_resource::<AssetStorage<Text>>();
+            match storage.get(self.0.as_ref().unwrap()) {
+                Some(text) => {
+                    assert!(text.0.contains("name = \"amethyst\""));
+                    Trans::Quit
+                }
+                None => Trans::None,
+            }
+        }
+    }
+
+    #[test]
+    fn headless_application_loads_assets() {
+        let game_data = GameDataBuilder::default().with(Processor::<Text>::new(), "text", &[]);
+        let mut game = Application::build("assets/", LoadManifest(None))
+            .unwrap()
+            .with_source("crate", Directory::new(env!("CARGO_MANIFEST_DIR")))
+            .headless()
+            .build(game_data)
+            .unwrap();
+        game.run();
+    }
+}
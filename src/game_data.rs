@@ -1,5 +1,7 @@
-use core::specs::prelude::{Dispatcher, DispatcherBuilder, System, World};
-use core::{SystemBundle, ThreadPool};
+use core::snapshot::{Rollback, SnapshotRegistry};
+use core::specs::prelude::{Component, Dispatcher, DispatcherBuilder, System, World};
+use core::timing::SimulationTime;
+use core::{SystemBundle, Teardown, ThreadPool};
 use error::{Error, Result};
 use renderer::pipe::pass::Pass;
 use std::path::Path;
@@ -10,15 +12,86 @@ pub trait DataInit<T> {
     fn build(self, world: &mut World) -> T;
 }
 
+/// Per-frame stage of game data, run by the state machine for the game data of the active
+/// state.
+pub trait DataUpdate {
+    /// Run the systems of a single frame.
+    ///
+    /// Game data without systems doesn't need to do anything.
+    fn update(&mut self, _world: &World) {}
+}
+
+/// Simulation stage of game data, run by the `Application` on every tick of the
+/// `SimulationTime`.
+pub trait DataSimulate {
+    /// Run a single tick of the simulation.
+    ///
+    /// Game data without a simulation stage doesn't need to do anything.
+    fn simulate(&mut self, _world: &World) {}
+}
+
+/// Cleanup of game data, run by the `Application` when it shuts down.
+pub trait DataDispose {
+    /// Runs the teardowns of the bundles the game data was built with.
+    ///
+    /// Game data without teardowns doesn't need to do anything.
+    fn dispose(&mut self, _world: &mut World) {}
+}
+
+/// Game data a state runs while it is the active one, built with `State::dispatcher`.
+///
+/// Implemented for every game data with a per-frame stage, a simulation stage and a cleanup.
+pub trait StateGameData: DataUpdate + DataSimulate + DataDispose {}
+
+impl<T> StateGameData for T
+where
+    T: DataUpdate + DataSimulate + DataDispose,
+{
+}
+
 /// Default game data
+///
+/// Holds the dispatcher run by the states on every frame, and optionally a simulation dispatcher
+/// run by the `Application` on every simulation tick.
+///
+/// With a `Rollback`, the state of the world is saved after every tick, and restored when a tick
+/// is simulated again after a rewind of the `SimulationTime`.
+///
+/// The teardowns of the bundles are kept until the game data is disposed.
 pub struct GameData<'a, 'b> {
     dispatcher: Dispatcher<'a, 'b>,
+    sim_dispatcher: Option<Dispatcher<'a, 'b>>,
+    rollback: Option<Rollback>,
+    teardowns: Vec<Teardown<'b>>,
 }
 
 impl<'a, 'b> GameData<'a, 'b> {
     /// Create new game data
     pub fn new(dispatcher: Dispatcher<'a, 'b>) -> Self {
-        GameData { dispatcher }
+        GameData {
+            dispatcher,
+            sim_dispatcher: None,
+            rollback: None,
+            teardowns: Vec::new(),
+        }
+    }
+
+    /// Use the given dispatcher for the simulation stage
+    pub fn with_sim(mut self, sim_dispatcher: Dispatcher<'a, 'b>) -> Self {
+        self.sim_dispatcher = Some(sim_dispatcher);
+        self
+    }
+
+    /// Save snapshots of the simulation with the given rollback history
+    pub fn with_rollback(mut self, rollback: Rollback) -> Self {
+        self.rollback = Some(rollback);
+        self
+    }
+
+    /// Run the given teardowns, in reverse order, when the game data is disposed
+    pub fn with_teardowns(mut self, teardowns: Vec<Teardown<'b>>) -> Self {
+        self.teardowns.extend(teardowns);
+        self
     }
 
     /// Update game data
@@ -27,9 +100,41 @@ impl<'a, 'b> GameData<'a, 'b> {
     }
 }
 
+impl<'a, 'b> DataUpdate for GameData<'a, 'b> {
+    fn update(&mut self, world: &World) {
+        GameData::update(self, world);
+    }
+}
+
+impl<'a, 'b> DataSimulate for GameData<'a, 'b> {
+    fn simulate(&mut self, world: &World) {
+        if let Some(ref mut sim_dispatcher) = self.sim_dispatcher {
+            let tick = world.read_resource::<SimulationTime>().tick();
+            if let Some(ref mut rollback) = self.rollback {
+                rollback.before_tick(tick, &world.res);
+            }
+            sim_dispatcher.dispatch(&world.res);
+            if let Some(ref mut rollback) = self.rollback {
+                rollback.after_tick(tick, &world.res);
+            }
+        }
+    }
+}
+
+impl<'a, 'b> DataDispose for GameData<'a, 'b> {
+    fn dispose(&mut self, world: &mut World) {
+        while let Some(mut teardown) = self.teardowns.pop() {
+            teardown(world);
+        }
+    }
+}
+
 /// Builder for default game data
 pub struct GameDataBuilder<'a, 'b> {
     disp_builder: DispatcherBuilder<'a, 'b>,
+    sim_builder: Option<DispatcherBuilder<'a, 'b>>,
+    snapshots: SnapshotRegistry,
+    teardowns: Vec<Teardown<'b>>,
 }
 
 impl<'a, 'b> Default for GameDataBuilder<'a, 'b> {
@@ -43,6 +148,9 @@ impl<'a, 'b> GameDataBuilder<'a, 'b> {
     pub fn new() -> Self {
         GameDataBuilder {
             disp_builder: DispatcherBuilder::new(),
+            sim_builder: None,
+            snapshots: SnapshotRegistry::new(),
+            teardowns: Vec::new(),
         }
     }
 
@@ -190,7 +298,8 @@ impl<'a, 'b> GameDataBuilder<'a, 'b> {
 
     /// Add a given ECS bundle to the game loop.
     ///
-    /// A bundle is a container for registering a bunch of ECS systems at once.
+    /// A bundle is a container for registering a bunch of ECS systems at once. The teardown of
+    /// the bundle, if any, runs when the application shuts down.
     ///
     /// # Parameters
     ///
@@ -207,16 +316,136 @@ impl<'a, 'b> GameDataBuilder<'a, 'b> {
     /// could result in any number of errors.
     /// See each individual bundle for a description of the errors it could produce.
     ///
-    pub fn with_bundle<B>(mut self, bundle: B) -> Result<Self>
+    pub fn with_bundle<B>(mut self, mut bundle: B) -> Result<Self>
     where
         B: SystemBundle<'a, 'b>,
     {
+        let teardown = bundle.teardown();
         bundle
             .build(&mut self.disp_builder)
             .map_err(|err| Error::Core(err))?;
+        self.teardowns.extend(teardown);
+        Ok(self)
+    }
+
+    /// Adds a given system to the simulation stage.
+    ///
+    /// Simulation systems are not run by `GameData::update`, but by the `Application` once per
+    /// tick of the `SimulationTime`. Use them for the gameplay logic which must advance at a fixed
+    /// rate, or only on the frames confirmed by a lockstep server.
+    ///
+    /// __Note:__ all dependencies must be simulation systems added before this one.
+    ///
+    /// # Parameters
+    ///
+    /// - `system`: The system that is to be added to the simulation.
+    /// - `name`: A unique string to identify the system by, among the simulation systems.
+    /// - `dependencies`: A list of named simulation systems that _must_ have completed running
+    ///                 before this system is permitted to run.
+    ///
+    /// # Returns
+    ///
+    /// This function returns GameDataBuilder after it has modified it.
+    ///
+    /// # Panics
+    ///
+    /// Same as `with`.
+    ///
+    /// # Examples
+    ///
+    /// ~~~no_run
+    /// use amethyst::prelude::*;
+    /// use amethyst::ecs::prelude::System;
+    ///
+    /// struct NopSystem;
+    /// impl<'a> System<'a> for NopSystem {
+    ///     type SystemData = ();
+    ///     fn run(&mut self, _: Self::SystemData) {}
+    /// }
+    ///
+    /// GameDataBuilder::default()
+    ///     // This system runs every frame.
+    ///     .with(NopSystem, "animation", &[])
+    ///     // This one only runs on simulation ticks.
+    ///     .with_sim(NopSystem, "movement", &[]);
+    /// ~~~
+    pub fn with_sim<S>(mut self, system: S, name: &str, dependencies: &[&str]) -> Self
+    where
+        for<'c> S: System<'c> + Send + 'a,
+    {
+        self.sim_builder
+            .get_or_insert_with(DispatcherBuilder::new)
+            .add(system, name, dependencies);
+        self
+    }
+
+    /// Add a given ECS bundle to the simulation stage.
+    ///
+    /// # Parameters
+    ///
+    /// - `bundle`: The bundle to add
+    ///
+    /// # Returns
+    ///
+    /// This function returns GameDataBuilder after it has modified it, this is
+    /// wrapped in a `Result`.
+    ///
+    /// # Errors
+    ///
+    /// See each individual bundle for a description of the errors it could produce.
+    ///
+    pub fn with_sim_bundle<B>(mut self, mut bundle: B) -> Result<Self>
+    where
+        B: SystemBundle<'a, 'b>,
+    {
+        let teardown = bundle.teardown();
+        bundle
+            .build(self.sim_builder.get_or_insert_with(DispatcherBuilder::new))
+            .map_err(|err| Error::Core(err))?;
+        self.teardowns.extend(teardown);
         Ok(self)
     }
 
+    /// Saves a component after every simulation tick, to roll the simulation back.
+    ///
+    /// Once a component or a resource is registered, the world is restored to the state saved
+    /// after the previous tick whenever a tick is simulated again, after the `SimulationTime` was
+    /// rewound by the network layer. Register everything the simulation systems write to.
+    ///
+    /// # Returns
+    ///
+    /// This function returns GameDataBuilder after it has modified it.
+    ///
+    /// # Type Parameters
+    ///
+    /// - `C`: The component to save.
+    pub fn with_snapshot<C>(mut self) -> Self
+    where
+        C: Component + Clone + Send + Sync,
+    {
+        self.snapshots = self.snapshots.with::<C>();
+        self
+    }
+
+    /// Saves a resource after every simulation tick, to roll the simulation back.
+    ///
+    /// See `with_snapshot`.
+    ///
+    /// # Returns
+    ///
+    /// This function returns GameDataBuilder after it has modified it.
+    ///
+    /// # Type Parameters
+    ///
+    /// - `R`: The resource to save.
+    pub fn with_snapshot_resource<R>(mut self) -> Self
+    where
+        R: Clone + Default + Send + Sync + 'static,
+    {
+        self.snapshots = self.snapshots.with_resource::<R>();
+        self
+    }
+
     /// Create a basic renderer with a single given `Pass`, and optional support for the `DrawUi` pass.
     ///
     /// Will set the clear color to black.
@@ -260,11 +489,29 @@ impl<'a, 'b> DataInit<GameData<'a, 'b>> for GameDataBuilder<'a, 'b> {
         let pool = world.read_resource::<ThreadPool>().clone();
 
         #[cfg(not(no_threading))]
-        let mut dispatcher = self.disp_builder.with_pool(pool).build();
+        let mut dispatcher = self.disp_builder.with_pool(pool.clone()).build();
         #[cfg(no_threading)]
         let mut dispatcher = self.disp_builder.build();
         dispatcher.setup(&mut world.res);
-        GameData::new(dispatcher)
+        let mut game_data = GameData::new(dispatcher).with_teardowns(self.teardowns);
+
+        if !self.snapshots.is_empty() {
+            let rollback = Rollback::new(self.snapshots);
+            rollback.setup(&mut world.res);
+            game_data = game_data.with_rollback(rollback);
+        }
+
+        match self.sim_builder {
+            Some(sim_builder) => {
+                #[cfg(not(no_threading))]
+                let mut sim_dispatcher = sim_builder.with_pool(pool).build();
+                #[cfg(no_threading)]
+                let mut sim_dispatcher = sim_builder.build();
+                sim_dispatcher.setup(&mut world.res);
+                game_data.with_sim(sim_dispatcher)
+            }
+            None => game_data,
+        }
     }
 }
 
@@ -273,3 +520,9 @@ impl DataInit<()> for () {
         ()
     }
 }
+
+impl DataUpdate for () {}
+
+impl DataSimulate for () {}
+
+impl DataDispose for () {}
@@ -0,0 +1,539 @@
+//! Lockstep clients and their server simulated within a single process, to test netcode.
+//!
+//! Available with the `harness` feature.
+
+use core::timing::{SimulationTime, Time};
+use core::ThreadPool;
+use ecs::common::Errors;
+use ecs::prelude::{Component, Entities, Join, ReadStorage, SystemData, World};
+use error::{Error, Result};
+use game_data::{DataInit, DataSimulate, GameData, GameDataBuilder};
+use input::{InputRelay, NetInputEvent};
+use network::{
+    self, LinkClock, LinkConditions, LockstepServer, LoopbackConnector, LoopbackListener,
+    LoopbackTransport, SimulatedListener, SimulatedTransport,
+};
+use rayon::ThreadPoolBuilder;
+use shred::Resource;
+use std::error::Error as StdError;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Seed of the transports the server accepts, far from the seeds of the clients.
+const SERVER_SEED: u64 = 1 << 32;
+
+/// Transport connecting a client of a `LockstepHarness` to its server.
+pub type HarnessTransport = SimulatedTransport<LoopbackTransport>;
+
+/// Runs a `LockstepServer` and several lockstep clients, each with its own `World` and
+/// `GameData`, within one process.
+///
+/// The clients talk to the server through loopback transports applying the same
+/// `LinkConditions` in both directions. Every `step` ticks the server once, and runs one frame
+/// of every client the way the `Application` does: the frame dispatcher, then the simulation
+/// stage for every tick which is due, so a client which fell behind catches up within a step.
+/// The `LinkClock` of the transports advances once per step,
+/// so delays are counted in steps. States are not run, so the entities of the game are created
+/// through `world_mut` once the client was added.
+///
+/// Input is scripted per client, and sent ahead through the `InputRelay` for the frame of the
+/// tick it was scripted for. The game data of every client needs a `LockstepBundle` talking
+/// through the transport handed to `add_client`, an `InputBundle` with `InputSource::Lockstep`
+/// and a `TickInputSystem` in the simulation stage.
+///
+/// Clients can be added while the others are playing, to join the match in progress.
+///
+/// The lockstep systems take the time between frames from the `Time` the harness sets, and the
+/// simulated links draw their delays from fixed seeds, so a run always goes the same way for
+/// the same clients, script and link conditions.
+///
+/// ## Type parameters
+///
+/// AX: The type used to identify input axes, same as in the `InputBundle`.
+/// AC: The type used to identify input actions, same as in the `InputBundle`.
+pub struct LockstepHarness<'a, 'b, AX, AC> {
+    server: LockstepServer,
+    connector: LoopbackConnector,
+    conditions: LinkConditions,
+    clock: LinkClock,
+    pool: ThreadPool,
+    step_duration: Duration,
+    clients: Vec<HarnessClient<'a, 'b>>,
+}
+
+struct HarnessClient<'a, 'b> {
+    world: World,
+    data: GameData<'a, 'b>,
+}
+
+impl<'a, 'b, AX, AC> LockstepHarness<'a, 'b, AX, AC>
+where
+    AX: Send + Sync + 'static,
+    AC: Send + Sync + 'static,
+{
+    /// Creates a harness without clients, whose links have the given conditions.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if the thread pool shared by the clients can't be created.
+    pub fn new(conditions: LinkConditions) -> Result<Self> {
+        let (listener, connector) = LoopbackListener::bind();
+        let clock = LinkClock::new();
+        let listener =
+            SimulatedListener::new(listener, conditions.clone(), clock.clone(), SERVER_SEED);
+        let pool = ThreadPoolBuilder::new()
+            .build()
+            .map(Arc::new)
+            .map_err(|err| Error::Core(err.description().to_string().into()))?;
+        Ok(LockstepHarness {
+            server: LockstepServer::new(listener),
+            connector,
+            conditions,
+            clock,
+            pool,
+            step_duration: Duration::from_millis(20),
+            clients: Vec::new(),
+        })
+    }
+
+    /// Configures the server, for example to use lobbies.
+    pub fn with_server<F>(mut self, configure: F) -> Self
+    where
+        F: FnOnce(LockstepServer) -> LockstepServer,
+    {
+        self.server = configure(self.server);
+        self
+    }
+
+    /// Sets the frame time of the clients reported by their `Time`. Defaults to 20 milliseconds.
+    pub fn with_step_duration(mut self, duration: Duration) -> Self {
+        self.step_duration = duration;
+        self
+    }
+
+    /// Adds a client whose game data is built by `build`, from the transport connecting it to
+    /// the server. Returns the index of the client.
+    pub fn add_client<F>(&mut self, build: F) -> Result<usize>
+    where
+        F: FnOnce(HarnessTransport) -> Result<GameDataBuilder<'a, 'b>>,
+    {
+        let transport = self.connector.connect().map_err(network_error)?;
+        let seed = self.clients.len() as u64 + 1;
+        let transport =
+            SimulatedTransport::new(transport, self.conditions.clone(), self.clock.clone(), seed);
+        let builder = build(transport)?;
+
+        let mut world = World::new();
+        world.add_resource(self.pool.clone());
+        world.add_resource(Errors::default());
+        world.add_resource(Time::default());
+        world.add_resource(SimulationTime::default());
+        let data = DataInit::build(builder, &mut world);
+        world
+            .res
+            .entry::<InputRelay<AX, AC>>()
+            .or_insert_with(InputRelay::new);
+        self.clients.push(HarnessClient { world, data });
+        Ok(self.clients.len() - 1)
+    }
+
+    /// Returns the server.
+    pub fn server(&self) -> &LockstepServer {
+        &self.server
+    }
+
+    /// Returns the world of a client.
+    pub fn world(&self, client: usize) -> &World {
+        &self.clients[client].world
+    }
+
+    /// Returns the world of a client, to create its entities.
+    pub fn world_mut(&mut self, client: usize) -> &mut World {
+        &mut self.clients[client].world
+    }
+
+    /// Returns the last tick a client simulated.
+    pub fn tick(&self, client: usize) -> u64 {
+        self.clients[client].world.read_resource::<SimulationTime>().tick()
+    }
+
+    /// Scripts an input event of a client, to be applied in the given tick.
+    ///
+    /// The event is sent on the next step, and applied in a later tick if the server confirmed
+    /// the frame of the given tick before receiving it, so leave the network enough steps to
+    /// deliver it.
+    pub fn script(&mut self, client: usize, tick: u64, event: NetInputEvent<AX, AC>) {
+        self.clients[client]
+            .world
+            .write_resource::<InputRelay<AX, AC>>()
+            .send_at(tick, event);
+    }
+
+    /// Ticks the server and runs one frame of every client.
+    pub fn step(&mut self) -> Result<()> {
+        self.step_until(None)
+    }
+
+    /// Steps until every client simulated `tick`, leaving the clients which got there alone.
+    ///
+    /// Clients don't simulate past `tick`, even when more ticks are due.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the clients don't get there within `max_steps` steps.
+    pub fn run_until(&mut self, tick: u64, max_steps: u64) -> Result<()> {
+        for _ in 0..max_steps {
+            if self.reached(tick) {
+                return Ok(());
+            }
+            self.step_until(Some(tick))?;
+        }
+        if self.reached(tick) {
+            return Ok(());
+        }
+        let ticks = (0..self.clients.len())
+            .map(|client| self.tick(client))
+            .collect::<Vec<_>>();
+        panic!(
+            "Clients did not reach tick {} within {} steps, they are at ticks {:?}",
+            tick, max_steps, ticks
+        );
+    }
+
+    /// Asserts that every client simulated the same tick and has the same components of type
+    /// `C`, matching the entities by their index.
+    pub fn assert_synced<C>(&self)
+    where
+        C: Component + PartialEq + Debug,
+    {
+        self.assert_same_tick();
+        let (entities, storage) = <(Entities, ReadStorage<C>)>::fetch(&self.clients[0].world.res);
+        let first = (&*entities, &storage)
+            .join()
+            .map(|(entity, component)| (entity.id(), component))
+            .collect::<Vec<_>>();
+        for (client, other) in self.clients.iter().enumerate().skip(1) {
+            let (entities, storage) = <(Entities, ReadStorage<C>)>::fetch(&other.world.res);
+            let components = (&*entities, &storage)
+                .join()
+                .map(|(entity, component)| (entity.id(), component))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                first,
+                components,
+                "Components of client {} differ from client 0 on tick {}",
+                client,
+                self.tick(0)
+            );
+        }
+    }
+
+    /// Asserts that every client simulated the same tick and has the same resource of type `R`.
+    pub fn assert_resource_synced<R>(&self)
+    where
+        R: Resource + PartialEq + Debug,
+    {
+        self.assert_same_tick();
+        let first = self.clients[0].world.read_resource::<R>();
+        for (client, other) in self.clients.iter().enumerate().skip(1) {
+            assert_eq!(
+                *first,
+                *other.world.read_resource::<R>(),
+                "Resource of client {} differs from client 0 on tick {}",
+                client,
+                self.tick(0)
+            );
+        }
+    }
+
+    fn reached(&self, tick: u64) -> bool {
+        (0..self.clients.len()).all(|client| self.tick(client) >= tick)
+    }
+
+    fn assert_same_tick(&self) {
+        let ticks = (0..self.clients.len())
+            .map(|client| self.tick(client))
+            .collect::<Vec<_>>();
+        assert!(
+            ticks.iter().all(|&tick| tick == ticks[0]),
+            "Clients are at different ticks: {:?}",
+            ticks
+        );
+    }
+
+    /// Ticks the server and runs one frame of every client which didn't simulate `until` yet.
+    fn step_until(&mut self, until: Option<u64>) -> Result<()> {
+        self.server.tick().map_err(network_error)?;
+        for client in &mut self.clients {
+            let tick = client.world.read_resource::<SimulationTime>().tick();
+            if until.map_or(false, |until| tick >= until) {
+                continue;
+            }
+            client.frame(until, self.step_duration);
+        }
+        self.clock.advance();
+        Ok(())
+    }
+}
+
+impl<'a, 'b> HarnessClient<'a, 'b> {
+    /// Runs one frame, stopping the simulation at `until`.
+    fn frame(&mut self, until: Option<u64>, duration: Duration) {
+        {
+            let mut time = self.world.write_resource::<Time>();
+            time.set_delta_time(duration);
+            time.increment_frame_number();
+        }
+
+        self.data.update(&self.world);
+        self.world
+            .write_resource::<SimulationTime>()
+            .accumulate(duration);
+        while self.next_tick(until) {
+            self.data.simulate(&self.world);
+        }
+        self.world.maintain();
+    }
+
+    /// Moves to the next tick which is due, unless `until` was simulated.
+    fn next_tick(&self, until: Option<u64>) -> bool {
+        let mut time = self.world.write_resource::<SimulationTime>();
+        until.map_or(true, |until| time.tick() < until) && time.next_tick()
+    }
+}
+
+fn network_error(err: network::Error) -> Error {
+    Error::Core(format!("Lockstep harness network error: {}", err).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::checksum::{Checksum, ChecksumBundle, WorldChecksum};
+    use ecs::prelude::{Builder, Read, System, VecStorage, WriteStorage};
+    use input::{InputBundle, InputSource, PlayerInputs, Replay, TickInputSystem};
+    use network::{LockstepBundle, NetworkEvent};
+    use std::hash::Hasher;
+    use {Application, SimpleState, SimpleTrans, StateData, Trans};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Position(i64);
+
+    impl Component for Position {
+        type Storage = VecStorage<Self>;
+    }
+
+    impl Checksum for Position {
+        fn checksum<H: Hasher>(&self, state: &mut H) {
+            self.0.checksum(state);
+        }
+    }
+
+    struct MoveSystem;
+
+    impl<'a> System<'a> for MoveSystem {
+        type SystemData = (
+            Read<'a, PlayerInputs<String, String>>,
+            WriteStorage<'a, Position>,
+        );
+
+        fn run(&mut self, (inputs, mut positions): Self::SystemData) {
+            for position in (&mut positions).join() {
+                for player in 0..2 {
+                    let value = inputs.axis_value(player, "move").unwrap_or(0.0);
+                    let step = value as i64 * (player as i64 + 1);
+                    position.0 = position.0.wrapping_mul(3).wrapping_add(step);
+                }
+            }
+        }
+    }
+
+    fn simulation(
+        builder: GameDataBuilder<'static, 'static>,
+        tick_input: TickInputSystem<String, String>,
+    ) -> Result<GameDataBuilder<'static, 'static>> {
+        builder
+            .with_sim(tick_input, "tick_input", &[])
+            .with_sim(MoveSystem, "move_system", &["tick_input"])
+            .with_sim_bundle(ChecksumBundle::new().with_interval(1).with::<Position>())
+    }
+
+    fn lockstep_client(
+        transport: HarnessTransport,
+        tick_input: TickInputSystem<String, String>,
+    ) -> Result<GameDataBuilder<'static, 'static>> {
+        let builder = GameDataBuilder::default()
+            .with_bundle(
+                LockstepBundle::<String, String, NetworkEvent>::new(transport)
+                    .with_input_delay(1)
+                    .with_state::<Position>(),
+            )?
+            .with_bundle(InputBundle::<String, String>::new().with_source(InputSource::Lockstep))?;
+        simulation(builder, tick_input)
+    }
+
+    fn client(transport: HarnessTransport) -> Result<GameDataBuilder<'static, 'static>> {
+        lockstep_client(transport, TickInputSystem::new())
+    }
+
+    fn recording_client(transport: HarnessTransport) -> Result<GameDataBuilder<'static, 'static>> {
+        lockstep_client(transport, TickInputSystem::new().with_recording())
+    }
+
+    fn add_player<F>(
+        harness: &mut LockstepHarness<'static, 'static, String, String>,
+        build: F,
+    ) -> usize
+    where
+        F: FnOnce(HarnessTransport) -> Result<GameDataBuilder<'static, 'static>>,
+    {
+        let client = harness.add_client(build).unwrap();
+        harness
+            .world_mut(client)
+            .create_entity()
+            .with(Position(0))
+            .build();
+        client
+    }
+
+    fn moved(value: f64) -> NetInputEvent<String, String> {
+        NetInputEvent::AxisMoved {
+            axis: "move".to_string(),
+            value,
+        }
+    }
+
+    /// Plays two players for 36 ticks, and checks that they agree on the position which follows
+    /// from their scripted moves.
+    fn play_scripted_match(conditions: LinkConditions) {
+        let mut harness = LockstepHarness::new(conditions).unwrap();
+        for _ in 0..2 {
+            add_player(&mut harness, client);
+        }
+        harness.script(0, 30, moved(1.0));
+        harness.script(1, 32, moved(-1.0));
+        harness.script(0, 34, moved(0.0));
+
+        harness.run_until(36, 10_000).unwrap();
+        harness.assert_synced::<Position>();
+        // Every tick: position = position * 9 + 3 * move0 + 2 * move1
+        let expected = (30..37).fold(0, |position, tick| {
+            let move0 = if tick < 34 { 1 } else { 0 };
+            let move1 = if tick < 32 { 0 } else { -1 };
+            position * 9 + 3 * move0 + 2 * move1
+        });
+        let positions = harness.world(0).read_storage::<Position>();
+        let position = (&positions).join().next().unwrap();
+        assert_eq!(*position, Position(expected));
+    }
+
+    #[test]
+    fn keeps_clients_in_sync_over_a_bad_network() {
+        play_scripted_match(LinkConditions {
+            latency: 2,
+            jitter: 2,
+            loss: 0.2,
+            reliable: true,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn keeps_clients_in_sync_when_messages_are_reordered() {
+        play_scripted_match(LinkConditions {
+            latency: 1,
+            jitter: 3,
+            reorder: 0.3,
+            reliable: true,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn late_client_catches_up_with_the_server() {
+        let mut harness = LockstepHarness::new(LinkConditions::default()).unwrap();
+        for _ in 0..2 {
+            add_player(&mut harness, client);
+        }
+        harness.script(0, 3, moved(1.0));
+        harness.run_until(100, 1_000).unwrap();
+
+        let late = add_player(&mut harness, client);
+        for _ in 0..50 {
+            harness.step().unwrap();
+        }
+        let behind = harness.server().frame() - harness.tick(late);
+        assert!(behind <= 4, "Late client is {} frames behind the server", behind);
+
+        let live = harness.server().frame();
+        harness.run_until(live, 10).unwrap();
+        harness.assert_synced::<Position>();
+    }
+
+    /// Plays a recording back, and checks the checksum of its last tick once it simulated it.
+    struct PlayBack {
+        last_tick: u64,
+        checksum: u64,
+        frames: u64,
+    }
+
+    impl<'a, 'b> SimpleState<'a, 'b> for PlayBack {
+        fn on_start(&mut self, data: StateData<GameData>) {
+            data.world.create_entity().with(Position(0)).build();
+        }
+
+        fn update(&mut self, data: &mut StateData<GameData>) -> SimpleTrans<'a, 'b> {
+            self.frames += 1;
+            assert!(self.frames < 1_000, "Replay did not reach tick {}", self.last_tick);
+            if data.world.read_resource::<SimulationTime>().tick() < self.last_tick {
+                return Trans::None;
+            }
+            let checksum = data.world.read_resource::<WorldChecksum>().get(self.last_tick);
+            assert_eq!(checksum, Some(self.checksum));
+            Trans::Quit
+        }
+    }
+
+    #[test]
+    fn replay_reproduces_the_recorded_match() {
+        let conditions = LinkConditions {
+            latency: 2,
+            jitter: 1,
+            loss: 0.1,
+            reliable: true,
+            ..Default::default()
+        };
+        let mut harness = LockstepHarness::new(conditions).unwrap();
+        add_player(&mut harness, recording_client);
+        add_player(&mut harness, client);
+        harness.script(0, 20, moved(1.0));
+        harness.script(1, 25, moved(-1.0));
+        harness.script(0, 30, moved(0.0));
+        harness.run_until(40, 10_000).unwrap();
+
+        let world = harness.world(0);
+        let replay = world.read_resource::<Replay<String, String>>().clone();
+        assert_eq!(replay.last_tick(), Some(40));
+        let checksum = world.read_resource::<WorldChecksum>().get(40).unwrap();
+
+        let input = InputBundle::<String, String>::new()
+            .with_replay(replay)
+            .fast_forward();
+        let game_data = simulation(
+            GameDataBuilder::default().with_bundle(input).unwrap(),
+            TickInputSystem::new(),
+        ).unwrap();
+        let state = PlayBack {
+            last_tick: 40,
+            checksum,
+            frames: 0,
+        };
+        let mut game = Application::build("assets/", state)
+            .unwrap()
+            .headless()
+            .build(game_data)
+            .unwrap();
+        game.run();
+    }
+}
@@ -68,12 +68,14 @@ pub extern crate amethyst_controls as controls;
 pub extern crate amethyst_core as core;
 pub extern crate amethyst_input as input;
 pub extern crate amethyst_locale as locale;
+pub extern crate amethyst_network as network;
 pub extern crate amethyst_renderer as renderer;
 pub extern crate amethyst_ui as ui;
 pub extern crate amethyst_utils as utils;
 pub extern crate winit;
 
 extern crate amethyst_ui;
+extern crate ctrlc;
 #[macro_use]
 extern crate derivative;
 extern crate fern;
@@ -82,13 +84,23 @@ extern crate log;
 extern crate amethyst_input;
 extern crate rayon;
 extern crate rustc_version_runtime;
+#[cfg(test)]
+extern crate serde;
+#[cfg(test)]
+#[macro_use]
+extern crate serde_derive;
 
-pub use self::app::{Application, ApplicationBuilder};
+pub use self::app::{Application, ApplicationBuilder, ErrorPolicy, ShutdownHook};
 pub use self::error::{Error, Result};
-pub use self::game_data::{DataInit, GameData, GameDataBuilder};
+pub use self::game_data::{
+    DataDispose, DataInit, DataSimulate, DataUpdate, GameData, GameDataBuilder, StateGameData,
+};
+#[cfg(feature = "harness")]
+pub use self::harness::{HarnessTransport, LockstepHarness};
 pub use self::logger::{start_logger, LevelFilter as LogLevelFilter, LoggerConfig};
 pub use self::state::{
     EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, StateMachine, Trans,
+    TransQueue,
 };
 pub use self::state_event::StateEvent;
 pub use core::shred;
@@ -100,6 +112,8 @@ pub mod prelude;
 mod app;
 mod error;
 mod game_data;
+#[cfg(any(test, feature = "harness"))]
+mod harness;
 mod logger;
 mod state;
 mod state_event;
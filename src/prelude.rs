@@ -3,6 +3,10 @@
 pub use app::{Application, ApplicationBuilder};
 pub use config::Config;
 pub use ecs::prelude::{Builder, World};
-pub use game_data::{DataInit, GameData, GameDataBuilder};
-pub use state::{EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, Trans};
+pub use game_data::{
+    DataDispose, DataInit, DataSimulate, DataUpdate, GameData, GameDataBuilder, StateGameData,
+};
+pub use state::{
+    EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, Trans, TransQueue,
+};
 pub use state_event::StateEvent;
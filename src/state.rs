@@ -2,6 +2,7 @@
 
 use amethyst_input::is_close_requested;
 use ecs::prelude::World;
+use game_data::{DataDispose, DataSimulate, DataUpdate, StateGameData};
 use {GameData, StateEvent};
 
 /// State data encapsulates the data sent to all state functions from the application main loop.
@@ -34,14 +35,67 @@ pub enum Trans<T, E> {
     /// Remove the active state and resume the next state on the stack or stop
     /// if there are none.
     Pop,
+    /// Remove the given number of states from the top of the stack and resume the next state or
+    /// stop if there are none.
+    PopN(usize),
     /// Pause the active state and push a new state onto the stack.
     Push(Box<State<T, E>>),
     /// Remove the current state on the stack and insert a different one.
     Switch(Box<State<T, E>>),
+    /// Remove all states and push the given ones in order, the last one becoming active.
+    /// An empty stack shuts down the engine.
+    Replace(Vec<Box<State<T, E>>>),
+    /// Apply the given transitions in order.
+    Sequence(Vec<Trans<T, E>>),
     /// Stop and remove all states and shut down the engine.
     Quit,
 }
 
+/// Transitions requested by systems, which can't return a `Trans` like states do.
+///
+/// The `Application` applies the queued transitions in order once the states and their game
+/// data were updated. States aren't `Send`, so the queue holds functions creating the
+/// transitions instead.
+///
+/// ### Type parameters:
+///
+/// - `T`: The game data of the application
+/// - `E`: The custom event type of the application
+pub struct TransQueue<T, E> {
+    queue: Vec<Box<Fn() -> Trans<T, E> + Send + Sync>>,
+}
+
+impl<T, E> Default for TransQueue<T, E> {
+    fn default() -> Self {
+        TransQueue { queue: Vec::new() }
+    }
+}
+
+impl<T, E> TransQueue<T, E> {
+    /// Creates an empty queue.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Queues the transition created by `trans`.
+    pub fn push<F>(&mut self, trans: F)
+    where
+        F: Fn() -> Trans<T, E> + Send + Sync + 'static,
+    {
+        self.queue.push(Box::new(trans));
+    }
+
+    /// Returns true if no transition is queued.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Creates the queued transitions and empties the queue.
+    pub fn take(&mut self) -> Vec<Trans<T, E>> {
+        self.queue.drain(..).map(|trans| trans()).collect()
+    }
+}
+
 /// An empty `Trans`. Made to be used with `EmptyState`.
 pub type EmptyTrans = Trans<(), ()>;
 
@@ -54,6 +108,16 @@ pub trait State<T, E: Send + Sync + 'static> {
     /// Executed when the game state begins.
     fn on_start(&mut self, _data: StateData<T>) {}
 
+    /// Builds the systems which only run while this state is the active one.
+    ///
+    /// Called right after `on_start`. The state machine updates the returned game data after
+    /// every update of the state and runs its simulation stage on every tick. It is paused while
+    /// another state is pushed on top, and disposed after `on_stop`. Any game data implementing
+    /// `StateGameData` can be returned, for example a `GameData` built by a `GameDataBuilder`.
+    fn dispatcher(&mut self, _world: &mut World) -> Option<Box<StateGameData>> {
+        None
+    }
+
     /// Executed when the game state exits.
     fn on_stop(&mut self, _data: StateData<T>) {}
 
@@ -74,6 +138,14 @@ pub trait State<T, E: Send + Sync + 'static> {
         Trans::None
     }
 
+    /// Executed at the same intervals as `fixed_update` for every state in the stack, including
+    /// paused ones, before the active state's `fixed_update`.
+    fn shadow_fixed_update(&mut self, _data: StateData<T>) {}
+
+    /// Executed on every frame for every state in the stack, including paused ones, before the
+    /// active state's `update`. Use it to keep a state animating under an overlay.
+    fn shadow_update(&mut self, _data: StateData<T>) {}
+
     /// Executed on every frame immediately, as fast as the engine will allow (taking into account the frame rate limit).
     fn update(&mut self, _data: StateData<T>) -> Trans<T, E> {
         Trans::None
@@ -85,6 +157,11 @@ pub trait EmptyState {
     /// Executed when the game state begins.
     fn on_start(&mut self, _data: StateData<()>) {}
 
+    /// Builds the systems which only run while this state is the active one.
+    fn dispatcher(&mut self, _world: &mut World) -> Option<Box<StateGameData>> {
+        None
+    }
+
     /// Executed when the game state exits.
     fn on_stop(&mut self, _data: StateData<()>) {}
 
@@ -113,6 +190,13 @@ pub trait EmptyState {
         Trans::None
     }
 
+    /// Executed at the same intervals as `fixed_update` for every state in the stack, including
+    /// paused ones.
+    fn shadow_fixed_update(&mut self, _data: StateData<()>) {}
+
+    /// Executed on every frame for every state in the stack, including paused ones.
+    fn shadow_update(&mut self, _data: StateData<()>) {}
+
     /// Executed on every frame immediately, as fast as the engine will allow (taking into account the frame rate limit).
     fn update(&mut self, _data: StateData<()>) -> EmptyTrans {
         Trans::None
@@ -125,6 +209,11 @@ impl<T: EmptyState> State<(), ()> for T {
         self.on_start(data)
     }
 
+    /// Builds the systems which only run while this state is the active one.
+    fn dispatcher(&mut self, world: &mut World) -> Option<Box<StateGameData>> {
+        self.dispatcher(world)
+    }
+
     /// Executed when the game state exits.
     fn on_stop(&mut self, data: StateData<()>) {
         self.on_stop(data)
@@ -151,6 +240,17 @@ impl<T: EmptyState> State<(), ()> for T {
         self.fixed_update(data)
     }
 
+    /// Executed at the same intervals as `fixed_update` for every state in the stack, including
+    /// paused ones.
+    fn shadow_fixed_update(&mut self, data: StateData<()>) {
+        self.shadow_fixed_update(data)
+    }
+
+    /// Executed on every frame for every state in the stack, including paused ones.
+    fn shadow_update(&mut self, data: StateData<()>) {
+        self.shadow_update(data)
+    }
+
     /// Executed on every frame immediately, as fast as the engine will allow (taking into account the frame rate limit).
     fn update(&mut self, data: StateData<()>) -> EmptyTrans {
         self.update(data)
@@ -162,6 +262,11 @@ pub trait SimpleState<'a, 'b> {
     /// Executed when the game state begins.
     fn on_start(&mut self, _data: StateData<GameData>) {}
 
+    /// Builds the systems which only run while this state is the active one.
+    fn dispatcher(&mut self, _world: &mut World) -> Option<Box<StateGameData>> {
+        None
+    }
+
     /// Executed when the game state exits.
     fn on_stop(&mut self, _data: StateData<GameData>) {}
 
@@ -194,6 +299,13 @@ pub trait SimpleState<'a, 'b> {
         Trans::None
     }
 
+    /// Executed at the same intervals as `fixed_update` for every state in the stack, including
+    /// paused ones.
+    fn shadow_fixed_update(&mut self, _data: StateData<GameData>) {}
+
+    /// Executed on every frame for every state in the stack, including paused ones.
+    fn shadow_update(&mut self, _data: StateData<GameData>) {}
+
     /// Executed on every frame immediately, as fast as the engine will allow (taking into account the frame rate limit).
     fn update(&mut self, _data: &mut StateData<GameData>) -> SimpleTrans<'a, 'b> {
         Trans::None
@@ -207,6 +319,11 @@ impl<'a, 'b, T: SimpleState<'a, 'b>> State<GameData<'a, 'b>, ()> for T {
         self.on_start(data)
     }
 
+    /// Builds the systems which only run while this state is the active one.
+    fn dispatcher(&mut self, world: &mut World) -> Option<Box<StateGameData>> {
+        self.dispatcher(world)
+    }
+
     /// Executed when the game state exits.
     fn on_stop(&mut self, data: StateData<GameData>) {
         self.on_stop(data)
@@ -237,6 +354,17 @@ impl<'a, 'b, T: SimpleState<'a, 'b>> State<GameData<'a, 'b>, ()> for T {
         self.fixed_update(data)
     }
 
+    /// Executed at the same intervals as `fixed_update` for every state in the stack, including
+    /// paused ones.
+    fn shadow_fixed_update(&mut self, data: StateData<GameData>) {
+        self.shadow_fixed_update(data)
+    }
+
+    /// Executed on every frame for every state in the stack, including paused ones.
+    fn shadow_update(&mut self, data: StateData<GameData>) {
+        self.shadow_update(data)
+    }
+
     /// Executed on every frame immediately, as fast as the engine will allow (taking into account the frame rate limit).
     fn update(&mut self, mut data: StateData<GameData>) -> SimpleTrans<'a, 'b> {
         let r = self.update(&mut data);
@@ -246,12 +374,17 @@ impl<'a, 'b, T: SimpleState<'a, 'b>> State<GameData<'a, 'b>, ()> for T {
 }
 
 /// A simple stack-based state machine (pushdown automaton).
+///
+/// Next to every state of the stack, the state machine keeps the game data the state built with
+/// `State::dispatcher`, and runs the one of the active state.
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct StateMachine<'a, T, E> {
     running: bool,
     #[derivative(Debug = "ignore")]
     state_stack: Vec<Box<State<T, E> + 'a>>,
+    #[derivative(Debug = "ignore")]
+    dispatchers: Vec<Option<Box<StateGameData>>>,
 }
 
 impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
@@ -260,6 +393,7 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
         StateMachine {
             running: false,
             state_stack: vec![Box::new(initial_state)],
+            dispatchers: Vec::new(),
         }
     }
 
@@ -274,8 +408,7 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
     /// Panics if no states are present in the stack.
     pub fn start(&mut self, data: StateData<T>) {
         if !self.running {
-            let state = self.state_stack.last_mut().unwrap();
-            state.on_start(data);
+            self.start_active(data);
             self.running = true;
         }
     }
@@ -293,10 +426,14 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
         }
     }
 
-    /// Updates the currently active state at a steady, fixed interval.
+    /// Updates the currently active state at a steady, fixed interval, after the shadow fixed
+    /// update of every state in the stack.
     pub fn fixed_update(&mut self, data: StateData<T>) {
         let StateData { world, data } = data;
         if self.running {
+            for state in &mut self.state_stack {
+                state.shadow_fixed_update(StateData { world, data });
+            }
             let trans = match self.state_stack.last_mut() {
                 Some(state) => state.fixed_update(StateData { world, data }),
                 None => Trans::None,
@@ -306,28 +443,52 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
         }
     }
 
-    /// Updates the currently active state immediately.
+    /// Updates the currently active state immediately, after the shadow update of every state in
+    /// the stack.
     pub fn update(&mut self, data: StateData<T>) {
         let StateData { world, data } = data;
         if self.running {
+            for state in &mut self.state_stack {
+                state.shadow_update(StateData { world, data });
+            }
             let trans = match self.state_stack.last_mut() {
                 Some(state) => state.update(StateData { world, data }),
                 None => Trans::None,
             };
+            if let Some(&mut Some(ref mut dispatcher)) = self.dispatchers.last_mut() {
+                dispatcher.update(world);
+            }
 
             self.transition(trans, StateData { world, data });
         }
     }
 
+    /// Runs a tick of the simulation stage of the active state's game data.
+    pub fn simulate(&mut self, world: &World) {
+        if self.running {
+            if let Some(&mut Some(ref mut dispatcher)) = self.dispatchers.last_mut() {
+                dispatcher.simulate(world);
+            }
+        }
+    }
+
     /// Performs a state transition, if requested by either update() or
     /// fixed_update().
-    fn transition(&mut self, request: Trans<T, E>, data: StateData<T>) {
+    pub(crate) fn transition(&mut self, request: Trans<T, E>, data: StateData<T>) {
         if self.running {
             match request {
                 Trans::None => (),
-                Trans::Pop => self.pop(data),
+                Trans::Pop => self.pop(1, data),
+                Trans::PopN(count) => self.pop(count, data),
                 Trans::Push(state) => self.push(state, data),
                 Trans::Switch(state) => self.switch(state, data),
+                Trans::Replace(states) => self.replace(states, data),
+                Trans::Sequence(requests) => {
+                    let StateData { world, data } = data;
+                    for request in requests {
+                        self.transition(request, StateData { world, data });
+                    }
+                }
                 Trans::Quit => self.stop(data),
             }
         }
@@ -337,13 +498,10 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
     fn switch(&mut self, state: Box<State<T, E>>, data: StateData<T>) {
         if self.running {
             let StateData { world, data } = data;
-            if let Some(mut state) = self.state_stack.pop() {
-                state.on_stop(StateData { world, data });
-            }
+            self.stop_active(StateData { world, data });
 
             self.state_stack.push(state);
-            let state = self.state_stack.last_mut().unwrap();
-            state.on_start(StateData { world, data });
+            self.start_active(StateData { world, data });
         }
     }
 
@@ -356,18 +514,17 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
             }
 
             self.state_stack.push(state);
-            let state = self.state_stack.last_mut().unwrap();
-            state.on_start(StateData { world, data });
+            self.start_active(StateData { world, data });
         }
     }
 
-    /// Stops and removes the active state and un-pauses the next state on the
+    /// Stops and removes the `count` top states and un-pauses the next state on the
     /// stack (if any).
-    fn pop(&mut self, data: StateData<T>) {
-        if self.running {
+    fn pop(&mut self, count: usize, data: StateData<T>) {
+        if self.running && count > 0 {
             let StateData { world, data } = data;
-            if let Some(mut state) = self.state_stack.pop() {
-                state.on_stop(StateData { world, data });
+            for _ in 0..count.min(self.state_stack.len()) {
+                self.stop_active(StateData { world, data });
             }
 
             if let Some(state) = self.state_stack.last_mut() {
@@ -378,26 +535,127 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
         }
     }
 
+    /// Stops and removes every state, then pushes the given states in order.
+    fn replace(&mut self, states: Vec<Box<State<T, E>>>, data: StateData<T>) {
+        if self.running {
+            let StateData { world, data } = data;
+            while !self.state_stack.is_empty() {
+                self.stop_active(StateData { world, data });
+            }
+            for state in states {
+                self.push(state, StateData { world, data });
+            }
+            if self.state_stack.is_empty() {
+                self.running = false;
+            }
+        }
+    }
+
     /// Shuts the state machine down.
     pub(crate) fn stop(&mut self, data: StateData<T>) {
         if self.running {
             let StateData { world, data } = data;
-            while let Some(mut state) = self.state_stack.pop() {
-                state.on_stop(StateData { world, data });
+            while !self.state_stack.is_empty() {
+                self.stop_active(StateData { world, data });
             }
 
             self.running = false;
         }
     }
+
+    /// Starts the state on top of the stack and builds its game data.
+    fn start_active(&mut self, data: StateData<T>) {
+        let StateData { world, data } = data;
+        let state = self.state_stack.last_mut().unwrap();
+        state.on_start(StateData { world, data });
+        self.dispatchers.push(state.dispatcher(world));
+    }
+
+    /// Stops and removes the state on top of the stack, then disposes of its game data.
+    fn stop_active(&mut self, data: StateData<T>) {
+        if let Some(mut state) = self.state_stack.pop() {
+            let StateData { world, data } = data;
+            state.on_stop(StateData { world, data });
+            if let Some(Some(mut dispatcher)) = self.dispatchers.pop() {
+                dispatcher.dispose(world);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ecs::prelude::{System, World, Write};
+    use game_data::DataInit;
+    use rayon::ThreadPoolBuilder;
+    use std::sync::Arc;
+    use {GameData, GameDataBuilder, StateEvent};
 
     struct State1(u8);
     struct State2;
 
+    #[derive(Default)]
+    struct Frames(u32);
+
+    struct CountFrames;
+
+    impl<'a> System<'a> for CountFrames {
+        type SystemData = Write<'a, Frames>;
+
+        fn run(&mut self, mut frames: Self::SystemData) {
+            frames.0 += 1;
+        }
+    }
+
+    struct Playing;
+    struct Paused;
+
+    impl State<(), ()> for Playing {
+        fn dispatcher(&mut self, world: &mut World) -> Option<Box<StateGameData>> {
+            let builder = GameDataBuilder::default().with(CountFrames, "count_frames", &[]);
+            let game_data: GameData = builder.build(world);
+            Some(Box::new(game_data))
+        }
+
+        fn handle_event(&mut self, _: StateData<()>, _: StateEvent<()>) -> Trans<(), ()> {
+            Trans::Push(Box::new(Paused))
+        }
+    }
+
+    impl State<(), ()> for Paused {
+        fn handle_event(&mut self, _: StateData<()>, _: StateEvent<()>) -> Trans<(), ()> {
+            Trans::Pop
+        }
+    }
+
+    #[derive(Default)]
+    struct Counts {
+        started: u32,
+        stopped: u32,
+        shadowed: u32,
+    }
+
+    struct Counted;
+
+    impl State<(), ()> for Counted {
+        fn on_start(&mut self, data: StateData<()>) {
+            data.world.write_resource::<Counts>().started += 1;
+        }
+
+        fn on_stop(&mut self, data: StateData<()>) {
+            data.world.write_resource::<Counts>().stopped += 1;
+        }
+
+        fn shadow_update(&mut self, data: StateData<()>) {
+            data.world.write_resource::<Counts>().shadowed += 1;
+        }
+    }
+
+    fn counted() -> Box<State<(), ()>> {
+        Box::new(Counted)
+    }
+
     impl State<(), ()> for State1 {
         fn update(&mut self, _: StateData<()>) -> Trans<(), ()> {
             if self.0 > 0 {
@@ -432,4 +690,60 @@ mod tests {
         sm.update(StateData::new(&mut world, &mut ()));
         assert!(!sm.is_running());
     }
+
+    #[test]
+    fn replace_pop_n_sequence() {
+        let mut world = World::new();
+        world.add_resource(Counts::default());
+
+        let mut sm = StateMachine::new(Counted);
+        sm.start(StateData::new(&mut world, &mut ()));
+        let push_two = Trans::Sequence(vec![Trans::Push(counted()), Trans::Push(counted())]);
+        sm.transition(push_two, StateData::new(&mut world, &mut ()));
+        assert_eq!(world.read_resource::<Counts>().started, 3);
+
+        sm.transition(Trans::PopN(2), StateData::new(&mut world, &mut ()));
+        assert_eq!(world.read_resource::<Counts>().stopped, 2);
+        assert!(sm.is_running());
+
+        let replace = Trans::Replace(vec![counted(), counted()]);
+        sm.transition(replace, StateData::new(&mut world, &mut ()));
+        assert_eq!(world.read_resource::<Counts>().started, 5);
+        assert_eq!(world.read_resource::<Counts>().stopped, 3);
+
+        sm.transition(Trans::PopN(5), StateData::new(&mut world, &mut ()));
+        assert_eq!(world.read_resource::<Counts>().stopped, 5);
+        assert!(!sm.is_running());
+    }
+
+    #[test]
+    fn shadow_updates_paused_states() {
+        let mut world = World::new();
+        world.add_resource(Counts::default());
+
+        let mut sm = StateMachine::new(Counted);
+        sm.start(StateData::new(&mut world, &mut ()));
+        sm.transition(Trans::Push(counted()), StateData::new(&mut world, &mut ()));
+        sm.update(StateData::new(&mut world, &mut ()));
+        assert_eq!(world.read_resource::<Counts>().shadowed, 2);
+    }
+
+    #[test]
+    fn runs_the_dispatcher_of_the_active_state() {
+        let mut world = World::new();
+        world.add_resource(Arc::new(ThreadPoolBuilder::new().build().unwrap()));
+
+        let mut sm = StateMachine::new(Playing);
+        sm.start(StateData::new(&mut world, &mut ()));
+        sm.update(StateData::new(&mut world, &mut ()));
+        assert_eq!(world.read_resource::<Frames>().0, 1);
+
+        sm.handle_event(StateData::new(&mut world, &mut ()), StateEvent::Custom(()));
+        sm.update(StateData::new(&mut world, &mut ()));
+        assert_eq!(world.read_resource::<Frames>().0, 1);
+
+        sm.handle_event(StateData::new(&mut world, &mut ()), StateEvent::Custom(()));
+        sm.update(StateData::new(&mut world, &mut ()));
+        assert_eq!(world.read_resource::<Frames>().0, 2);
+    }
 }
@@ -1,3 +1,4 @@
+use core::SystemError;
 use renderer::Event;
 use ui::UiEvent;
 
@@ -10,4 +11,6 @@ pub enum StateEvent<E: Send + Sync + 'static> {
     /// Custom user events.
     /// To receive events from there, you need to write `E` instances into EventChannel<E>
     Custom(E),
+    /// Errors reported by systems, delivered when the application uses `ErrorPolicy::Escalate`.
+    Error(SystemError),
 }